@@ -99,14 +99,27 @@ pub fn batch_inverse<F: Field>(v: &mut [F]) {
     v[0] = prod;
 }
 
-pub fn as_bytes_vec<F: Field>(v: &[F]) -> Vec<u8> {
-    let mut buffer = vec![0; F::SIZE * v.len()];
-    let mut cnt = 0;
-    for i in v.iter() {
-        i.serialize_into(&mut buffer[cnt..cnt + F::SIZE]);
-        cnt += F::SIZE;
-    }
-    buffer
+// bytes of a plaintext in transcripts and merkle leaves: its slot values, little endian
+pub fn plaintext_size(encoder: &BFVEncoder) -> usize {
+    encoder.get_slot_count() * std::mem::size_of::<u64>()
+}
+
+pub fn as_bytes_vec(v: &[Plaintext], encoder: &BFVEncoder) -> Vec<u8> {
+    v.iter()
+        .flat_map(|x| x.get_value(encoder))
+        .flat_map(|x| x.to_le_bytes())
+        .collect()
+}
+
+// inverse of as_bytes_vec for one plaintext, slot values reduced so any bytes decode
+pub fn from_bytes(buffer: &[u8], encoder: &BFVEncoder) -> Plaintext {
+    assert_eq!(buffer.len(), plaintext_size(encoder));
+    let modulus = encoder.get_params().get_plain_modulus().value();
+    let values = buffer
+        .chunks(std::mem::size_of::<u64>())
+        .map(|x| u64::from_le_bytes(x.try_into().unwrap()) % modulus)
+        .collect::<Vec<_>>();
+    encoder.encode_unsigned(&values).unwrap()
 }
 
 // #[cfg(test)]
@@ -1,3 +1,6 @@
 pub mod field;
 pub mod mul_group;
 pub mod poly;
+
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
@@ -1,22 +1,50 @@
 use std::rc::Rc;
 
-use crate::field::FftField;
+use seal_fhe::{BFVEncoder, Plaintext};
 
+type F = Plaintext;
+
+pub fn mul_mod(a: u64, b: u64, modulus: u64) -> u64 {
+    (a as u128 * b as u128 % modulus as u128) as u64
+}
+
+pub fn pow_mod(mut base: u64, mut exp: u64, modulus: u64) -> u64 {
+    let mut acc = 1 % modulus;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            acc = mul_mod(acc, base, modulus);
+        }
+        base = mul_mod(base, base, modulus);
+        exp >>= 1;
+    }
+    acc
+}
+
+// the multiplicative subgroup of order 2^log_order modulo the plain modulus. its elements
+// are scalars, applied to every slot of a plaintext alike
 #[derive(Debug, Clone)]
-pub struct Radix2Group<F: FftField> {
+pub struct Radix2Group {
     log_order: u32,
-    omega: F,
-    elements: Rc<Vec<F>>,
+    modulus: u64,
+    omega: u64,
+    elements: Rc<Vec<u64>>,
 }
 
-impl<F: FftField> Radix2Group<F> {
-    pub fn new(log_order: u32) -> Self {
-        let omega = F::ROOT_OF_UNITY.exp(1usize << (F::LOG_ORDER - log_order));
-        let elements = std::iter::successors(Some(F::one()), |&last| Some(last * omega))
+impl Radix2Group {
+    pub fn new(log_order: u32, modulus: u64) -> Self {
+        assert_eq!((modulus - 1) % (1 << log_order), 0);
+        // a non-residue has the full two-adic order, so each subgroup of the chain is
+        // the square of the previous one
+        let non_residue = (2..modulus)
+            .find(|&x| pow_mod(x, (modulus - 1) / 2, modulus) == modulus - 1)
+            .unwrap();
+        let omega = pow_mod(non_residue, (modulus - 1) >> log_order, modulus);
+        let elements = std::iter::successors(Some(1), |&last| Some(mul_mod(last, omega, modulus)))
             .take(1 << log_order)
             .collect();
         Radix2Group {
             log_order,
+            modulus,
             omega,
             elements: Rc::new(elements),
         }
@@ -26,139 +54,178 @@ impl<F: FftField> Radix2Group<F> {
         1 << self.log_order
     }
 
-    pub fn element_at(&self, index: usize) -> F {
+    pub fn modulus(&self) -> u64 {
+        self.modulus
+    }
+
+    pub fn element_at(&self, index: usize) -> u64 {
         self.elements[index]
     }
 
-    pub fn element_inv_at(&self, index: usize) -> F {
+    pub fn element_inv_at(&self, index: usize) -> u64 {
         if index == 0 {
-            F::one()
+            1
         } else {
             self.elements[self.size() - index]
         }
     }
 
-    pub fn exp(&self, index: usize) -> Radix2Group<F> {
+    pub fn exp(&self, index: usize) -> Radix2Group {
         assert_eq!(index & (index - 1), 0);
-        Radix2Group::new(self.log_order - index.ilog2())
+        Radix2Group::new(self.log_order - index.ilog2(), self.modulus)
     }
 
     fn batch_bit_reverse(log_n: usize) -> Vec<usize> {
         let n = 1 << log_n;
         let mut res = (0..n).into_iter().map(|_| 0).collect::<Vec<usize>>();
-        for i in 0..n {
+        for i in 1..n {
             res[i] = (res[i >> 1] >> 1) | ((i & 1) << (log_n - 1));
         }
         res
     }
 
-    fn _fft(coeff: &mut Vec<F>, omega: F) {
+    // one transform per slot, on the decoded slot values
+    fn _fft(&self, coeff: &mut Vec<Vec<u64>>, omega: u64) {
         let n = coeff.len();
         let log_n = n.ilog2() as usize;
         let rank = Self::batch_bit_reverse(log_n);
         for i in 0..n {
             if i < rank[i] {
-                (coeff[i], coeff[rank[i]]) = (coeff[rank[i]], coeff[i]);
+                coeff.swap(i, rank[i]);
             }
         }
+        let modulus = self.modulus;
         let mut log_m = 0usize;
         for _i in 0..log_n {
-            let w_m = omega.exp(n >> (log_m + 1));
+            let w_m = pow_mod(omega, (n >> (log_m + 1)) as u64, modulus);
             let m = 1 << log_m;
             for j in (0..n).step_by(m * 2) {
-                let mut w = F::from(1);
+                let mut w = 1;
                 for k in 0..m {
-                    let t = w * coeff[j + k + m];
-                    coeff[j + k + m] = coeff[j + k] - t;
-                    coeff[j + k] += t;
-                    w *= w_m;
+                    let (low, high) = coeff.split_at_mut(j + k + m);
+                    for (x, y) in low[j + k].iter_mut().zip(high[0].iter_mut()) {
+                        let t = mul_mod(w, *y, modulus);
+                        *y = (*x + modulus - t) % modulus;
+                        *x = (*x + t) % modulus;
+                    }
+                    w = mul_mod(w, w_m, modulus);
                 }
             }
             log_m += 1;
         }
     }
 
-    pub fn fft(&self, coeff: Vec<F::BaseField>) -> Vec<F> {
-        let mut coeff = coeff.into_iter().map(|x| F::from(x)).collect::<Vec<_>>();
-        let padding_zero = self.size() - coeff.len();
-        for _ in 0..padding_zero {
-            coeff.push(F::zero());
-        }
-        Self::_fft(&mut coeff, self.omega);
-        coeff
+    pub fn fft(&self, coeff: Vec<F>, encoder: &BFVEncoder) -> Vec<F> {
+        assert!(coeff.len() <= self.size());
+        let mut values = coeff
+            .iter()
+            .map(|x| x.get_value(encoder))
+            .collect::<Vec<_>>();
+        values.resize(self.size(), vec![0; encoder.get_slot_count()]);
+        self._fft(&mut values, self.omega);
+        values
+            .iter()
+            .map(|x| encoder.encode_unsigned(x).unwrap())
+            .collect()
     }
 
-    pub fn ifft(&self, mut evals: Vec<F>) -> Vec<F> {
+    pub fn ifft(&self, evals: Vec<F>, encoder: &BFVEncoder) -> Vec<F> {
         assert_eq!(self.size(), evals.len());
-        Self::_fft(&mut evals, self.omega.exp((1 << self.size()) - 1));
-        let t = F::from(self.size() as u32).inv().unwrap();
-        evals.iter_mut().for_each(|x| *x *= t);
-        evals
+        let mut values = evals
+            .iter()
+            .map(|x| x.get_value(encoder))
+            .collect::<Vec<_>>();
+        self._fft(&mut values, self.element_inv_at(1));
+        let t = pow_mod(self.size() as u64, self.modulus - 2, self.modulus);
+        values
+            .iter()
+            .map(|x| {
+                let x = x
+                    .iter()
+                    .map(|&v| mul_mod(v, t, self.modulus))
+                    .collect::<Vec<_>>();
+                encoder.encode_unsigned(&x).unwrap()
+            })
+            .collect()
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-
-//     use crate::field::{goldilocks64::Goldilocks64, Field};
-//     use super::*;
-//     #[test]
-//     fn fft_and_ifft() {
-//         let mut a = vec![];
-//         let mut b = vec![];
-//         let mut rng = rand::thread_rng();
-//         for _i in 0..16 {
-//             a.push(Goldilocks64::random(&mut rng));
-//             b.push(Goldilocks64::random(&mut rng));
-//         }
-//         for _i in 16..32 {
-//             a.push(Goldilocks64::zero());
-//             b.push(Goldilocks64::zero());
-//         }
-//         let mul_group = Radix2Group::new(5);
-//         let mut fft_a = mul_group.fft(a.clone());
-//         let fft_b = mul_group.fft(b.clone());
-//         for i in 0..fft_a.len() {
-//             fft_a[i] *= fft_b[i];
-//         }
-//         let fft_a_times_b = mul_group.ifft(fft_a);
-//         let mut a_times_b = vec![];
-//         for _i in 0..32 {
-//             a_times_b.push(Goldilocks64::zero());
-//         }
-//         for i in 0..16usize {
-//             for j in 0..16usize {
-//                 a_times_b[i + j] += a[i] * b[j];
-//             }
-//         }
-//         assert_eq!(fft_a_times_b, a_times_b);
-//         let b = mul_group.fft(a.clone());
-//         let c = mul_group.ifft(b);
-//         assert_eq!(a, c);
-//     }
-
-//     #[test]
-//     fn elements() {
-//         let coset = Radix2Group::<Goldilocks64>::new(5);
-//         assert_eq!(coset.element_at(0), Goldilocks64::one());
-//         assert_eq!(coset.element_inv_at(0), Goldilocks64::one());
-//         let omega = coset.omega;
-//         for i in 0..30 {
-//             assert_eq!(coset.element_at(i) * omega, coset.element_at(i + 1));
-//             assert_eq!(
-//                 coset.element_inv_at(i) * omega.inv().unwrap(),
-//                 coset.element_inv_at(i + 1)
-//             );
-//         }
-//     }
-
-//     #[test]
-//     fn exp() {
-//         let coset = Radix2Group::<Goldilocks64>::new(5);
-//         let coset_square = coset.exp(2);
-//         for (idx, i) in coset_square.elements.iter().enumerate() {
-//             assert_eq!(*i, coset.element_at(idx).exp(2));
-//             assert_eq!(*i, coset.element_at(idx + coset_square.size()).exp(2));
-//         }
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use seal_fhe::{BFVEncoder, Plaintext};
+
+    use crate::test_utils::gen_params_n_ctx;
+
+    use super::*;
+
+    #[test]
+    fn fft_and_ifft() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+        let modulus = params.get_plain_modulus().value();
+        let zero = Plaintext::from_int(0, &encoder);
+        let mut a = vec![];
+        let mut b = vec![];
+        for _i in 0..16 {
+            a.push(Plaintext::random_pt(&encoder));
+            b.push(Plaintext::random_pt(&encoder));
+        }
+        for _i in 16..32 {
+            a.push(zero.clone());
+            b.push(zero.clone());
+        }
+        let mul_group = Radix2Group::new(5, modulus);
+        let fft_a = mul_group.fft(a.clone(), &encoder);
+        let fft_b = mul_group.fft(b.clone(), &encoder);
+        let fft_a = fft_a
+            .iter()
+            .zip(fft_b.iter())
+            .map(|(x, y)| Plaintext::mult(x, y, &encoder))
+            .collect();
+        let fft_a_times_b = mul_group.ifft(fft_a, &encoder);
+        let mut a_times_b = vec![zero.clone(); 32];
+        for i in 0..16usize {
+            for j in 0..16usize {
+                let t = Plaintext::mult(&a[i], &b[j], &encoder);
+                a_times_b[i + j] = Plaintext::add(&a_times_b[i + j], &t, &encoder);
+            }
+        }
+        assert_eq!(fft_a_times_b, a_times_b);
+        let b = mul_group.fft(a.clone(), &encoder);
+        let c = mul_group.ifft(b, &encoder);
+        assert_eq!(a, c);
+    }
+
+    #[test]
+    fn elements() {
+        let (params, _) = gen_params_n_ctx();
+        let modulus = params.get_plain_modulus().value();
+        let coset = Radix2Group::new(5, modulus);
+        assert_eq!(coset.element_at(0), 1);
+        assert_eq!(coset.element_inv_at(0), 1);
+        let omega = coset.omega;
+        let omega_inv = pow_mod(omega, modulus - 2, modulus);
+        for i in 0..30 {
+            assert_eq!(mul_mod(coset.element_at(i), omega, modulus), coset.element_at(i + 1));
+            assert_eq!(
+                mul_mod(coset.element_inv_at(i), omega_inv, modulus),
+                coset.element_inv_at(i + 1)
+            );
+        }
+    }
+
+    #[test]
+    fn exp() {
+        let (params, _) = gen_params_n_ctx();
+        let modulus = params.get_plain_modulus().value();
+        let coset = Radix2Group::new(5, modulus);
+        let coset_square = coset.exp(2);
+        for (idx, i) in coset_square.elements.iter().enumerate() {
+            assert_eq!(*i, pow_mod(coset.element_at(idx), 2, modulus));
+            assert_eq!(
+                *i,
+                pow_mod(coset.element_at(idx + coset_square.size()), 2, modulus)
+            );
+        }
+    }
+}
@@ -112,33 +112,19 @@ mod tests {
     use rand::thread_rng;
 
     use seal_fhe::{
-        Asym, BFVEncoder, BfvEncryptionParametersBuilder, CoefficientModulus, Context, Decryptor, EncryptionParameters, Encryptor, KeyGenerator, PlainModulus, Plaintext, SecurityLevel
+        Asym, BFVEncoder, Decryptor, Encryptor, KeyGenerator, Plaintext
     };
 
+    use crate::test_utils::gen_params_n_ctx;
+
     // use crate::{field::{bn_254::Bn254F, Field}, poly::F};
 
     use super::MultiLinearPoly;
 
     const VN: usize = 2;
-    const BATCH_SIZE: u64 = 4096;
-    const CIPHER_BIT_VEC: &[i32] = &[40, 30, 30];
 
     type F = Plaintext;
 
-    fn gen_params_n_ctx() -> (EncryptionParameters, Context) {
-        let params = BfvEncryptionParametersBuilder::new()
-            .set_poly_modulus_degree(BATCH_SIZE)
-            .set_coefficient_modulus(
-                CoefficientModulus::create(BATCH_SIZE, CIPHER_BIT_VEC).unwrap(),
-            )
-            .set_plain_modulus(PlainModulus::batching(BATCH_SIZE, 20).unwrap())
-            .build()
-            .unwrap(); 
-
-        let ctx = Context::new(&params, false, SecurityLevel::TC128).unwrap();
-        (params, ctx)
-    }
-
     #[test]
     fn eq() {
         let mut rng = thread_rng();
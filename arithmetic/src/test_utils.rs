@@ -0,0 +1,17 @@
+use seal_fhe::{
+    BfvEncryptionParametersBuilder, CoefficientModulus, Context, EncryptionParameters,
+    PlainModulus, SecurityLevel,
+};
+
+// bfv parameters with 8192 slots and a 40-bit plain modulus, large enough that random
+// challenges do not hit a zero slot
+pub fn gen_params_n_ctx() -> (EncryptionParameters, Context) {
+    let params = BfvEncryptionParametersBuilder::new()
+        .set_poly_modulus_degree(8192)
+        .set_coefficient_modulus(CoefficientModulus::create(8192, &[50, 30, 30, 50, 50]).unwrap())
+        .set_plain_modulus(PlainModulus::batching(8192, 40).unwrap())
+        .build()
+        .unwrap();
+    let ctx = Context::new(&params, false, SecurityLevel::TC128).unwrap();
+    (params, ctx)
+}
@@ -1,73 +1,84 @@
 use std::time::Instant;
 
-use arithmetic::{
-    field::{
-        goldilocks64::{Goldilocks64, Goldilocks64Ext},
-        Field,
-    },
-    mul_group::Radix2Group,
-};
+use arithmetic::test_utils::gen_params_n_ctx;
 use csv::Writer;
-use poly_commit::deepfold::{DeepFoldParam, DeepFoldProver, DeepFoldVerifier};
-use rand::thread_rng;
+use poly_commit::deepfold::{DeepFoldConfig, DeepFoldParam, DeepFoldProver};
+use seal_fhe::{BFVEncoder, Plaintext};
+
+use hyperplonk::{
+    circuit::{identity_permutation, Circuit},
+    gate::GateSet,
+    permutation::PermutationCheck,
+    prover::Prover,
+    verifier::Verifier,
+};
 
-use hyperplonk::{circuit::Circuit, prover::Prover, verifier::Verifier};
+type F = Plaintext;
 
 fn main() {
+    let nv = 10;
     let mut wtr = Writer::from_path("deepfold_snark.csv").unwrap();
     wtr.write_record(["nv", "prover_time", "proof_size", "verifier_time"])
         .unwrap();
-    let (prover_time, proof_size, verifier_time) = bench_mock_circuit(20, 1);
-    wtr.write_record([20, prover_time, proof_size, verifier_time].map(|x| x.to_string()))
+    let (prover_time, proof_size, verifier_time) = bench_mock_circuit(nv, 1);
+    wtr.write_record([nv, prover_time, proof_size, verifier_time].map(|x| x.to_string()))
         .unwrap();
 }
 
-fn bench_mock_circuit(nv: u32, repetition: usize) -> (usize, usize, usize) {
-    let num_gates = 1u32 << nv;
-    let mock_circuit = Circuit::<Goldilocks64Ext> {
-        permutation: [
-            (0..num_gates).map(|x| x.into()).collect(),
-            (0..num_gates).map(|x| (x + (1 << 29)).into()).collect(),
-            (0..num_gates).map(|x| (x + (1 << 30)).into()).collect(),
-        ], // identical permutation
-        selector: (0..num_gates).map(|x| (x & 1).into()).collect(),
+fn bench_mock_circuit(nv: usize, repetition: usize) -> (usize, usize, usize) {
+    let num_gates = 1u64 << nv;
+    let (params, ctx) = gen_params_n_ctx();
+    let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+    let mock_circuit = Circuit {
+        public_num: 0,
+        permutation: identity_permutation(3, nv, &encoder),
+        selectors: vec![(0..num_gates)
+            .map(|x| F::from_int(x & 1, &encoder))
+            .collect()],
+        gates: GateSet::add_mul(),
+        lookup: None,
+        permutation_check: PermutationCheck::Product,
+        zk: false,
     };
 
-    let mut mult_subgroups = vec![Radix2Group::<Goldilocks64>::new(nv + 2)];
-    for i in 1..nv as usize {
-        mult_subgroups.push(mult_subgroups[i - 1].exp(2));
-    }
-    let pp = DeepFoldParam::<Goldilocks64Ext> {
-        mult_subgroups,
-        variable_num: nv as usize,
-        query_num: 45,
+    let config = DeepFoldConfig {
+        log_blowup: 2,
+        final_poly_log_size: 4,
+        folding_factor: 2,
+        ..DeepFoldConfig::new(nv)
     };
-    let (pk, vk) = mock_circuit.setup::<DeepFoldProver<_>, DeepFoldVerifier<_>>(&pp, &pp);
-    let prover = Prover { prover_key: pk };
-    let verifier = Verifier { verifier_key: vk };
+    let (pp, report) = DeepFoldParam::new(config, &ctx, &params);
+    println!("{}", report);
+    let (pk, vk) = mock_circuit.setup::<DeepFoldProver>(&pp, &params, &ctx);
+    let prover = Prover::new(pk, &ctx, &params);
+    let verifier = Verifier::new(vk, &params, &ctx);
     let a = (0..num_gates)
-        .map(|_| Goldilocks64::random(&mut thread_rng()))
+        .map(|_| F::random_pt(&encoder))
         .collect::<Vec<_>>();
     let b = (0..num_gates)
-        .map(|_| Goldilocks64::random(&mut thread_rng()))
+        .map(|_| F::random_pt(&encoder))
         .collect::<Vec<_>>();
-    let c = (0..num_gates)
+    let c = (0..num_gates as usize)
         .map(|i| {
-            let i = i as usize;
-            let s = mock_circuit.selector[i];
-            -((Goldilocks64::one() - s) * (a[i] + b[i]) + s * a[i] * b[i])
+            let s = &mock_circuit.selectors[0][i];
+            let sum = F::add(&a[i], &b[i], &encoder);
+            let prod = F::mult(&a[i], &b[i], &encoder);
+            // (1 - s) * (a + b) + s * a * b = a + b + s * (a * b - a - b)
+            let diff = F::sub(&prod, &sum, &encoder);
+            F::neg(&F::add(&sum, &F::mult(s, &diff, &encoder), &encoder), &encoder)
         })
         .collect::<Vec<_>>();
+    let witness = vec![a, b, c];
     let start = Instant::now();
     for _ in 0..repetition - 1 {
-        let _proof = prover.prove(&pp, nv as usize, [a.clone(), b.clone(), c.clone()]);
+        let _proof = prover.prove(&pp, nv, witness.clone(), &params, &ctx, &encoder);
     }
-    let proof = prover.prove(&pp, nv as usize, [a, b, c]);
+    let proof = prover.prove(&pp, nv, witness, &params, &ctx, &encoder);
     let prover_time = start.elapsed().as_micros() as usize / repetition;
-    let proof_size = proof.bytes.len();
+    let proof_size = proof.size();
 
     let start = Instant::now();
-    assert!(verifier.verify(&pp, nv as usize, proof));
+    assert!(verifier.verify(&pp, nv, &[], &proof));
     let verifier_time = start.elapsed().as_micros() as usize;
 
     (prover_time, proof_size, verifier_time)
@@ -0,0 +1,339 @@
+use std::fmt::Display;
+
+use seal_fhe::{BFVEncoder, Plaintext};
+
+use crate::{
+    circuit::{wire_id, Circuit},
+    gate::GateSet,
+    lookup::Lookup,
+    permutation::PermutationCheck,
+    prover::padded_public_num,
+    zk::ZK_ROWS,
+};
+
+type F = Plaintext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Variable(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    // a table was set on a zero-knowledge circuit, whose blinding rows the lookup argument
+    // does not cover
+    LookupInZeroKnowledge,
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::LookupInZeroKnowledge => write!(f, "lookups are not supported in zero-knowledge mode"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+// selector columns of GateSet::plonk
+const Q_L: usize = 0;
+const Q_R: usize = 1;
+const Q_O: usize = 2;
+const Q_M: usize = 3;
+const Q_C: usize = 4;
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn push(&mut self) {
+        self.parent.push(self.parent.len());
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut x = x;
+        while self.parent[x] != root {
+            let next = self.parent[x];
+            self.parent[x] = root;
+            x = next;
+        }
+        root
+    }
+
+    fn union(&mut self, x: usize, y: usize) {
+        let (x, y) = (self.find(x), self.find(y));
+        self.parent[x] = y;
+    }
+}
+
+// one row of GateSet::plonk, cells without a variable hold zero. lookup rows check column
+// a against the table
+struct Row {
+    selectors: [F; 5],
+    wires: [Option<Variable>; 3],
+    lookup: bool,
+}
+
+// records variables, gates and copy constraints and lays them out as a circuit over
+// GateSet::plonk. public inputs take the first rows of column a, padded with zeros to a
+// power of two, then come the gates and zero rows up to the next power of two, at least
+// ZK_ROWS of them in zk mode
+pub struct CircuitBuilder<'a> {
+    encoder: &'a BFVEncoder<'a>,
+    values: Vec<F>,
+    copies: UnionFind,
+    public: Vec<Variable>,
+    rows: Vec<Row>,
+    table: Option<Vec<F>>,
+    zk: bool,
+}
+
+impl<'a> CircuitBuilder<'a> {
+    pub fn new(encoder: &'a BFVEncoder<'a>) -> Self {
+        CircuitBuilder {
+            encoder,
+            values: vec![],
+            copies: UnionFind { parent: vec![] },
+            public: vec![],
+            rows: vec![],
+            table: None,
+            zk: false,
+        }
+    }
+
+    pub fn value(&self, x: Variable) -> &F {
+        &self.values[x.0]
+    }
+
+    pub fn alloc_variable(&mut self, value: F) -> Variable {
+        self.values.push(value);
+        self.copies.push();
+        Variable(self.values.len() - 1)
+    }
+
+    pub fn alloc_public(&mut self, value: F) -> Variable {
+        let x = self.alloc_variable(value);
+        self.public.push(x);
+        x
+    }
+
+    fn push_row(&mut self, coeffs: &[(usize, F)], wires: [Option<Variable>; 3]) {
+        let mut selectors = [0; 5].map(|_| F::from_int(0, self.encoder));
+        for (i, coeff) in coeffs {
+            selectors[*i] = coeff.clone();
+        }
+        self.rows.push(Row { selectors, wires, lookup: false });
+    }
+
+    fn minus_one(&self) -> F {
+        F::from_int(1, self.encoder).neg(self.encoder)
+    }
+
+    // x + y
+    pub fn add_gate(&mut self, x: Variable, y: Variable) -> Variable {
+        let z = self.alloc_variable(self.value(x).add(self.value(y), self.encoder));
+        let one = F::from_int(1, self.encoder);
+        self.push_row(
+            &[(Q_L, one.clone()), (Q_R, one), (Q_O, self.minus_one())],
+            [Some(x), Some(y), Some(z)],
+        );
+        z
+    }
+
+    // x * y
+    pub fn mul_gate(&mut self, x: Variable, y: Variable) -> Variable {
+        let z = self.alloc_variable(self.value(x).mult(self.value(y), self.encoder));
+        let one = F::from_int(1, self.encoder);
+        self.push_row(
+            &[(Q_M, one), (Q_O, self.minus_one())],
+            [Some(x), Some(y), Some(z)],
+        );
+        z
+    }
+
+    // a variable fixed to `value` by the circuit
+    pub fn constant(&mut self, value: F) -> Variable {
+        let x = self.alloc_variable(value.clone());
+        let one = F::from_int(1, self.encoder);
+        self.push_row(
+            &[(Q_L, one), (Q_C, value.neg(self.encoder))],
+            [Some(x), None, None],
+        );
+        x
+    }
+
+    pub fn assert_equal(&mut self, x: Variable, y: Variable) {
+        self.copies.union(x.0, y.0);
+    }
+
+    // the table variables are looked up in, padded with its first entry to the row number
+    pub fn set_table(&mut self, table: Vec<F>) {
+        assert!(!table.is_empty());
+        self.table = Some(table);
+    }
+
+    // x is one of the table entries
+    pub fn lookup(&mut self, x: Variable) {
+        assert!(self.table.is_some());
+        self.push_row(&[], [Some(x), None, None]);
+        self.rows.last_mut().unwrap().lookup = true;
+    }
+
+    // zk keys for the circuit, which then proves the copies with the log-derivative check and
+    // has no lookup
+    pub fn zero_knowledge(&mut self) {
+        self.zk = true;
+    }
+
+    // the circuit and its witness columns; the public inputs the verifier expects are the
+    // first circuit.public_num values of column a, the gates starting after their padding
+    pub fn build(mut self) -> Result<(Circuit, Vec<Vec<F>>), BuildError> {
+        if self.zk && self.table.is_some() {
+            return Err(BuildError::LookupInZeroKnowledge);
+        }
+        let encoder = self.encoder;
+        let public_num = padded_public_num(self.public.len());
+        let table_len = self.table.as_ref().map_or(0, |x| x.len());
+        let blinding_num = if self.zk { ZK_ROWS } else { 0 };
+        let nv = (public_num + self.rows.len() + blinding_num)
+            .max(table_len)
+            .next_power_of_two()
+            .max(4)
+            .ilog2() as usize;
+        let row_num = 1 << nv;
+        let gates = GateSet::plonk();
+
+        let mut cells = vec![vec![None; row_num]; 3];
+        for (i, x) in self.public.iter().enumerate() {
+            cells[0][i] = Some(*x);
+        }
+        let mut selectors = vec![vec![F::from_int(0, encoder); row_num]; gates.selector_num];
+        for (i, row) in self.rows.iter().enumerate() {
+            for (j, s) in row.selectors.iter().enumerate() {
+                selectors[j][public_num + i] = s.clone();
+            }
+            for (j, w) in row.wires.iter().enumerate() {
+                cells[j][public_num + i] = *w;
+            }
+        }
+
+        let witness = cells
+            .iter()
+            .map(|column| {
+                column
+                    .iter()
+                    .map(|x| x.map_or(F::from_int(0, encoder), |x| self.values[x.0].clone()))
+                    .collect()
+            })
+            .collect();
+
+        // every cell points to the next cell of its copy class, and the last one back to the
+        // first, cells without a variable being fixed points
+        let mut permutation = (0..3)
+            .map(|j| (0..row_num).map(|i| F::from_int(wire_id(j, i, nv), encoder)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut classes = vec![vec![]; self.values.len()];
+        for (j, column) in cells.iter().enumerate() {
+            for (i, x) in column.iter().enumerate() {
+                if let Some(x) = x {
+                    classes[self.copies.find(x.0)].push((j, i));
+                }
+            }
+        }
+        for class in classes.iter().filter(|x| x.len() > 1) {
+            for (k, &(j, i)) in class.iter().enumerate() {
+                let (next_j, next_i) = class[(k + 1) % class.len()];
+                permutation[j][i] = F::from_int(wire_id(next_j, next_i, nv), encoder);
+            }
+        }
+
+        let lookup = self.table.map(|mut table| {
+            table.resize(row_num, table[0].clone());
+            let mut selector = vec![F::from_int(0, encoder); row_num];
+            for (i, row) in self.rows.iter().enumerate() {
+                if row.lookup {
+                    selector[public_num + i] = F::from_int(1, encoder);
+                }
+            }
+            Lookup {
+                selector,
+                table,
+                columns: vec![0],
+            }
+        });
+
+        Ok((
+            Circuit {
+                public_num: self.public.len(),
+                permutation,
+                selectors,
+                gates,
+                lookup,
+                permutation_check: if self.zk { PermutationCheck::LogDerivative } else { PermutationCheck::default() },
+                zk: self.zk,
+            },
+            witness,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use arithmetic::test_utils::gen_params_n_ctx;
+    use poly_commit::nil::NilPcProver;
+    use seal_fhe::{BFVEncoder, Plaintext};
+
+    use crate::{prover::Prover, verifier::Verifier};
+
+    use super::{BuildError, CircuitBuilder};
+
+    type F = Plaintext;
+
+    #[test]
+    fn build_and_prove() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        // out = x * y + 5, with out public
+        let prove = |out_value: u64| {
+            let mut builder = CircuitBuilder::new(&encoder);
+            let x = builder.alloc_variable(F::from_int(3, &encoder));
+            let y = builder.alloc_variable(F::from_int(4, &encoder));
+            let out = builder.alloc_public(F::from_int(out_value, &encoder));
+            let xy = builder.mul_gate(x, y);
+            let five = builder.constant(F::from_int(5, &encoder));
+            let sum = builder.add_gate(xy, five);
+            builder.assert_equal(sum, out);
+            let (circuit, witness) = builder.build().unwrap();
+            let nv = witness[0].len().ilog2() as usize;
+            let public_inputs = witness[0][..circuit.public_num].to_vec();
+            assert_eq!(public_inputs, vec![F::from_int(out_value, &encoder)]);
+
+            let (pk, vk) = circuit.setup::<NilPcProver>(&(), &params, &ctx);
+            let prover = Prover::new(pk, &ctx, &params);
+            let verifier = Verifier::new(vk, &params, &ctx);
+            let proof = prover.prove(&(), nv, witness, &params, &ctx, &encoder);
+            verifier.verify(&(), nv, &public_inputs, &proof)
+        };
+        assert!(prove(17));
+        // the public output breaks the copy constraint
+        assert!(!prove(18));
+    }
+
+    #[test]
+    fn lookup_in_zero_knowledge() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        let mut builder = CircuitBuilder::new(&encoder);
+        builder.zero_knowledge();
+        builder.set_table((0..4).map(|i| F::from_int(i, &encoder)).collect());
+        let x = builder.alloc_variable(F::from_int(2, &encoder));
+        builder.lookup(x);
+        assert_eq!(builder.build().err(), Some(BuildError::LookupInZeroKnowledge));
+    }
+}
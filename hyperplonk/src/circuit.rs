@@ -1,51 +1,483 @@
-use std::marker::PhantomData;
+use std::{fmt::Display, marker::PhantomData};
 
 use arithmetic::{field::Field, poly::MultiLinearPoly};
-use poly_commit::{PolyCommitProver, PolyCommitVerifier};
+use poly_commit::PolynomialCommitmentScheme;
 use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
 
-use crate::{prover::ProverKey, verifier::VerifierKey};
+use crate::{
+    gate::GateSet, lookup::Lookup, permutation::PermutationCheck, prover::{padded_public_num, ProverKey},
+    verifier::VerifierKey, zk::ZK_ROWS,
+};
 
 type F = Plaintext;
 
-// the circuit gate is (1 - s(X)) * (a(X) + b(X)) + s(X) * a(X) * b(X) + c(X) = 0
-// the first m elements in a(X) are public inputs
+// the wire in `column` and `row` is numbered column * 2^nv + row, so the identity over all
+// columns is one identity polynomial with the column index in its top variables
+pub fn wire_id(column: usize, row: usize, nv: usize) -> u64 {
+    ((column << nv) + row) as u64
+}
+
+// variables indexing the columns, padded to a power of two
+pub fn column_var_num(wire_num: usize) -> usize {
+    wire_num.next_power_of_two().ilog2() as usize
+}
+
+pub fn identity_permutation(wire_num: usize, nv: usize, encoder: &BFVEncoder) -> Vec<Vec<F>> {
+    (0..wire_num)
+        .map(|j| (0..1 << nv).map(|i| F::from_int(wire_id(j, i, nv), encoder)).collect())
+        .collect()
+}
+
+// a row whose gate does not vanish
+#[derive(Debug, Clone, PartialEq)]
+pub struct GateFailure {
+    pub row: usize,
+    pub wires: Vec<F>,
+    // simd slots the gate is nonzero in
+    pub slots: Vec<usize>,
+}
+
+// a copy cycle whose cells do not all hold the same value, cells being (column, row)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CopyFailure {
+    pub cycle: Vec<(usize, usize)>,
+    // the first cell differing from the next one in the cycle
+    pub cell: (usize, usize),
+    pub next: (usize, usize),
+    pub slots: Vec<usize>,
+}
+
+// witness columns the circuit cannot be checked on, nothing else being checked then
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShapeFailure {
+    // the number of columns against the wires of the gates
+    Width { expected: usize, found: usize },
+    // a column whose length is not the number of rows
+    Height { column: usize, expected: usize, found: usize },
+}
+
+// a permutation that is no bijection of the cells, the copy cycles being left unchecked
+#[derive(Debug, Clone, PartialEq)]
+pub enum PermutationFailure {
+    // the entry of `cell` is not the wire id of a cell in every slot
+    OutOfRange { cell: (usize, usize) },
+    // the entries of both cells send them to `target`
+    Collision { cells: [(usize, usize); 2], target: (usize, usize) },
+}
+
+// a selected cell of a lookup column whose value is missing from the table
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupFailure {
+    pub cell: (usize, usize),
+    pub value: F,
+    pub slots: Vec<usize>,
+}
+
+// a row of a(X) between the public inputs and their padding to a power of two that is not
+// zero, which the verifier pads them with
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicFailure {
+    pub row: usize,
+    pub slots: Vec<usize>,
+}
+
+// the first failures found by Circuit::check_witness, at most `limit` of each kind, and
+// how many there are in total
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WitnessReport {
+    pub shape: Option<ShapeFailure>,
+    pub gates: Vec<GateFailure>,
+    pub copies: Vec<CopyFailure>,
+    pub permutation: Vec<PermutationFailure>,
+    pub lookups: Vec<LookupFailure>,
+    pub public: Vec<PublicFailure>,
+    pub gate_failure_num: usize,
+    pub copy_failure_num: usize,
+    pub permutation_failure_num: usize,
+    pub lookup_failure_num: usize,
+    pub public_failure_num: usize,
+}
+
+impl WitnessReport {
+    fn is_empty(&self) -> bool {
+        self.shape.is_none()
+            && self.gate_failure_num == 0
+            && self.copy_failure_num == 0
+            && self.permutation_failure_num == 0
+            && self.lookup_failure_num == 0
+            && self.public_failure_num == 0
+    }
+}
+
+impl Display for WitnessReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.shape {
+            Some(ShapeFailure::Width { expected, found }) => {
+                return writeln!(f, "{} witness columns for {} wires", found, expected);
+            }
+            Some(ShapeFailure::Height { column, expected, found }) => {
+                return writeln!(f, "witness column {} has {} rows instead of {}", column, found, expected);
+            }
+            None => {}
+        }
+        writeln!(
+            f,
+            "{} failing gates, {} broken copy cycles, {} bad permutation entries, {} lookup misses, {} nonzero public padding rows",
+            self.gate_failure_num,
+            self.copy_failure_num,
+            self.permutation_failure_num,
+            self.lookup_failure_num,
+            self.public_failure_num
+        )?;
+        for x in &self.gates {
+            writeln!(f, "gate at row {} fails in slots {:?}", x.row, x.slots)?;
+        }
+        for x in &self.copies {
+            writeln!(
+                f,
+                "copy cycle {:?} breaks between {:?} and {:?} in slots {:?}",
+                x.cycle, x.cell, x.next, x.slots
+            )?;
+        }
+        for x in &self.permutation {
+            match x {
+                PermutationFailure::OutOfRange { cell } => {
+                    writeln!(f, "permutation entry of {:?} is no wire id", cell)?
+                }
+                PermutationFailure::Collision { cells, target } => {
+                    writeln!(f, "permutation sends both {:?} and {:?} to {:?}", cells[0], cells[1], target)?
+                }
+            }
+        }
+        for x in &self.lookups {
+            writeln!(f, "lookup of {:?} misses the table in slots {:?}", x.cell, x.slots)?;
+        }
+        for x in &self.public {
+            writeln!(f, "public padding row {} is nonzero in slots {:?}", x.row, x.slots)?;
+        }
+        Ok(())
+    }
+}
+
+fn differing_slots(x: &F, y: &F, encoder: &BFVEncoder) -> Vec<usize> {
+    x.get_value(encoder)
+        .into_iter()
+        .zip(y.get_value(encoder))
+        .enumerate()
+        .filter(|(_, (x, y))| x != y)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// every row satisfies the gates over the selectors and the wire columns, e.g.
+// (1 - s(X)) * (a(X) + b(X)) + s(X) * a(X) * b(X) + c(X) = 0 for GateSet::add_mul. the
+// permutation sends each wire to the wire_id of the next one in its copy cycle
+// the first public_num elements in a(X) are public inputs, followed by zeros up to the
+// next power of two. an optional lookup checks wire values against a table on the rows its
+// selector is on. permutation_check picks the argument the keys prove the copies with. zk
+// keys hide the witness, leaving its last ZK_ROWS rows to the prover's blinding values
 pub struct Circuit {
-    pub permutation: [Vec<F>; 3],
-    pub selector: Vec<F>,
+    pub public_num: usize,
+    pub permutation: Vec<Vec<F>>,
+    pub selectors: Vec<Vec<F>>,
+    pub gates: GateSet,
+    pub lookup: Option<Lookup>,
+    pub permutation_check: PermutationCheck,
+    pub zk: bool,
 }
 
 impl<'a> Circuit {
-    pub fn setup<
-        PcProver: PolyCommitProver,
-        PcVerifier: PolyCommitVerifier<Commitment = PcProver::Commitment>,
-    >(
+    // the cell a wire id refers to, if it is the same in every slot and within the wires
+    fn cell(&self, id: &F, nv: usize, encoder: &BFVEncoder) -> Option<(usize, usize)> {
+        let slots = id.get_value(encoder);
+        let id = slots[0] as usize;
+        let cell = (id >> nv, id & ((1 << nv) - 1));
+        (slots.iter().all(|&x| x == slots[0]) && cell.0 < self.permutation.len()).then_some(cell)
+    }
+
+    // evaluates the gates, copy constraints, lookup and public input padding on the witness
+    // directly, so a bad witness is located before it reaches the prover. the permutation is
+    // checked to be a bijection first, the copy cycles being walked only then
+    pub fn check_witness(
         &self,
-        pp: &PcProver::Param,
-        vp: &PcVerifier::Param,
+        witness: &[Vec<F>],
+        encoder: &BFVEncoder,
+        limit: usize,
+    ) -> Result<(), Box<WitnessReport>> {
+        let row_num = self.permutation[0].len();
+        let nv = row_num.ilog2() as usize;
+        let zero = F::from_int(0, encoder);
+        let mut report = WitnessReport::default();
+        if witness.len() != self.gates.wire_num {
+            report.shape = Some(ShapeFailure::Width { expected: self.gates.wire_num, found: witness.len() });
+            return Err(Box::new(report));
+        }
+        if let Some((column, x)) = witness.iter().enumerate().find(|(_, x)| x.len() != row_num) {
+            report.shape = Some(ShapeFailure::Height { column, expected: row_num, found: x.len() });
+            return Err(Box::new(report));
+        }
+
+        for row in 0..row_num {
+            let selectors = self.selectors.iter().map(|x| x[row].clone()).collect::<Vec<_>>();
+            let wires = witness.iter().map(|x| x[row].clone()).collect::<Vec<_>>();
+            let slots = differing_slots(&self.gates.evaluate(&selectors, &wires, encoder), &zero, encoder);
+            if !slots.is_empty() {
+                report.gate_failure_num += 1;
+                if report.gates.len() < limit {
+                    report.gates.push(GateFailure { row, wires, slots });
+                }
+            }
+        }
+
+        // the cell each entry sends to, and the first entry sending to each cell
+        let mut next = vec![vec![None; row_num]; witness.len()];
+        let mut sources = vec![vec![None; row_num]; witness.len()];
+        for (column, ids) in self.permutation.iter().enumerate() {
+            for (row, id) in ids.iter().enumerate() {
+                let failure = match self.cell(id, nv, encoder) {
+                    None => Some(PermutationFailure::OutOfRange { cell: (column, row) }),
+                    Some(target) => {
+                        next[column][row] = Some(target);
+                        sources[target.0][target.1]
+                            .replace((column, row))
+                            .map(|first| PermutationFailure::Collision { cells: [first, (column, row)], target })
+                    }
+                };
+                if let Some(failure) = failure {
+                    report.permutation_failure_num += 1;
+                    if report.permutation.len() < limit {
+                        report.permutation.push(failure);
+                    }
+                }
+            }
+        }
+
+        // without a bijection there are no cycles to walk
+        let bijective = report.permutation_failure_num == 0;
+        let mut visited = vec![vec![false; row_num]; witness.len()];
+        for column in 0..witness.len() {
+            for row in 0..row_num {
+                if !bijective || visited[column][row] {
+                    continue;
+                }
+                let mut cycle = vec![];
+                let mut cell = (column, row);
+                while !visited[cell.0][cell.1] {
+                    visited[cell.0][cell.1] = true;
+                    cycle.push(cell);
+                    cell = next[cell.0][cell.1].unwrap();
+                }
+                let failure = cycle.iter().enumerate().find_map(|(k, &cell)| {
+                    let next = cycle[(k + 1) % cycle.len()];
+                    let slots = differing_slots(&witness[cell.0][cell.1], &witness[next.0][next.1], encoder);
+                    (!slots.is_empty()).then_some((cell, next, slots))
+                });
+                if let Some((cell, next, slots)) = failure {
+                    report.copy_failure_num += 1;
+                    if report.copies.len() < limit {
+                        report.copies.push(CopyFailure { cycle, cell, next, slots });
+                    }
+                }
+            }
+        }
+
+        for (column, row, slots) in self.lookup.iter().flat_map(|x| x.misses(witness, encoder)) {
+            report.lookup_failure_num += 1;
+            if report.lookups.len() < limit {
+                let value = witness[column][row].clone();
+                report.lookups.push(LookupFailure { cell: (column, row), value, slots });
+            }
+        }
+
+        let padding = padded_public_num(self.public_num) - self.public_num;
+        for (row, x) in witness[0].iter().enumerate().skip(self.public_num).take(padding) {
+            let slots = differing_slots(x, &zero, encoder);
+            if !slots.is_empty() {
+                report.public_failure_num += 1;
+                if report.public.len() < limit {
+                    report.public.push(PublicFailure { row, slots });
+                }
+            }
+        }
+
+        if report.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(report))
+        }
+    }
+
+    pub fn setup<PC: PolynomialCommitmentScheme<'a>>(
+        &self,
+        pp: &PC::Param,
         params: &'a EncryptionParameters,
         ctx: &'a Context,
-    ) -> (ProverKey<'a, PcProver>, VerifierKey<PcVerifier>) {
-        let pc_prover = PcProver::new(
-            pp,
-            &[
-                self.selector.clone(),
-                self.permutation[0].clone(),
-                self.permutation[1].clone(),
-                self.permutation[2].clone(),
-            ],
-        );
+    ) -> (ProverKey<'a, PC>, VerifierKey<'a, PC>) {
+        assert_eq!(self.selectors.len(), self.gates.selector_num);
+        assert_eq!(self.permutation.len(), self.gates.wire_num);
+        assert!(padded_public_num(self.public_num) <= self.permutation[0].len());
+        // the lookup selector and table are committed after the permutation
+        let lookup_polys = self
+            .lookup
+            .iter()
+            .flat_map(|x| {
+                assert_eq!(x.table.len(), self.permutation[0].len());
+                assert!(x.columns.iter().all(|&c| c < self.gates.wire_num));
+                [&x.selector, &x.table]
+            });
+        let polys = self
+            .selectors
+            .iter()
+            .chain(self.permutation.iter())
+            .chain(lookup_polys)
+            .cloned()
+            .collect::<Vec<_>>();
+        let (commitment, prover_data) = PC::commit(pp, &polys);
 
         let encoder = BFVEncoder::new(ctx, params).unwrap();
+        if self.zk {
+            self.check_blinding_rows::<PC>(&encoder);
+        }
         (
             ProverKey {
-                selector: MultiLinearPoly::new(self.selector.clone(), &params, &ctx),
-                commitments: pc_prover.clone(),
-                permutation: self.permutation.clone().map(|x| MultiLinearPoly::new(x, &params, &ctx)),
+                selectors: self.selectors.iter().map(|x| MultiLinearPoly::new(x.clone(), &params, &ctx)).collect(),
+                commitments: prover_data,
+                permutation: self.permutation.iter().map(|x| MultiLinearPoly::new(x.clone(), &params, &ctx)).collect(),
+                gates: self.gates.clone(),
+                public_num: self.public_num,
+                lookup: self.lookup.clone(),
+                permutation_check: self.permutation_check,
+                zk: self.zk,
             },
             VerifierKey {
-                commitment: PcVerifier::new(vp, pc_prover.commit(), 4),
+                commitment,
+                gates: self.gates.clone(),
+                public_num: self.public_num,
+                lookup_columns: self.lookup.as_ref().map(|x| x.columns.clone()),
+                permutation_check: self.permutation_check,
+                zk: self.zk,
             },
         )
     }
+
+    // what zk mode relies on: a hiding pcs, the log-derivative permutation check, no lookup,
+    // and blinding rows on which any witness satisfies the gates and no copy cycle lands
+    fn check_blinding_rows<PC: PolynomialCommitmentScheme<'a>>(&self, encoder: &BFVEncoder) {
+        assert!(PC::HIDING);
+        assert_eq!(self.permutation_check, PermutationCheck::LogDerivative);
+        assert!(self.lookup.is_none());
+        assert!(self.gates.terms.iter().all(|x| x.selector.is_some()));
+        let row_num = self.permutation[0].len();
+        let nv = row_num.ilog2() as usize;
+        assert!(padded_public_num(self.public_num) + ZK_ROWS <= row_num);
+        let zero = F::from_int(0, encoder);
+        for row in row_num - ZK_ROWS..row_num {
+            assert!(self.selectors.iter().all(|x| x[row] == zero));
+            for (j, column) in self.permutation.iter().enumerate() {
+                assert_eq!(column[row], F::from_int(wire_id(j, row, nv), encoder));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arithmetic::test_utils::gen_params_n_ctx;
+    use seal_fhe::{BFVEncoder, Plaintext};
+
+    use crate::builder::CircuitBuilder;
+
+    use super::{wire_id, PermutationFailure, ShapeFailure};
+
+    type F = Plaintext;
+
+    #[test]
+    fn check_witness() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        // (x * y) + x, the product being copied into the add gate
+        let mut builder = CircuitBuilder::new(&encoder);
+        let x = builder.alloc_variable(F::random_pt(&encoder));
+        let y = builder.alloc_variable(F::random_pt(&encoder));
+        let xy = builder.mul_gate(x, y);
+        builder.add_gate(xy, x);
+        let (circuit, mut witness) = builder.build().unwrap();
+        assert_eq!(circuit.check_witness(&witness, &encoder, 8), Ok(()));
+
+        // change one slot of the product, the output of row 0
+        let mut slots = witness[2][0].get_value(&encoder);
+        slots[3] ^= 1;
+        witness[2][0] = encoder.encode_unsigned(&slots).unwrap();
+        let report = circuit.check_witness(&witness, &encoder, 8).unwrap_err();
+        assert_eq!(report.gates.len(), 1);
+        assert_eq!(report.gates[0].row, 0);
+        assert_eq!(report.gates[0].slots, vec![3]);
+        assert_eq!(report.copies.len(), 1);
+        assert!(report.copies[0].cycle.contains(&(2, 0)));
+        assert!(report.copies[0].cycle.contains(&(0, 1)));
+        assert_eq!(report.copies[0].slots, vec![3]);
+
+        let report = circuit.check_witness(&witness, &encoder, 0).unwrap_err();
+        assert!(report.gates.is_empty() && report.copies.is_empty());
+        assert_eq!((report.gate_failure_num, report.copy_failure_num), (1, 1));
+    }
+
+    #[test]
+    fn malformed_witness() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        // three public inputs padded to four, x in a table of [0, 8), and x * y
+        let mut builder = CircuitBuilder::new(&encoder);
+        builder.set_table((0..8).map(|i| F::from_int(i, &encoder)).collect());
+        for i in 0..3 {
+            builder.alloc_public(F::from_int(i, &encoder));
+        }
+        let x = builder.alloc_variable(F::from_int(5, &encoder));
+        let y = builder.alloc_variable(F::random_pt(&encoder));
+        builder.lookup(x);
+        builder.mul_gate(x, y);
+        let (mut circuit, witness) = builder.build().unwrap();
+        assert_eq!(circuit.public_num, 3);
+        assert_eq!(circuit.check_witness(&witness, &encoder, 8), Ok(()));
+        let row_num = witness[0].len();
+        let nv = row_num.ilog2() as usize;
+
+        let report = circuit.check_witness(&witness[..2], &encoder, 8).unwrap_err();
+        assert_eq!(report.shape, Some(ShapeFailure::Width { expected: 3, found: 2 }));
+        let mut short = witness.clone();
+        short[1].pop();
+        let report = circuit.check_witness(&short, &encoder, 8).unwrap_err();
+        assert_eq!(report.shape, Some(ShapeFailure::Height { column: 1, expected: row_num, found: row_num - 1 }));
+
+        // the padding row of the public inputs, and a looked up value outside the table
+        let mut bad = witness.clone();
+        bad[0][3] = F::from_int(1, &encoder);
+        let lookup_row = (4..row_num).find(|&i| circuit.lookup.as_ref().unwrap().selector[i] != F::from_int(0, &encoder)).unwrap();
+        let mut slots = bad[0][lookup_row].get_value(&encoder);
+        slots[2] = 8;
+        bad[0][lookup_row] = encoder.encode_unsigned(&slots).unwrap();
+        let report = circuit.check_witness(&bad, &encoder, 8).unwrap_err();
+        assert_eq!(report.public_failure_num, 1);
+        assert_eq!(report.public[0].row, 3);
+        assert_eq!(report.lookup_failure_num, 1);
+        assert_eq!(report.lookups[0].cell, (0, lookup_row));
+        assert_eq!(report.lookups[0].slots, vec![2]);
+
+        // a wire id past the wires, then two cells sent to the same one
+        let identity = circuit.permutation.clone();
+        circuit.permutation[1][0] = F::from_int(wire_id(3, 0, nv), &encoder);
+        let report = circuit.check_witness(&witness, &encoder, 8).unwrap_err();
+        assert_eq!(report.permutation, vec![PermutationFailure::OutOfRange { cell: (1, 0) }]);
+        assert_eq!(report.copy_failure_num, 0);
+        circuit.permutation = identity;
+        circuit.permutation[1][0] = F::from_int(wire_id(1, 1, nv), &encoder);
+        let report = circuit.check_witness(&witness, &encoder, 8).unwrap_err();
+        assert_eq!(report.permutation_failure_num, 1);
+        assert_eq!(
+            report.permutation[0],
+            PermutationFailure::Collision { cells: [(1, 0), (1, 1)], target: (1, 1) }
+        );
+    }
 }
@@ -0,0 +1,112 @@
+use seal_fhe::{BFVEncoder, Plaintext};
+
+type F = Plaintext;
+
+// coeff * q_selector * prod_j w_j^powers[j], the selector being optional
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GateTerm {
+    pub coeff: i64,
+    pub selector: Option<usize>,
+    pub powers: Vec<usize>,
+}
+
+impl GateTerm {
+    pub fn new(coeff: i64, selector: Option<usize>, powers: &[usize]) -> Self {
+        GateTerm {
+            coeff,
+            selector,
+            powers: powers.to_vec(),
+        }
+    }
+
+    fn degree(&self) -> usize {
+        self.selector.is_some() as usize + self.powers.iter().sum::<usize>()
+    }
+}
+
+// the gate constraint of a circuit: the sum of its terms vanishes on every row. the prover
+// runs the zero-check over it and the verifier checks its value at the sumcheck point, so
+// both go through evaluate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GateSet {
+    pub selector_num: usize,
+    pub wire_num: usize,
+    pub terms: Vec<GateTerm>,
+}
+
+impl GateSet {
+    pub fn new(selector_num: usize, wire_num: usize, terms: Vec<GateTerm>) -> Self {
+        for term in &terms {
+            assert!(term.selector.map_or(true, |s| s < selector_num));
+            assert!(term.powers.len() <= wire_num);
+        }
+        GateSet {
+            selector_num,
+            wire_num,
+            terms,
+        }
+    }
+
+    // (1 - s) * (a + b) + s * a * b + c
+    pub fn add_mul() -> Self {
+        Self::new(
+            1,
+            3,
+            vec![
+                GateTerm::new(1, None, &[1]),
+                GateTerm::new(1, None, &[0, 1]),
+                GateTerm::new(-1, Some(0), &[1]),
+                GateTerm::new(-1, Some(0), &[0, 1]),
+                GateTerm::new(1, Some(0), &[1, 1]),
+                GateTerm::new(1, None, &[0, 0, 1]),
+            ],
+        )
+    }
+
+    // q_l * a + q_r * b + q_o * c + q_m * a * b + q_c
+    pub fn plonk() -> Self {
+        Self::new(
+            5,
+            3,
+            vec![
+                GateTerm::new(1, Some(0), &[1]),
+                GateTerm::new(1, Some(1), &[0, 1]),
+                GateTerm::new(1, Some(2), &[0, 0, 1]),
+                GateTerm::new(1, Some(3), &[1, 1]),
+                GateTerm::new(1, Some(4), &[]),
+            ],
+        )
+    }
+
+    // plonk + q_h1 * a^5 + q_h2 * b^5
+    pub fn turbo() -> Self {
+        let mut terms = Self::plonk().terms;
+        terms.push(GateTerm::new(1, Some(5), &[5]));
+        terms.push(GateTerm::new(1, Some(6), &[0, 5]));
+        Self::new(7, 3, terms)
+    }
+
+    pub fn degree(&self) -> usize {
+        self.terms.iter().map(|x| x.degree()).max().unwrap_or(0)
+    }
+
+    pub fn evaluate(&self, selectors: &[F], wires: &[F], encoder: &BFVEncoder) -> F {
+        assert_eq!(selectors.len(), self.selector_num);
+        assert_eq!(wires.len(), self.wire_num);
+        self.terms.iter().fold(F::from_int(0, encoder), |acc, term| {
+            let mut v = F::from_int(term.coeff.unsigned_abs(), encoder);
+            if term.coeff < 0 {
+                v = v.neg(encoder);
+            }
+            if let Some(s) = term.selector {
+                v = v.mult(&selectors[s], encoder);
+            }
+            for (w, &p) in wires.iter().zip(term.powers.iter()) {
+                for _ in 0..p {
+                    v = v.mult(w, encoder);
+                }
+            }
+            acc.add(&v, encoder)
+        })
+    }
+}
@@ -0,0 +1,399 @@
+use arithmetic::poly::MultiLinearPoly;
+use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
+use util::fiat_shamir::Transcript;
+
+use crate::{prover::combine, sumcheck::Sumcheck};
+
+type F = Plaintext;
+
+// how two children make their parent: a product tree multiplies them, a fraction tree over
+// (p, q) pairs adds p_0 / q_0 + p_1 / q_1 = (p_0 * q_1 + p_1 * q_0) / (q_0 * q_1) as in
+// logUp-GKR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeKind {
+    Product,
+    Fraction,
+}
+
+impl TreeKind {
+    // columns of every layer
+    pub fn width(&self) -> usize {
+        match self {
+            TreeKind::Product => 1,
+            TreeKind::Fraction => 2,
+        }
+    }
+
+    pub fn parent(&self, left: &[F], right: &[F], encoder: &BFVEncoder) -> Vec<F> {
+        match self {
+            TreeKind::Product => vec![left[0].mult(&right[0], encoder)],
+            TreeKind::Fraction => vec![
+                left[0]
+                    .mult(&right[1], encoder)
+                    .add(&right[0].mult(&left[1], encoder), encoder),
+                left[1].mult(&right[1], encoder),
+            ],
+        }
+    }
+}
+
+// the leaves of one tree, at least two and a power of two
+#[derive(Debug, Clone, PartialEq)]
+pub enum Leaves {
+    Product(Vec<F>),
+    Fraction(Vec<F>, Vec<F>),
+}
+
+impl Leaves {
+    pub fn kind(&self) -> TreeKind {
+        match self {
+            Leaves::Product(_) => TreeKind::Product,
+            Leaves::Fraction(..) => TreeKind::Fraction,
+        }
+    }
+
+    fn columns(self) -> Vec<Vec<F>> {
+        match self {
+            Leaves::Product(x) => vec![x],
+            Leaves::Fraction(p, q) => {
+                assert_eq!(p.len(), q.len());
+                vec![p, q]
+            }
+        }
+    }
+}
+
+// the messages of a batch of trees, walked from the roots down. at depth d the trees with at
+// least d variables are active: the prover sends the evaluations of both children of their
+// layer, then the verifier's challenge moves each claim one layer down. the evaluations of
+// every depth are absorbed before that challenge
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GrandProductProof {
+    // rounds of the sumcheck of every depth from 2
+    pub sumchecks: Vec<Vec<[Vec<F>; 1]>>,
+    // at every depth, the left child columns then the right ones of each active tree
+    pub evals: Vec<Vec<F>>,
+}
+
+impl GrandProductProof {
+    // trees are given by their kind and number of variables
+    pub fn has_shape(&self, trees: &[(TreeKind, usize)]) -> bool {
+        let depth = trees.iter().map(|x| x.1).max().unwrap_or(0);
+        self.sumchecks.len() == depth.saturating_sub(1)
+            && self.evals.len() == depth
+            && self.sumchecks.iter().enumerate().all(|(i, x)| {
+                x.len() == i + 1 && x.iter().all(|x| x[0].len() == 4)
+            })
+            && self.evals.iter().enumerate().all(|(i, x)| {
+                let active = trees.iter().filter(|t| t.1 > i);
+                x.len() == active.map(|t| 2 * t.0.width()).sum::<usize>()
+            })
+    }
+}
+
+// a tree as the verifier sees it after the argument: its root, and the claimed evaluation of
+// its leaf columns at `point`, left for the caller to check
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrandProductClaim {
+    pub root: Vec<F>,
+    pub point: Vec<F>,
+    pub leaves: Vec<F>,
+}
+
+pub struct GrandProduct;
+
+// the claim on a layer at r, from its children at the sumcheck point
+fn interpolate(left: &[F], right: &[F], r: &F, encoder: &BFVEncoder) -> Vec<F> {
+    left.iter()
+        .zip(right)
+        .map(|(l, x)| l.add(&x.sub(l, encoder).mult(r, encoder), encoder))
+        .collect()
+}
+
+// the left and right children in the evals of one depth, for trees of the given widths
+fn children(evals: &[F], widths: impl Iterator<Item = usize>) -> Vec<(&[F], &[F])> {
+    let mut idx = 0;
+    widths
+        .map(|w| {
+            idx += 2 * w;
+            (&evals[idx - 2 * w..idx - w], &evals[idx - w..idx])
+        })
+        .collect()
+}
+
+impl<'a> GrandProduct {
+    // a single tree, one sumcheck per layer
+    pub fn prove(
+        leaves: Leaves,
+        params: &'a EncryptionParameters,
+        ctx: &'a Context,
+        encoder: &BFVEncoder,
+        transcript: &mut Transcript,
+    ) -> (Vec<F>, GrandProductProof) {
+        let (mut points, proof) = Self::prove_batch(vec![leaves], params, ctx, encoder, transcript);
+        (points.remove(0), proof)
+    }
+
+    // all trees in lockstep, with one sumcheck per depth over the layers at that depth
+    // batched by a random combination. a smaller tree drops out once its leaves are reached,
+    // so the trees may differ in size. returns the point the leaves of each tree are claimed
+    // at
+    pub fn prove_batch(
+        leaves: Vec<Leaves>,
+        params: &'a EncryptionParameters,
+        ctx: &'a Context,
+        encoder: &BFVEncoder,
+        transcript: &mut Transcript,
+    ) -> (Vec<Vec<F>>, GrandProductProof) {
+        let kinds = leaves.iter().map(|x| x.kind()).collect::<Vec<_>>();
+        // layers[t][i][c]: column c of layer i of tree t, layer 0 being the leaves and the
+        // last the two children of the root
+        let layers = leaves
+            .into_iter()
+            .zip(&kinds)
+            .map(|(x, kind)| {
+                let mut layers = vec![x.columns()];
+                assert!(layers[0][0].len() >= 2 && layers[0][0].len().is_power_of_two());
+                while layers.last().unwrap()[0].len() > 2 {
+                    let last = layers.last().unwrap();
+                    let mut next = vec![vec![]; kind.width()];
+                    for k in 0..last[0].len() / 2 {
+                        let left = last.iter().map(|x| x[2 * k].clone()).collect::<Vec<_>>();
+                        let right = last.iter().map(|x| x[2 * k + 1].clone()).collect::<Vec<_>>();
+                        for (c, x) in kind.parent(&left, &right, encoder).into_iter().enumerate() {
+                            next[c].push(x);
+                        }
+                    }
+                    layers.push(next);
+                }
+                layers
+            })
+            .collect::<Vec<_>>();
+        let var_nums = layers.iter().map(|x| x.len()).collect::<Vec<_>>();
+        let depth = *var_nums.iter().max().unwrap();
+
+        let mut proof = GrandProductProof {
+            sumchecks: vec![],
+            evals: vec![layers
+                .iter()
+                .flat_map(|x| {
+                    let top = x.last().unwrap();
+                    top.iter().map(|x| x[0].clone()).chain(top.iter().map(|x| x[1].clone()))
+                })
+                .collect()],
+        };
+        for x in &proof.evals[0] {
+            transcript.append_f(x, encoder);
+        }
+        // the point of the layer below the roots, then lambda batching the trees, each drawn
+        // on its own
+        let mut point = vec![transcript.challenge_f(encoder)];
+        let lambda = transcript.challenge_f(encoder);
+        let mut points = vec![vec![]; layers.len()];
+        for d in 2..=depth + 1 {
+            for (t, &n) in var_nums.iter().enumerate() {
+                if n == d - 1 {
+                    points[t] = point.clone();
+                }
+            }
+            if d > depth {
+                break;
+            }
+            let active = (0..layers.len()).filter(|&t| var_nums[t] >= d).collect::<Vec<_>>();
+            let evals = active
+                .iter()
+                .flat_map(|&t| {
+                    let layer = &layers[t][var_nums[t] - d];
+                    let half = |parity: usize| {
+                        layer
+                            .iter()
+                            .map(move |x| x.iter().skip(parity).step_by(2).cloned().collect::<Vec<_>>())
+                    };
+                    half(0).chain(half(1)).collect::<Vec<_>>()
+                })
+                .chain([MultiLinearPoly::new_eq(&point, params, ctx).evals])
+                .collect();
+            let (new_point, v, sumcheck) = Sumcheck::prove_vec(
+                evals,
+                3,
+                |v: Vec<F>| {
+                    let mut idx = 0;
+                    let parents = active
+                        .iter()
+                        .flat_map(|&t| {
+                            let w = kinds[t].width();
+                            let parent = kinds[t].parent(&v[idx..idx + w], &v[idx + w..idx + 2 * w], encoder);
+                            idx += 2 * w;
+                            parent
+                        })
+                        .collect::<Vec<_>>();
+                    [combine(parents.iter(), &lambda, encoder).mult(v.last().unwrap(), encoder)]
+                },
+                ctx,
+                encoder,
+                transcript,
+            );
+            for x in &v[..v.len() - 1] {
+                transcript.append_f(x, encoder);
+            }
+            proof.sumchecks.push(sumcheck);
+            proof.evals.push(v[..v.len() - 1].to_vec());
+            point = [transcript.challenge_f(encoder)]
+                .into_iter()
+                .chain(new_point)
+                .collect();
+        }
+        (points, proof)
+    }
+
+    // None when a check fails, like Sumcheck::verify. the caller checks the roots against
+    // each other and the leaf claims against the polynomials behind the leaves
+    pub fn verify(
+        trees: &[(TreeKind, usize)],
+        proof: &GrandProductProof,
+        params: &'a EncryptionParameters,
+        ctx: &'a Context,
+        encoder: &BFVEncoder,
+        transcript: &mut Transcript,
+    ) -> Option<Vec<GrandProductClaim>> {
+        if !proof.has_shape(trees) {
+            return None;
+        }
+        let depth = trees.iter().map(|x| x.1).max().unwrap();
+        for x in &proof.evals[0] {
+            transcript.append_f(x, encoder);
+        }
+        // the point of the layer below the roots, then lambda batching the trees, each drawn
+        // on its own
+        let mut point = vec![transcript.challenge_f(encoder)];
+        let lambda = transcript.challenge_f(encoder);
+        let mut claims = children(&proof.evals[0], trees.iter().map(|x| x.0.width()))
+            .into_iter()
+            .enumerate()
+            .map(|(t, (left, right))| GrandProductClaim {
+                root: trees[t].0.parent(left, right, encoder),
+                point: point.clone(),
+                leaves: interpolate(left, right, &point[0], encoder),
+            })
+            .collect::<Vec<_>>();
+        for d in 2..=depth {
+            let active = (0..trees.len()).filter(|&t| trees[t].1 >= d).collect::<Vec<_>>();
+            let y = combine(active.iter().flat_map(|&t| &claims[t].leaves), &lambda, encoder);
+            let (new_point, v) = Sumcheck::verify(
+                [y],
+                3,
+                d - 1,
+                proof.sumchecks[d - 2].clone(),
+                params,
+                ctx,
+                encoder,
+                transcript,
+            )?;
+            let children = children(&proof.evals[d - 1], active.iter().map(|&t| trees[t].0.width()));
+            let parents = active
+                .iter()
+                .zip(&children)
+                .flat_map(|(&t, (left, right))| trees[t].0.parent(left, right, encoder))
+                .collect::<Vec<_>>();
+            if v[0]
+                != combine(parents.iter(), &lambda, encoder)
+                    .mult(&MultiLinearPoly::eval_eq(&point, &new_point, encoder), encoder)
+            {
+                return None;
+            }
+            for x in &proof.evals[d - 1] {
+                transcript.append_f(x, encoder);
+            }
+            let r = transcript.challenge_f(encoder);
+            point = [r.clone()].into_iter().chain(new_point).collect();
+            for (&t, (left, right)) in active.iter().zip(&children) {
+                claims[t].point = point.clone();
+                claims[t].leaves = interpolate(left, right, &r, encoder);
+            }
+        }
+        Some(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arithmetic::{poly::MultiLinearPoly, test_utils::gen_params_n_ctx};
+    use seal_fhe::{BFVEncoder, Plaintext};
+    use util::fiat_shamir::Transcript;
+
+    use super::{GrandProduct, Leaves, TreeKind};
+
+    type F = Plaintext;
+
+    const VN: usize = 5;
+
+    #[test]
+    fn products() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+        let random = |n: usize| (0..n).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+
+        // x * y over tables of 8 and 32 against the same tables reversed, in the other order
+        let x = random(1 << 3);
+        let y = random(1 << VN);
+        let tables = [
+            x.clone(),
+            y.clone(),
+            y.into_iter().rev().collect(),
+            x.into_iter().rev().collect(),
+        ];
+        let (points, proof) = GrandProduct::prove_batch(
+            tables.iter().cloned().map(Leaves::Product).collect(),
+            &params,
+            &ctx,
+            &encoder,
+            &mut Transcript::new(),
+        );
+        let trees = [3, VN, VN, 3].map(|n| (TreeKind::Product, n));
+        let claims = GrandProduct::verify(&trees, &proof, &params, &ctx, &encoder, &mut Transcript::new()).unwrap();
+        assert_eq!(
+            claims[0].root[0].mult(&claims[1].root[0], &encoder),
+            claims[2].root[0].mult(&claims[3].root[0], &encoder)
+        );
+        for (i, table) in tables.iter().enumerate() {
+            assert_eq!(claims[i].point, points[i]);
+            assert_eq!(
+                MultiLinearPoly::eval_multilinear_ext(table, &points[i], &encoder),
+                claims[i].leaves[0]
+            );
+        }
+        assert!(!proof.has_shape(&trees[..3]));
+        assert!(GrandProduct::verify(&trees[..3], &proof, &params, &ctx, &encoder, &mut Transcript::new()).is_none());
+
+        // a single tree
+        let (point, proof) = GrandProduct::prove(Leaves::Product(tables[1].clone()), &params, &ctx, &encoder, &mut Transcript::new());
+        let claims = GrandProduct::verify(&trees[1..2], &proof, &params, &ctx, &encoder, &mut Transcript::new()).unwrap();
+        assert_eq!(claims[0].root[0], tables[1].iter().skip(1).fold(tables[1][0].clone(), |acc, x| acc.mult(x, &encoder)));
+        assert_eq!(MultiLinearPoly::eval_multilinear_ext(&tables[1], &point, &encoder), claims[0].leaves[0]);
+    }
+
+    #[test]
+    fn fractions() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        // denominators without zero slots, which inv would send to zero
+        let nonzero = || {
+            let slots = F::random_pt(&encoder).get_value(&encoder).into_iter().map(|x| x.max(1)).collect::<Vec<_>>();
+            encoder.encode_unsigned(&slots).unwrap()
+        };
+        let p = (0..1 << VN).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let q = (0..1 << VN).map(|_| nonzero()).collect::<Vec<_>>();
+        let (point, proof) = GrandProduct::prove(Leaves::Fraction(p.clone(), q.clone()), &params, &ctx, &encoder, &mut Transcript::new());
+        let claims = GrandProduct::verify(&[(TreeKind::Fraction, VN)], &proof, &params, &ctx, &encoder, &mut Transcript::new()).unwrap();
+
+        // P / Q = sum p / q
+        let sum = p
+            .iter()
+            .zip(&q)
+            .fold(F::from_int(0, &encoder), |acc, (p, q)| acc.add(&p.mult(&q.inv(&encoder), &encoder), &encoder));
+        assert_eq!(claims[0].root[0], claims[0].root[1].mult(&sum, &encoder));
+        assert_eq!(MultiLinearPoly::eval_multilinear_ext(&p, &point, &encoder), claims[0].leaves[0]);
+        assert_eq!(MultiLinearPoly::eval_multilinear_ext(&q, &point, &encoder), claims[0].leaves[1]);
+    }
+}
@@ -1,45 +1,31 @@
+pub mod builder;
 pub mod circuit;
-mod prod_eq_check;
+pub mod gate;
+pub mod grand_product;
+pub mod lookup;
+pub mod permutation;
+pub mod proof;
 pub mod prover;
 mod sumcheck;
 pub mod verifier;
+pub mod zk;
 
 #[cfg(test)]
 mod tests {
-    use arithmetic::{
-        field::{
-            // goldilocks64::{Goldilocks64, Goldilocks64Ext},
-            Field,
-        },
-        mul_group::Radix2Group,
-    };
-    use poly_commit::{
-        // deepfold::{DeepFoldParam, DeepFoldProver, DeepFoldVerifier},
-        nil::{NilPcProver, NilPcVerifier},
-        // shuffle::{ShufflePcProver, ShufflePcVerifier},
-    };
-    use rand::thread_rng;
-    use seal_fhe::{BFVEncoder, BfvEncryptionParametersBuilder, CoefficientModulus, Context, EncryptionParameters, KeyGenerator, PlainModulus, Plaintext};
-    use util::random_oracle::RandomOracle;
 
-    use crate::{circuit::Circuit, prover::Prover, verifier::Verifier};
-
-    type F = Plaintext;
+    use arithmetic::{poly::MultiLinearPoly, test_utils::gen_params_n_ctx};
+    use poly_commit::nil::NilPcProver;
+    use seal_fhe::{BFVEncoder, Plaintext};
+    use util::fiat_shamir::{Proof, Transcript};
 
-    fn gen_params_n_ctx() -> (EncryptionParameters, Context) {
-        let params = BfvEncryptionParametersBuilder::new()
-            .set_poly_modulus_degree(8192)
-            .set_coefficient_modulus(
-                CoefficientModulus::create(8192, &[50, 30, 30, 50, 50]).unwrap(),
-            )
-            .set_plain_modulus(PlainModulus::batching(8192, 40).unwrap())
-            .build()
-            .unwrap();
-
-        let ctx = Context::new(&params, false, seal_fhe::SecurityLevel::TC128).unwrap();
+    use crate::{
+        circuit::{identity_permutation, wire_id, Circuit},
+        gate::{GateSet, GateTerm}, permutation::PermutationCheck, proof::HyperPlonkProof,
+        prover::{open_preprocessed, Prover},
+        verifier::{verify_preprocessed, Verifier},
+    };
 
-        (params, ctx)
-    }
+    type F = Plaintext;
 
     /**
      * 1. circuits需要用plaintext来encode
@@ -53,44 +39,223 @@ mod tests {
 
         let (params, ctx) = gen_params_n_ctx();
         let encoder = BFVEncoder::new(&ctx, &params).unwrap();
-        let key_gen = KeyGenerator::new(&ctx).unwrap();
-        let oracle = RandomOracle::new(10, 1, &ctx, &params, &key_gen);
 
         let mock_circuit = Circuit {
-            permutation: [
-                (0..num_gates).map(|x| F::from_int(x.into(), &encoder)).collect(),
-                (0..num_gates).map(|x| F::from_int((x + (1 << 29)).into(), &encoder)).collect(),
-                (0..num_gates).map(|x| F::from_int((x + (1 << 30)).into(), &encoder)).collect(),
-            ], // identical permutation
-            selector: (0..num_gates).map(|x| F::from_int((x & 1).into(), &encoder)).collect(),
+            public_num: 3,
+            permutation: identity_permutation(3, nv as usize, &encoder), // identical permutation
+            selectors: vec![(0..num_gates).map(|x| F::from_int((x & 1).into(), &encoder)).collect()],
+            gates: GateSet::add_mul(),
+            lookup: None,
+            permutation_check: PermutationCheck::Product,
+            zk: false,
         };
 
         // let mut mult_subgroups = vec![Radix2Group::<Goldilocks64>::new(nv + 2)];
         // for i in 1..nv as usize {
         //     mult_subgroups.push(mult_subgroups[i - 1].exp(2));
         // }
-        let (pk, vk) = mock_circuit.setup::<NilPcProver, NilPcVerifier>(&(), &(), &params, &ctx);
-        let prover = Prover::new(pk, &ctx, &params, &oracle);
-        let verifier = Verifier::new(vk, &params, &ctx, &oracle);
-        let a = (0..num_gates)
+        let (pk, vk) = mock_circuit.setup::<NilPcProver>(&(), &params, &ctx);
+        let prover = Prover::new(pk, &ctx, &params);
+        let verifier = Verifier::new(vk, &params, &ctx);
+        // three public inputs, padded by the verifier with a zero the witness has to match
+        let mut a = (0..num_gates)
             .map(|_| F::random_pt(&encoder))
             .collect::<Vec<_>>();
+        a[3] = F::from_int(0, &encoder);
         let b = (0..num_gates)
             .map(|_| F::random_pt(&encoder))
             .collect::<Vec<_>>();
-        let c = (0..num_gates)
+        let witness = |a: &Vec<F>| {
+            let c = (0..num_gates)
+                .map(|i| {
+                    let i = i as usize;
+                    let s = mock_circuit.selectors[0][i].clone();
+                    let p1 = F::mult(&F::sub(&F::from_int(1, &encoder), &s, &encoder), &F::add(&a[i], &b[i], &encoder), &encoder);
+                    let p2 = [&a[i], &b[i]].into_iter().fold(s, |x, y| {
+                        F::mult(&x, y, &encoder)
+                    });
+                    F::neg(&F::add(&p1, &p2, &encoder), &encoder)
+                })
+                .collect();
+            vec![a.clone(), b.clone(), c]
+        };
+        let public_inputs = a[..3].to_vec();
+        let proof = prover.prove(&(), nv as usize, witness(&a), &params, &ctx, &encoder);
+        assert!(verifier.verify(&(), nv as usize, &public_inputs, &proof));
+
+        // the verifier rejects public inputs the witness does not agree with
+        assert!(!verifier.verify(&(), nv as usize, &public_inputs[..2], &proof));
+        let mut wrong_inputs = public_inputs.clone();
+        wrong_inputs[1] = wrong_inputs[1].add(&F::from_int(1, &encoder), &encoder);
+        assert!(!verifier.verify(&(), nv as usize, &wrong_inputs, &proof));
+        // nor a witness that is not zero on the padding of the public inputs
+        let mut padded = a.clone();
+        padded[3] = F::from_int(1, &encoder);
+        let other = prover.prove(&(), nv as usize, witness(&padded), &params, &ctx, &encoder);
+        assert!(!verifier.verify(&(), nv as usize, &public_inputs, &other));
+
+        let mut buffer = vec![0u8; proof.size()];
+        proof.serialize_into(&mut buffer);
+        assert_eq!(proof.size_breakdown().total(), buffer.len());
+        let mut bytes = Proof::default();
+        bytes.append_u8_slice(&buffer, buffer.len());
+        let decoded = HyperPlonkProof::deserialize_from(&mut bytes, &ctx).unwrap();
+        assert_eq!(bytes.remaining(), 0);
+        assert_eq!(decoded, proof);
+        assert!(verifier.verify(&(), nv as usize, &public_inputs, &decoded));
+
+        let mut truncated = Proof::default();
+        truncated.append_u8_slice(&buffer, buffer.len() - 1);
+        assert!(HyperPlonkProof::deserialize_from(&mut truncated, &ctx).is_none());
+        assert!(!verifier.verify(&(), nv as usize - 1, &public_inputs, &proof));
+    }
+
+    #[test]
+    fn custom_gates() {
+        let nv = 2;
+        let num_gates = 1u32 << nv;
+
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        // q_o is one everywhere, so c is minus the rest of the gate
+        let gates = GateSet::turbo();
+        let selectors = (0..gates.selector_num)
+            .map(|i| {
+                (0..num_gates)
+                    .map(|_| if i == 2 { F::from_int(1, &encoder) } else { F::random_pt(&encoder) })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let circuit = Circuit {
+            public_num: 0,
+            permutation: identity_permutation(3, nv as usize, &encoder),
+            selectors: selectors.clone(),
+            gates: gates.clone(),
+            lookup: None,
+            permutation_check: PermutationCheck::Product,
+            zk: false,
+        };
+        let (pk, vk) = circuit.setup::<NilPcProver>(&(), &params, &ctx);
+        let prover = Prover::new(pk, &ctx, &params);
+        let verifier = Verifier::new(vk, &params, &ctx);
+        let a = (0..num_gates).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let b = (0..num_gates).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let c = (0..num_gates as usize)
             .map(|i| {
-                let i = i as usize;
-                let s = mock_circuit.selector[i].clone();
-                let p1 = F::mult(&F::sub(&F::from_int(1, &encoder), &s, &encoder), &F::add(&a[i], &b[i], &encoder), &encoder);
-                let p2 = [&a[i], &b[i]].into_iter().fold(s, |x, y| {
-                    F::mult(&x, y, &encoder)
-                });
-                F::neg(&F::add(&p1, &p2, &encoder), &encoder)
+                let row = selectors.iter().map(|x| x[i].clone()).collect::<Vec<_>>();
+                let wires = [a[i].clone(), b[i].clone(), F::from_int(0, &encoder)];
+                gates.evaluate(&row, &wires, &encoder).neg(&encoder)
             })
             .collect();
-        let (total_sums, prod_total_sums, prod_transcript, sc_total_sums, proof) = prover.prove(&(), nv as usize, [a, b, c], &params, &ctx, &encoder, &oracle);
-        assert!(verifier.verify(&(), nv as usize, proof, total_sums, prod_total_sums, sc_total_sums, prod_transcript));
-        println!("ok.");
+        let proof = prover.prove(&(), nv as usize, vec![a, b, c], &params, &ctx, &encoder);
+        assert_eq!(proof.evals.selectors.len(), gates.selector_num);
+        assert!(verifier.verify(&(), nv as usize, &[], &proof));
+    }
+
+    #[test]
+    fn wide_circuit() {
+        let nv = 2;
+        let wire_num = 5;
+
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        // q * w0 * w1 + w2 + w3 + w4 = 0, with w3 at row 0 copied to w4 at row 1
+        let gates = GateSet::new(
+            1,
+            wire_num,
+            (2..wire_num)
+                .map(|j| {
+                    let mut powers = vec![0; j + 1];
+                    powers[j] = 1;
+                    GateTerm::new(1, None, &powers)
+                })
+                .chain([GateTerm::new(1, Some(0), &[1, 1])])
+                .collect(),
+        );
+        let mut permutation = identity_permutation(wire_num, nv, &encoder);
+        permutation[3][0] = F::from_int(wire_id(4, 1, nv), &encoder);
+        permutation[4][1] = F::from_int(wire_id(3, 0, nv), &encoder);
+        let selectors = vec![(0..1 << nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>()];
+        let circuit = Circuit {
+            public_num: 0,
+            permutation,
+            selectors: selectors.clone(),
+            gates: gates.clone(),
+            lookup: None,
+            permutation_check: PermutationCheck::Product,
+            zk: false,
+        };
+        let (pk, vk) = circuit.setup::<NilPcProver>(&(), &params, &ctx);
+        let prover = Prover::new(pk, &ctx, &params);
+        let verifier = Verifier::new(vk, &params, &ctx);
+
+        let witness = |copied: bool| {
+            let mut w = (0..wire_num)
+                .map(|_| (0..1 << nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            if copied {
+                w[4][1] = w[3][0].clone();
+            }
+            for i in 0..1 << nv {
+                w[2][i] = F::from_int(0, &encoder);
+                let row = w.iter().map(|x| x[i].clone()).collect::<Vec<_>>();
+                w[2][i] = gates.evaluate(&[selectors[0][i].clone()], &row, &encoder).neg(&encoder);
+            }
+            w
+        };
+        let proof = prover.prove(&(), nv, witness(true), &params, &ctx, &encoder);
+        assert_eq!(proof.evals.witness.len(), wire_num);
+        assert!(verifier.verify(&(), nv, &[], &proof));
+
+        let proof = prover.prove(&(), nv, witness(false), &params, &ctx, &encoder);
+        assert!(!verifier.verify(&(), nv, &[], &proof));
+    }
+
+    #[test]
+    fn preprocessed_batch() {
+        let nv = 2;
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        // add/mul circuits of one shape that differ in their selectors
+        let keys = (0..3)
+            .map(|_| {
+                let circuit = Circuit {
+                    public_num: 0,
+                    permutation: identity_permutation(3, nv, &encoder),
+                    selectors: vec![(0..1 << nv).map(|_| F::random_pt(&encoder)).collect()],
+                    gates: GateSet::add_mul(),
+                    lookup: None,
+                    permutation_check: PermutationCheck::Product,
+                    zk: false,
+                };
+                circuit.setup::<NilPcProver>(&(), &params, &ctx)
+            })
+            .collect::<Vec<_>>();
+        let point = (0..nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let mut transcript = Transcript::new();
+        let opening = open_preprocessed(&(), &keys.iter().map(|x| &x.0).collect::<Vec<_>>(), point.clone(), &encoder, &mut transcript);
+        let proof = transcript.proof;
+
+        let vks = keys.iter().map(|x| &x.1).collect::<Vec<_>>();
+        let mut evals = keys
+            .iter()
+            .map(|(pk, _)| {
+                pk.preprocessed()
+                    .iter()
+                    .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &encoder))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let verify = |vks: &[&_], evals: &[Vec<F>]| {
+            verify_preprocessed::<NilPcProver>(&(), vks, point.clone(), evals, &encoder, &mut Transcript::new(), &mut proof.clone(), &opening)
+        };
+        assert!(verify(&vks, &evals));
+
+        evals[1][0] = evals[1][0].add(&F::from_int(1, &encoder), &encoder);
+        assert!(!verify(&vks, &evals));
+        assert!(!verify(&vks[..2], &evals[..2]));
     }
 }
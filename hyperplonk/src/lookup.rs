@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+
+use seal_fhe::{BFVEncoder, Plaintext};
+
+use crate::prover::combine;
+
+type F = Plaintext;
+
+// logUp: the witness columns `columns` take values of the preprocessed `table` on the rows
+// where `selector` is one, which holds iff for a random beta
+//     sum_i sum_x q(x) / (beta + f_i(x)) = sum_x m(x) / (beta + t(x))
+// with m the multiplicities. the prover commits m before beta is drawn, then the helper
+// columns h_i = q / (beta + f_i) and h_t = m / (beta + t), and one sumcheck shows that the h
+// columns sum as above and that h_i * (beta + f_i) = q and h_t * (beta + t) = m on every row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lookup {
+    pub selector: Vec<F>,
+    pub table: Vec<F>,
+    pub columns: Vec<usize>,
+}
+
+// number of values the lookup sumcheck reads per row for k lookup columns:
+// q, t, f_1..f_k, m, h_t, h_1..h_k
+pub(crate) fn value_num(k: usize) -> usize {
+    2 * k + 4
+}
+
+// the sum of h_i - h_t, and the row identities batched by gamma, over the values of one row
+// in the order of value_num. the second one vanishes on every row of a valid lookup, so
+// both callers weight it with eq
+pub(crate) fn identities(v: &[F], k: usize, beta: &F, gamma: &F, encoder: &BFVEncoder) -> [F; 2] {
+    assert_eq!(v.len(), value_num(k));
+    let (q, t, f, m, h_t, h) = (&v[0], &v[1], &v[2..k + 2], &v[k + 2], &v[k + 3], &v[k + 4..]);
+    let sum = h.iter().fold(h_t.neg(encoder), |acc, x| acc.add(x, encoder));
+    let rows = [h_t.mult(&beta.add(t, encoder), encoder).sub(m, encoder)]
+        .into_iter()
+        .chain(
+            h.iter()
+                .zip(f)
+                .map(|(h, f)| h.mult(&beta.add(f, encoder), encoder).sub(q, encoder)),
+        )
+        .collect::<Vec<_>>();
+    [sum, combine(rows.iter(), gamma, encoder)]
+}
+
+impl Lookup {
+    // how many selected lookups hit each table row, slot by slot. a repeated table entry
+    // takes all its hits on its first row, and values missing from the table are not
+    // counted, so the proof fails
+    pub fn multiplicities(&self, witness: &[Vec<F>], encoder: &BFVEncoder) -> Vec<F> {
+        let table = self.table.iter().map(|x| x.get_value(encoder)).collect::<Vec<_>>();
+        let selector = self.selector.iter().map(|x| x.get_value(encoder)).collect::<Vec<_>>();
+        let columns = self
+            .columns
+            .iter()
+            .map(|&c| witness[c].iter().map(|x| x.get_value(encoder)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let slot_num = table[0].len();
+        let mut counts = vec![vec![0u64; slot_num]; table.len()];
+        for s in 0..slot_num {
+            let rows = table
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, t)| (t[s], i))
+                .collect::<HashMap<_, _>>();
+            for column in &columns {
+                for (x, f) in column.iter().enumerate() {
+                    if selector[x][s] != 0 {
+                        if let Some(&i) = rows.get(&f[s]) {
+                            counts[i][s] += 1;
+                        }
+                    }
+                }
+            }
+        }
+        counts.iter().map(|x| encoder.encode_unsigned(x).unwrap()).collect()
+    }
+
+    // the selected cells missing from the table, as (column, row, slots they miss in)
+    pub fn misses(&self, witness: &[Vec<F>], encoder: &BFVEncoder) -> Vec<(usize, usize, Vec<usize>)> {
+        let table = self.table.iter().map(|x| x.get_value(encoder)).collect::<Vec<_>>();
+        let slot_num = table[0].len();
+        let entries = (0..slot_num)
+            .map(|s| table.iter().map(|t| t[s]).collect::<HashSet<_>>())
+            .collect::<Vec<_>>();
+        let mut misses = vec![];
+        for &c in &self.columns {
+            for (row, x) in witness[c].iter().enumerate() {
+                let selector = self.selector[row].get_value(encoder);
+                let value = x.get_value(encoder);
+                let slots = (0..slot_num)
+                    .filter(|&s| selector[s] != 0 && !entries[s].contains(&value[s]))
+                    .collect::<Vec<_>>();
+                if !slots.is_empty() {
+                    misses.push((c, row, slots));
+                }
+            }
+        }
+        misses
+    }
+
+    // the columns of the lookup sumcheck in the order of value_num, the committed ones being
+    // the last k + 2: m, then the k + 1 helper columns
+    pub(crate) fn values(&self, witness: &[Vec<F>], multiplicities: Vec<F>, beta: &F, encoder: &BFVEncoder) -> Vec<Vec<F>> {
+        let fraction = |numerator: &[F], denominator: &[F]| {
+            numerator
+                .iter()
+                .zip(denominator)
+                .map(|(n, d)| n.mult(&beta.add(d, encoder).inv(encoder), encoder))
+                .collect::<Vec<_>>()
+        };
+        let h_t = fraction(&multiplicities, &self.table);
+        let h = self
+            .columns
+            .iter()
+            .map(|&c| fraction(&self.selector, &witness[c]))
+            .collect::<Vec<_>>();
+        [self.selector.clone(), self.table.clone()]
+            .into_iter()
+            .chain(self.columns.iter().map(|&c| witness[c].clone()))
+            .chain([multiplicities, h_t])
+            .chain(h)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use arithmetic::test_utils::gen_params_n_ctx;
+    use poly_commit::nil::NilPcProver;
+    use seal_fhe::{BFVEncoder, Plaintext};
+
+    use crate::{builder::CircuitBuilder, prover::Prover, verifier::Verifier};
+
+    type F = Plaintext;
+
+    #[test]
+    fn range_check() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        // x, y and x + y all in [0, 8)
+        let prove = |x: u64, y: u64| {
+            let mut builder = CircuitBuilder::new(&encoder);
+            builder.set_table((0..8).map(|i| F::from_int(i, &encoder)).collect());
+            let x = builder.alloc_variable(F::from_int(x, &encoder));
+            let y = builder.alloc_variable(F::from_int(y, &encoder));
+            let z = builder.add_gate(x, y);
+            for v in [x, y, z] {
+                builder.lookup(v);
+            }
+            let (circuit, witness) = builder.build().unwrap();
+            let nv = witness[0].len().ilog2() as usize;
+            // the hits counted in every slot
+            let hits = circuit
+                .lookup
+                .as_ref()
+                .unwrap()
+                .multiplicities(&witness, &encoder)
+                .iter()
+                .fold(F::from_int(0, &encoder), |acc, x| acc.add(x, &encoder));
+
+            let (pk, vk) = circuit.setup::<NilPcProver>(&(), &params, &ctx);
+            let prover = Prover::new(pk, &ctx, &params);
+            let verifier = Verifier::new(vk, &params, &ctx);
+            let proof = prover.prove(&(), nv, witness, &params, &ctx, &encoder);
+            assert!(proof.lookup.is_some());
+            (hits, verifier.verify(&(), nv, &[], &proof))
+        };
+        assert_eq!(prove(3, 4), (F::from_int(3, &encoder), true));
+        // 8 is missing from the table
+        assert_eq!(prove(3, 5), (F::from_int(2, &encoder), false));
+    }
+
+    #[test]
+    fn forged_multiplicities() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        let mut builder = CircuitBuilder::new(&encoder);
+        builder.set_table((0..8).map(|i| F::from_int(i, &encoder)).collect());
+        let x = builder.alloc_variable(F::from_int(5, &encoder));
+        builder.lookup(x);
+        let (circuit, witness) = builder.build().unwrap();
+        let nv = witness[0].len().ilog2() as usize;
+        let (pk, vk) = circuit.setup::<NilPcProver>(&(), &params, &ctx);
+        let prover = Prover::new(pk, &ctx, &params);
+        let verifier = Verifier::new(vk, &params, &ctx);
+        let proof = prover.prove(&(), nv, witness, &params, &ctx, &encoder);
+        assert!(verifier.verify(&(), nv, &[], &proof));
+
+        // m claimed other than committed, consistently at both points, is rejected
+        let mut forged = proof.clone();
+        let lookup = forged.lookup.as_mut().unwrap();
+        let one = F::from_int(1, &encoder);
+        lookup.evals[3] = lookup.evals[3].add(&one, &encoder);
+        lookup.openings[2] = lookup.openings[2].add(&one, &encoder);
+        assert!(!verifier.verify(&(), nv, &[], &forged));
+    }
+}
@@ -0,0 +1,133 @@
+use seal_fhe::{BFVEncoder, Plaintext};
+
+use crate::{grand_product::GrandProductProof, prover::combine};
+
+type F = Plaintext;
+
+// how the prover shows that the witness is the same along every copy cycle, with
+// f = beta + w + gamma * id and g = beta + w + gamma * sigma over every wire
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PermutationCheck {
+    // prod f = prod g, by two product trees over the wires padded to a power of two columns
+    #[default]
+    Product,
+    // sum 1 / f = sum 1 / g, by one sumcheck over committed columns of 1 / f and 1 / g,
+    // trading the layers of the product trees for a commitment
+    LogDerivative,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogDerivativeProof {
+    // round polynomials of the sum and the row identities
+    pub sumcheck: Vec<[Vec<F>; 2]>,
+    // 1 / f of every wire then 1 / g, at the sumcheck point and at the opening point
+    pub evals: Vec<F>,
+    pub openings: Vec<F>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PermutationProof {
+    Product(GrandProductProof),
+    LogDerivative(LogDerivativeProof),
+}
+
+impl PermutationProof {
+    pub fn check(&self) -> PermutationCheck {
+        match self {
+            PermutationProof::Product(_) => PermutationCheck::Product,
+            PermutationProof::LogDerivative(_) => PermutationCheck::LogDerivative,
+        }
+    }
+}
+
+// 1 / f of every wire, then 1 / g
+pub(crate) fn inverses(
+    witness: &[Vec<F>],
+    identity: &[Vec<F>],
+    permutation: &[Vec<F>],
+    beta: &F,
+    gamma: &F,
+    encoder: &BFVEncoder,
+) -> Vec<Vec<F>> {
+    [identity, permutation]
+        .into_iter()
+        .flat_map(|ids| {
+            witness.iter().zip(ids).map(|(w, id)| {
+                w.iter()
+                    .zip(id)
+                    .map(|(w, id)| beta.add(w, encoder).add(&gamma.mult(id, encoder), encoder).inv(encoder))
+                    .collect()
+            })
+        })
+        .collect()
+}
+
+// the sum of 1 / f - 1 / g, and the row identities batched by lambda, over the values of
+// one row: w, id, sigma, 1 / f and 1 / g of every wire in turn
+pub(crate) fn identities(v: &[F], beta: &F, gamma: &F, lambda: &F, encoder: &BFVEncoder) -> [F; 2] {
+    let one = F::from_int(1, encoder);
+    let mut sum = F::from_int(0, encoder);
+    let mut rows = vec![];
+    for x in v.chunks(5) {
+        let (w, id, sigma, h_f, h_g) = (&x[0], &x[1], &x[2], &x[3], &x[4]);
+        sum = sum.add(&h_f.sub(h_g, encoder), encoder);
+        for (h, y) in [(h_f, id), (h_g, sigma)] {
+            let f = beta.add(w, encoder).add(&gamma.mult(y, encoder), encoder);
+            rows.push(h.mult(&f, encoder).sub(&one, encoder));
+        }
+    }
+    [sum, combine(rows.iter(), lambda, encoder)]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use arithmetic::test_utils::gen_params_n_ctx;
+    use poly_commit::nil::NilPcProver;
+    use seal_fhe::{BFVEncoder, Plaintext};
+    use util::fiat_shamir::Proof;
+
+    use crate::{builder::CircuitBuilder, proof::HyperPlonkProof, prover::Prover, verifier::Verifier};
+
+    use super::{PermutationCheck, PermutationProof};
+
+    type F = Plaintext;
+
+    #[test]
+    fn log_derivative() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        // (x * y) * x, x being copied into both gates
+        let mut builder = CircuitBuilder::new(&encoder);
+        let x = builder.alloc_variable(F::random_pt(&encoder));
+        let y = builder.alloc_variable(F::random_pt(&encoder));
+        let xy = builder.mul_gate(x, y);
+        builder.mul_gate(xy, x);
+        let (mut circuit, witness) = builder.build().unwrap();
+        circuit.permutation_check = PermutationCheck::LogDerivative;
+        let nv = witness[0].len().ilog2() as usize;
+
+        let (pk, vk) = circuit.setup::<NilPcProver>(&(), &params, &ctx);
+        let prover = Prover::new(pk, &ctx, &params);
+        let verifier = Verifier::new(vk, &params, &ctx);
+        let proof = prover.prove(&(), nv, witness.clone(), &params, &ctx, &encoder);
+        assert!(matches!(proof.permutation_check, PermutationProof::LogDerivative(_)));
+        assert!(verifier.verify(&(), nv, &[], &proof));
+
+        let mut buffer = vec![0u8; proof.size()];
+        proof.serialize_into(&mut buffer);
+        let mut bytes = Proof::default();
+        bytes.append_u8_slice(&buffer, buffer.len());
+        assert_eq!(HyperPlonkProof::deserialize_from(&mut bytes, &ctx), Some(proof));
+
+        // x in the second gate, row 1 column b, no longer matches x in the first, the gates
+        // still holding
+        let mut witness = witness;
+        witness[1][1] = F::random_pt(&encoder);
+        witness[2][1] = witness[0][1].mult(&witness[1][1], &encoder);
+        assert!(circuit.check_witness(&witness, &encoder, 1).is_err());
+        let proof = prover.prove(&(), nv, witness, &params, &ctx, &encoder);
+        assert!(!verifier.verify(&(), nv, &[], &proof));
+    }
+}
@@ -0,0 +1,415 @@
+use std::mem::size_of;
+
+use poly_commit::{OpeningProof, ProofSerde};
+use seal_fhe::{Context, FromBytes, Plaintext, ToBytes};
+use util::fiat_shamir::Proof;
+
+use crate::{
+    circuit::column_var_num,
+    gate::GateSet,
+    grand_product::{GrandProductProof, TreeKind},
+    lookup::value_num,
+    permutation::{LogDerivativeProof, PermutationCheck, PermutationProof},
+    zk::{mask_column_num, mask_shapes, ZkProof},
+};
+
+type F = Plaintext;
+
+// claimed evaluations sent by the prover, in the order the verifier consumes them
+#[derive(Debug, Clone, PartialEq)]
+pub struct HyperPlonkEvals {
+    // selectors and witness at the point of the gate sumcheck
+    pub gate_selectors: Vec<F>,
+    pub gate_witness: Vec<F>,
+    // witness and permutation at the point of the product check
+    pub prod_witness: Vec<F>,
+    pub prod_permutation: Vec<F>,
+    // every committed polynomial at the point of the opening sumcheck, checked by the pcs
+    pub selectors: Vec<F>,
+    pub permutation: Vec<F>,
+    pub witness: Vec<F>,
+}
+
+impl HyperPlonkEvals {
+    fn lists(&self) -> [&Vec<F>; 7] {
+        [
+            &self.gate_selectors,
+            &self.gate_witness,
+            &self.prod_witness,
+            &self.prod_permutation,
+            &self.selectors,
+            &self.permutation,
+            &self.witness,
+        ]
+    }
+
+    fn size(&self) -> usize {
+        self.lists()
+            .iter()
+            .map(|x| size_of::<u64>() + fs_size(x.iter()))
+            .sum()
+    }
+}
+
+// the logUp part of a proof, for circuits with a lookup
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupProof {
+    // round polynomials of the sum and the row identities
+    pub sumcheck: Vec<[Vec<F>; 2]>,
+    // q, t, the lookup columns, m, h_t and the h_i at the point of the lookup sumcheck
+    pub evals: Vec<F>,
+    // q, t, m, h_t and the h_i at the point of the opening sumcheck, the lookup columns being
+    // among the witness evaluations
+    pub openings: Vec<F>,
+}
+
+impl LookupProof {
+    fn size(&self) -> usize {
+        rounds_size(&self.sumcheck)
+            + size_of::<u64>()
+            + fs_size(&self.evals)
+            + size_of::<u64>()
+            + fs_size(&self.openings)
+    }
+}
+
+impl ZkProof {
+    fn size(&self) -> usize {
+        size_of::<u64>()
+            + fs_size(&self.sums)
+            + size_of::<u64>()
+            + fs_size(&self.evals)
+            + rounds_size(&self.sumcheck)
+            + size_of::<u64>()
+            + fs_size(&self.openings)
+    }
+}
+
+// serialized bytes of each part of a proof
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProofSize {
+    pub gate_sumcheck: usize,
+    pub permutation_check: usize,
+    pub lookup: usize,
+    pub opening_sumcheck: usize,
+    pub zk: usize,
+    pub evals: usize,
+    pub pcs: usize,
+}
+
+impl ProofSize {
+    pub fn total(&self) -> usize {
+        self.gate_sumcheck
+            + self.permutation_check
+            + self.lookup
+            + self.opening_sumcheck
+            + self.zk
+            + self.evals
+            + self.pcs
+    }
+}
+
+// everything the prover sends. plaintexts have no fixed encoding size, so each one is
+// written with a u64 length prefix, as is every list, big-endian like OpeningProof
+#[derive(Debug, Clone, PartialEq)]
+pub struct HyperPlonkProof {
+    // round polynomials of the gate identity sumcheck
+    pub gate_sumcheck: Vec<[Vec<F>; 1]>,
+    // the two product trees or the log-derivative sumcheck of the permutation check
+    pub permutation_check: PermutationProof,
+    pub lookup: Option<LookupProof>,
+    // round polynomials of the sumcheck batching all evaluations, the public input and
+    // lookup claims included, into one opening
+    pub opening_sumcheck: Vec<[Vec<F>; 4]>,
+    // the sumcheck masks in zk mode
+    pub zk: Option<ZkProof>,
+    pub evals: HyperPlonkEvals,
+    // the witness commitment, then those of the masks, the inverse columns and the lookup
+    // columns if any, then the opening, and that of the masks
+    pub pcs: OpeningProof,
+}
+
+fn f_size(x: &F) -> usize {
+    size_of::<u64>() + x.as_bytes().unwrap().len()
+}
+
+fn fs_size<'a>(xs: impl IntoIterator<Item = &'a F>) -> usize {
+    xs.into_iter().map(f_size).sum()
+}
+
+fn rounds_size<const M: usize>(rounds: &[[Vec<F>; M]]) -> usize {
+    size_of::<u64>()
+        + rounds
+            .iter()
+            .flatten()
+            .map(|x| size_of::<u64>() + fs_size(x))
+            .sum::<usize>()
+}
+
+fn permutation_size(proof: &PermutationProof) -> usize {
+    size_of::<u64>()
+        + match proof {
+            PermutationProof::Product(x) => {
+                size_of::<u64>()
+                    + x.sumchecks.iter().map(|x| rounds_size(x)).sum::<usize>()
+                    + size_of::<u64>()
+                    + x.evals
+                        .iter()
+                        .map(|x| size_of::<u64>() + fs_size(x))
+                        .sum::<usize>()
+            }
+            PermutationProof::LogDerivative(x) => {
+                rounds_size(&x.sumcheck)
+                    + size_of::<u64>()
+                    + fs_size(&x.evals)
+                    + size_of::<u64>()
+                    + fs_size(&x.openings)
+            }
+        }
+}
+
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    idx: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn put_len(&mut self, len: usize) {
+        self.put_slice(&(len as u64).to_be_bytes());
+    }
+
+    fn put_slice(&mut self, bytes: &[u8]) {
+        self.buffer[self.idx..self.idx + bytes.len()].copy_from_slice(bytes);
+        self.idx += bytes.len();
+    }
+
+    fn put_f(&mut self, x: &F) {
+        let bytes = x.as_bytes().unwrap();
+        self.put_len(bytes.len());
+        self.put_slice(&bytes);
+    }
+
+    fn put_fs(&mut self, xs: &[F]) {
+        self.put_len(xs.len());
+        xs.iter().for_each(|x| self.put_f(x));
+    }
+
+    fn put_rounds<const M: usize>(&mut self, rounds: &[[Vec<F>; M]]) {
+        self.put_len(rounds.len());
+        rounds.iter().flatten().for_each(|x| self.put_fs(x));
+    }
+
+    // a tag, 0 for product trees and 1 for the log-derivative sumcheck, then the proof
+    fn put_permutation(&mut self, proof: &PermutationProof) {
+        match proof {
+            PermutationProof::Product(x) => {
+                self.put_len(0);
+                self.put_len(x.sumchecks.len());
+                x.sumchecks.iter().for_each(|x| self.put_rounds(x));
+                self.put_len(x.evals.len());
+                x.evals.iter().for_each(|x| self.put_fs(x));
+            }
+            PermutationProof::LogDerivative(x) => {
+                self.put_len(1);
+                self.put_rounds(&x.sumcheck);
+                self.put_fs(&x.evals);
+                self.put_fs(&x.openings);
+            }
+        }
+    }
+}
+
+// a length is rejected when the remaining bytes cannot hold that many items of at least
+// `unit` bytes, so a corrupted prefix cannot trigger a huge allocation
+fn get_len(proof: &mut Proof, unit: usize) -> Option<usize> {
+    if proof.remaining() < size_of::<u64>() {
+        return None;
+    }
+    let len = u64::from_be_bytes(proof.get_next_slice(size_of::<u64>()).try_into().unwrap());
+    (len <= (proof.remaining() / unit) as u64).then_some(len as usize)
+}
+
+fn get_f(proof: &mut Proof, ctx: &Context) -> Option<F> {
+    let len = get_len(proof, 1)?;
+    F::from_bytes(ctx, &proof.get_next_slice(len)).ok()
+}
+
+fn get_fs(proof: &mut Proof, ctx: &Context) -> Option<Vec<F>> {
+    let len = get_len(proof, size_of::<u64>())?;
+    (0..len).map(|_| get_f(proof, ctx)).collect()
+}
+
+fn get_rounds<const M: usize>(proof: &mut Proof, ctx: &Context) -> Option<Vec<[Vec<F>; M]>> {
+    let len = get_len(proof, size_of::<u64>())?;
+    (0..len)
+        .map(|_| {
+            (0..M)
+                .map(|_| get_fs(proof, ctx))
+                .collect::<Option<Vec<_>>>()?
+                .try_into()
+                .ok()
+        })
+        .collect()
+}
+
+fn get_permutation(proof: &mut Proof, ctx: &Context) -> Option<PermutationProof> {
+    match get_len(proof, 1)? {
+        0 => {
+            let len = get_len(proof, size_of::<u64>())?;
+            let sumchecks = (0..len)
+                .map(|_| get_rounds(proof, ctx))
+                .collect::<Option<Vec<_>>>()?;
+            let len = get_len(proof, size_of::<u64>())?;
+            let evals = (0..len)
+                .map(|_| get_fs(proof, ctx))
+                .collect::<Option<Vec<_>>>()?;
+            Some(PermutationProof::Product(GrandProductProof { sumchecks, evals }))
+        }
+        1 => Some(PermutationProof::LogDerivative(LogDerivativeProof {
+            sumcheck: get_rounds(proof, ctx)?,
+            evals: get_fs(proof, ctx)?,
+            openings: get_fs(proof, ctx)?,
+        })),
+        _ => None,
+    }
+}
+
+impl HyperPlonkProof {
+    pub fn size_breakdown(&self) -> ProofSize {
+        ProofSize {
+            gate_sumcheck: rounds_size(&self.gate_sumcheck),
+            permutation_check: permutation_size(&self.permutation_check),
+            lookup: size_of::<u64>() + self.lookup.as_ref().map_or(0, |x| x.size()),
+            opening_sumcheck: rounds_size(&self.opening_sumcheck),
+            zk: size_of::<u64>() + self.zk.as_ref().map_or(0, |x| x.size()),
+            evals: self.evals.size(),
+            pcs: self.pcs.size(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size_breakdown().total()
+    }
+
+    pub fn serialize_into(&self, buffer: &mut [u8]) {
+        let mut writer = Writer { buffer, idx: 0 };
+        writer.put_rounds(&self.gate_sumcheck);
+        writer.put_permutation(&self.permutation_check);
+        writer.put_len(self.lookup.is_some() as usize);
+        if let Some(lookup) = &self.lookup {
+            writer.put_rounds(&lookup.sumcheck);
+            writer.put_fs(&lookup.evals);
+            writer.put_fs(&lookup.openings);
+        }
+        writer.put_rounds(&self.opening_sumcheck);
+        writer.put_len(self.zk.is_some() as usize);
+        if let Some(zk) = &self.zk {
+            writer.put_fs(&zk.sums);
+            writer.put_fs(&zk.evals);
+            writer.put_rounds(&zk.sumcheck);
+            writer.put_fs(&zk.openings);
+        }
+        self.evals.lists().iter().for_each(|x| writer.put_fs(x));
+        self.pcs.serialize_into(&mut writer.buffer[writer.idx..]);
+    }
+
+    pub fn deserialize_from(proof: &mut Proof, ctx: &Context) -> Option<Self> {
+        let gate_sumcheck = get_rounds(proof, ctx)?;
+        let permutation_check = get_permutation(proof, ctx)?;
+        let lookup = match get_len(proof, 1)? {
+            0 => None,
+            1 => Some(LookupProof {
+                sumcheck: get_rounds(proof, ctx)?,
+                evals: get_fs(proof, ctx)?,
+                openings: get_fs(proof, ctx)?,
+            }),
+            _ => return None,
+        };
+        let opening_sumcheck = get_rounds(proof, ctx)?;
+        let zk = match get_len(proof, 1)? {
+            0 => None,
+            1 => Some(ZkProof {
+                sums: get_fs(proof, ctx)?,
+                evals: get_fs(proof, ctx)?,
+                sumcheck: get_rounds(proof, ctx)?,
+                openings: get_fs(proof, ctx)?,
+            }),
+            _ => return None,
+        };
+        let evals = HyperPlonkEvals {
+            gate_selectors: get_fs(proof, ctx)?,
+            gate_witness: get_fs(proof, ctx)?,
+            prod_witness: get_fs(proof, ctx)?,
+            prod_permutation: get_fs(proof, ctx)?,
+            selectors: get_fs(proof, ctx)?,
+            permutation: get_fs(proof, ctx)?,
+            witness: get_fs(proof, ctx)?,
+        };
+        Some(HyperPlonkProof {
+            gate_sumcheck,
+            permutation_check,
+            lookup,
+            opening_sumcheck,
+            zk,
+            evals,
+            pcs: OpeningProof::deserialize_from(proof)?,
+        })
+    }
+
+    // the number of messages the verifier expects for nv variables, the permutation check,
+    // lookup_num lookup columns and zk mode, checked before reading so a proof of the wrong
+    // shape is rejected instead of indexed out of bounds
+    pub fn has_shape(
+        &self,
+        nv: usize,
+        gates: &GateSet,
+        permutation_check: PermutationCheck,
+        lookup_num: Option<usize>,
+        zk: bool,
+    ) -> bool {
+        let rounds = |x: &[Vec<F>], degree: usize| x.iter().all(|x| x.len() == degree + 1);
+        let prod_var_num = nv + column_var_num(gates.wire_num);
+        self.gate_sumcheck.len() == nv
+            && self.gate_sumcheck.iter().all(|x| rounds(x, gates.degree() + 1))
+            && self.permutation_check.check() == permutation_check
+            && match &self.permutation_check {
+                PermutationProof::Product(x) => x.has_shape(&[(TreeKind::Product, prod_var_num); 2]),
+                PermutationProof::LogDerivative(x) => {
+                    x.sumcheck.len() == nv
+                        && x.sumcheck.iter().all(|x| rounds(x, 3))
+                        && x.evals.len() == 2 * gates.wire_num
+                        && x.openings.len() == 2 * gates.wire_num
+                }
+            }
+            && match (&self.lookup, lookup_num) {
+                (None, None) => true,
+                (Some(x), Some(k)) => {
+                    x.sumcheck.len() == nv
+                        && x.sumcheck.iter().all(|x| rounds(x, 3))
+                        && x.evals.len() == value_num(k)
+                        && x.openings.len() == k + 4
+                }
+                _ => false,
+            }
+            && self.opening_sumcheck.len() == nv
+            && self.opening_sumcheck.iter().all(|x| rounds(x, 2))
+            && match (&self.zk, zk) {
+                (None, false) => true,
+                (Some(x), true) => {
+                    let output_num = mask_shapes(gates).iter().map(|(m, _)| m).sum::<usize>();
+                    x.sums.len() == output_num
+                        && x.evals.len() == output_num
+                        && x.sumcheck.len() == nv
+                        && x.sumcheck.iter().all(|x| rounds(x, 2))
+                        && x.openings.len() == mask_column_num(nv, gates)
+                }
+                _ => false,
+            }
+            && self.evals.gate_selectors.len() == gates.selector_num
+            && self.evals.selectors.len() == gates.selector_num
+            && self.evals.lists()[1..4]
+                .iter()
+                .chain(&self.evals.lists()[5..])
+                .all(|x| x.len() == gates.wire_num)
+    }
+}
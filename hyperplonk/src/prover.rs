@@ -1,241 +1,533 @@
 use arithmetic::{field::Field, poly::MultiLinearPoly};
-use poly_commit::{CommitmentSerde, PolyCommitProver};
+use poly_commit::{CommitmentSerde, OpeningProof, PolynomialCommitmentScheme, ProofSerde};
 use seal_fhe::{BFVEncoder, Ciphertext, Context, EncryptionParameters, Plaintext};
-use util::{fiat_shamir::{Proof, Transcript}, random_oracle::RandomOracle};
+use util::fiat_shamir::{Proof, Transcript};
 
-use crate::{prod_eq_check::ProdEqCheck, sumcheck::Sumcheck};
+use crate::{
+    circuit::{column_var_num, identity_permutation},
+    gate::GateSet,
+    grand_product::{GrandProduct, Leaves},
+    lookup::{identities, value_num, Lookup},
+    permutation::{self, LogDerivativeProof, PermutationCheck, PermutationProof},
+    proof::{HyperPlonkEvals, HyperPlonkProof, LookupProof},
+    sumcheck::Sumcheck,
+    zk::{basis_columns, mask_column_num, mask_columns, mask_shapes, SumcheckMask, ZkProof, ZK_ROWS},
+};
 
 type F = Plaintext;
 type Q = Ciphertext;
 
-pub struct ProverKey<'a, PC: PolyCommitProver> {
-    pub selector: MultiLinearPoly<'a>,
-    pub commitments: PC,
-    pub permutation: [MultiLinearPoly<'a>; 3],
+pub struct ProverKey<'a, PC: PolynomialCommitmentScheme<'a>> {
+    pub selectors: Vec<MultiLinearPoly<'a>>,
+    pub commitments: PC::ProverData,
+    pub permutation: Vec<MultiLinearPoly<'a>>,
+    pub gates: GateSet,
+    pub public_num: usize,
+    pub lookup: Option<Lookup>,
+    pub permutation_check: PermutationCheck,
+    pub zk: bool,
 }
 
-pub struct Prover<'a, PC: PolyCommitProver> {
+impl<'a, PC: PolynomialCommitmentScheme<'a>> ProverKey<'a, PC> {
+    // the selector, permutation and lookup polynomials, in the order they are committed
+    pub fn preprocessed(&self) -> Vec<Vec<F>> {
+        let lookup_polys = self.lookup.iter().flat_map(|x| [&x.selector, &x.table]);
+        self.selectors
+            .iter()
+            .chain(self.permutation.iter())
+            .map(|x| &x.evals)
+            .chain(lookup_polys)
+            .cloned()
+            .collect()
+    }
+}
+
+// the prover side of `verifier::verify_preprocessed`: sends the evaluations of the
+// preprocessed polynomials of every key at `point`, then opens their combination with
+// transcript challenges as one batch. the keys must share their shape
+pub fn open_preprocessed<'a, PC: PolynomialCommitmentScheme<'a>>(
+    pp: &PC::Param,
+    keys: &[&ProverKey<'a, PC>],
+    point: Vec<F>,
+    encoder: &BFVEncoder,
+    transcript: &mut Transcript,
+) -> PC::Proof {
+    let polys = keys.iter().map(|x| x.preprocessed()).collect::<Vec<_>>();
+    for x in polys.iter().flatten() {
+        transcript.append_f(&MultiLinearPoly::eval_multilinear(x, &point, encoder), encoder);
+    }
+    let coeffs = (0..keys.len()).map(|_| transcript.challenge_f(encoder)).collect::<Vec<_>>();
+    let combined = (0..polys[0].len())
+        .map(|j| {
+            (0..polys[0][j].len())
+                .map(|i| {
+                    polys.iter().zip(coeffs.iter()).fold(F::from_int(0, encoder), |acc, (p, c)| {
+                        acc.add(&p[j][i].mult(c, encoder), encoder)
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let (_, data) = PC::commit(pp, &combined);
+    PC::open(pp, &[&data], &point, transcript)
+}
+
+// the public inputs padded with zeros to a power of two, the size of the subcube of a(X)
+// they are checked on
+pub(crate) fn padded_public_num(public_num: usize) -> usize {
+    match public_num {
+        0 => 0,
+        n => n.next_power_of_two(),
+    }
+}
+
+// the padded public inputs open the transcript, so every challenge drawn from it depends
+// on them
+pub(crate) fn bind_public_inputs(transcript: &mut Transcript, public_inputs: &[F], encoder: &BFVEncoder) {
+    append_all(transcript, public_inputs, encoder);
+}
+
+// a random point of the subcube holding the padded public inputs: the low coordinates are
+// drawn from the transcript and the rest are zero
+pub(crate) fn public_point(nv: usize, public_num: usize, transcript: &mut Transcript, encoder: &BFVEncoder) -> Vec<F> {
+    let log_num = public_num.max(1).ilog2() as usize;
+    (0..nv)
+        .map(|i| if i < log_num { transcript.challenge_f(encoder) } else { F::from_int(0, encoder) })
+        .collect()
+}
+
+// the commitment goes into the transcript, and its bytes into the pcs proof the verifier
+// reads it back from
+fn send_commitment<'a, PC: PolynomialCommitmentScheme<'a>>(
+    commitment: &PC::Commitment,
+    nv: usize,
+    poly_num: usize,
+    encoder: &BFVEncoder,
+    transcript: &mut Transcript,
+    pcs: &mut Vec<u8>,
+) {
+    let mut buffer = vec![0u8; PC::Commitment::size(nv, poly_num, encoder)];
+    commitment.serialize_into(&mut buffer, encoder);
+    transcript.append_u8_slice(&buffer, buffer.len());
+    pcs.extend_from_slice(&buffer);
+}
+
+// an opening goes into the pcs proof after the commitments, length-prefixed
+fn send_opening<'a, PC: PolynomialCommitmentScheme<'a>>(opening: &PC::Proof, pcs: &mut Vec<u8>) {
+    let mut buffer = vec![0u8; opening.size()];
+    opening.serialize_into(&mut buffer);
+    pcs.extend_from_slice(&buffer);
+}
+
+pub(crate) fn append_all<'b>(transcript: &mut Transcript, values: impl IntoIterator<Item = &'b F>, encoder: &BFVEncoder) {
+    for x in values {
+        transcript.append_f(x, encoder);
+    }
+}
+
+// values[0] + r * values[1] + r^2 * values[2] + ...
+pub(crate) fn combine<'b>(values: impl DoubleEndedIterator<Item = &'b F>, r: &F, encoder: &BFVEncoder) -> F {
+    values
+        .rev()
+        .fold(F::from_int(0, encoder), |acc, x| acc.mult(r, encoder).add(x, encoder))
+}
+
+pub struct Prover<'a, PC: PolynomialCommitmentScheme<'a>> {
     pub prover_key: ProverKey<'a, PC>,
     pub ctx: &'a Context,
     pub params: &'a EncryptionParameters,
     pub encoder: BFVEncoder<'a>,
-    pub oracle: &'a RandomOracle<'a>,
 }
 
-impl<'a, PC: PolyCommitProver> Prover<'a, PC> {
-    pub fn new(prover_key: ProverKey<'a, PC>, ctx: &'a Context, params: &'a EncryptionParameters, oracle: &'a RandomOracle) -> Self {
+impl<'a, PC: PolynomialCommitmentScheme<'a>> Prover<'a, PC> {
+    pub fn new(prover_key: ProverKey<'a, PC>, ctx: &'a Context, params: &'a EncryptionParameters) -> Self {
         Self {
             prover_key,
             ctx,
             params,
             encoder: BFVEncoder::new(ctx, params).unwrap(),
-            oracle,
         }
     }
 
     pub fn prove(&self, pp: &PC::Param, nv: usize, 
-        witness: [Vec<F>; 3], params: &'a EncryptionParameters, 
-        ctx: &'a Context, encoder: &'a BFVEncoder, oracle: &'a RandomOracle) -> (Vec<[Vec<F>; 1]>, Vec<Vec<[Vec<F>; 2]>>, Vec<F>, Vec<[Vec<Plaintext>; 2]>, Vec<F>) {
+        mut witness: Vec<Vec<F>>, params: &'a EncryptionParameters, 
+        ctx: &'a Context, encoder: &'a BFVEncoder) -> HyperPlonkProof {
 
-        // 0. setup: initialize fiat shamir and commit witness
-        // let mut transcript = Transcript::new();
-        // let witness_pc = PC::new(pp, &witness);
-
-        // let commit = witness_pc.commit();
-        // let mut buffer = vec![0u8; PC::Commitment::size(nv, 3)];
-        // commit.serialize_into(&mut buffer);
-        // transcript.append_u8_slice(&buffer, PC::Commitment::size(nv, 3));
+        // 0. setup: initialize fiat shamir and commit witness, blinded in zk mode
+        if self.prover_key.zk {
+            for column in witness.iter_mut() {
+                for x in &mut column[(1 << nv) - ZK_ROWS..] {
+                    *x = F::random_pt(encoder);
+                }
+            }
+        }
+        let mut transcript = Transcript::new();
+        let public_num = padded_public_num(self.prover_key.public_num);
+        bind_public_inputs(&mut transcript, &witness[0][..public_num], encoder);
+        // the commitments then the openings, without the messages between them
+        let mut pcs = vec![];
+        let (witness_commit, witness_pc) = PC::commit(pp, &witness);
+        let wire_num = self.prover_key.gates.wire_num;
+        assert_eq!(witness.len(), wire_num);
+        send_commitment::<PC>(&witness_commit, nv, wire_num, encoder, &mut transcript, &mut pcs);
 
-        let mut transcript = vec![];
+        // the masks of the gate, log-derivative and opening sumchecks are committed before them
+        let gates = &self.prover_key.gates;
+        let masks = self.prover_key.zk.then(|| {
+            let masks = mask_shapes(gates).map(|(m, d)| SumcheckMask::random(m, nv, d, encoder));
+            let column_num = mask_column_num(nv, gates);
+            let columns = mask_columns(&masks, nv, column_num, encoder);
+            let (mask_commit, mask_pc) = PC::commit(pp, &columns);
+            send_commitment::<PC>(&mask_commit, nv, column_num, encoder, &mut transcript, &mut pcs);
+            let sums = masks.iter().flat_map(|x| x.sums(encoder)).collect::<Vec<_>>();
+            append_all(&mut transcript, &sums, encoder);
+            // rho scaling the masks, drawn once they are bound
+            let rho = transcript.challenge_f(encoder);
+            (masks, columns, mask_pc, sums, rho)
+        });
+        let mask = |i: usize| masks.as_ref().map(|(masks, .., rho)| (&masks[i], rho));
 
-        let bookkeeping = witness
-            .clone()
-            .map(|x| x.into_iter().map(|i| i).collect::<Vec<_>>());
+        let bookkeeping = &witness;
 
         // 1. generate challenge vector and eq polynomial
-        // let r = (0..nv)
-        //     .map(|_| transcript.challenge_f(self.ctx))
-        //     .collect::<Vec<_>>();
-        let r = (0..nv).map(|i| oracle.folding_challenges[i].clone()).collect::<Vec<_>>();
+        let r = (0..nv).map(|_| transcript.challenge_f(encoder)).collect::<Vec<_>>();
         let eq_r = MultiLinearPoly::new_eq(&r, self.params, self.ctx);
 
         // 2. sumcheck prove:
         // 2.1 evals are: all prover keys (selectors), L poly, R poly, O poly, eq evals
-        // 2.2 f is the target function: eq((1-selector)*(l+r) + selector*l*r + minus_o)
-        let (sumcheck_point, v, total_sums) = Sumcheck::prove(
-            [
-                self.prover_key
-                    .selector
-                    .evals
-                    .iter()
-                    .map(|x| x.clone())
-                    .collect(),
-                bookkeeping[0].clone(),
-                bookkeeping[1].clone(),
-                bookkeeping[2].clone(),
-                eq_r.evals.clone(),
-            ],
-            4,
-            // &mut transcript,
-            // |v: [F; 5]| [v[4] * ((1 - v[0]) * (v[1] + v[2]) + v[0] * v[1] * v[2] + v[3])],
-            |v: [F; 5]| [
-                F::from_int(1, &encoder).sub(&v[0], &encoder).mult(&v[1].add(&v[2], &encoder), &encoder)
-                    .add(&v[0].mult(&v[1], &encoder).mult(&v[2], &encoder), &encoder).add(&v[3], &encoder)
-                    .mult(&v[4], &encoder)
+        // 2.2 f is the target function: eq * gates(selectors, l, r, o)
+        let selector_num = gates.selector_num;
+        let (sumcheck_point, v, total_sums) = Sumcheck::prove_masked(
+            self.prover_key
+                .selectors
+                .iter()
+                .map(|x| x.evals.clone())
+                .chain(bookkeeping.iter().cloned())
+                .chain([eq_r.evals.clone()])
+                .collect(),
+            gates.degree() + 1,
+            |v: Vec<F>| [
+                gates
+                    .evaluate(&v[..selector_num], &v[selector_num..selector_num + wire_num], encoder)
+                    .mult(&v[selector_num + wire_num], encoder)
             ],
+            mask(0),
             ctx,
             encoder,
-            oracle,
+            &mut transcript,
         );
+        append_all(&mut transcript, &v[..selector_num + wire_num], encoder);
 
-        for i in 0..4 {
-            transcript.push(v[i].clone());
-        }
-        let witness_flatten = bookkeeping[0]
-            .clone()
-            .into_iter()
-            .chain(bookkeeping[1].clone().into_iter())
-            .chain(bookkeeping[2].clone().into_iter())
-            .chain((0..(1 << nv)).into_iter().map(|_| F::from_int(0, &self.encoder)))
-            .collect::<Vec<_>>();
-        let identical = MultiLinearPoly::new_identical(nv, F::from_int(0, &self.encoder), self.params, self.ctx)
-            .evals
-            .into_iter()
-            .chain(
-                MultiLinearPoly::new_identical(nv, F::from_int(1 << 29, &self.encoder), self.params, self.ctx)
-                    .evals
-                    .into_iter(),
-            )
-            .chain(
-                MultiLinearPoly::new_identical(nv, F::from_int(1 << 30, &self.encoder), self.params, self.ctx)
-                    .evals
-                    .into_iter(),
-            )
-            .chain((0..(1 << nv)).into_iter().map(|_| F::from_int(0, &self.encoder)))
-            .collect::<Vec<_>>();
-        let permutation = self.prover_key.permutation[0]
-            .clone()
-            .evals
-            .into_iter()
-            .chain(self.prover_key.permutation[1].clone().evals.into_iter())
-            .chain(self.prover_key.permutation[2].clone().evals.into_iter())
-            .chain((0..(1 << nv)).into_iter().map(|_| F::from_int(0, &self.encoder)))
-            .collect::<Vec<_>>();
+        // beta and gamma of the permutation check, the witness and permutation being evaluated
+        // at prod_point afterwards either way
+        let (beta, gamma) = (transcript.challenge_f(encoder), transcript.challenge_f(encoder));
+        let (prod_point, mut permutation_check, inverses) = match self.prover_key.permutation_check {
+            PermutationCheck::Product => {
+                // columns padded to a power of two with zero witnesses and the identity permutation
+                let column_vars = column_var_num(wire_num);
+                let identical = MultiLinearPoly::new_identical(nv + column_vars, F::from_int(0, &self.encoder), self.params, self.ctx).evals;
+                let witness_flatten = bookkeeping
+                    .iter()
+                    .flatten()
+                    .cloned()
+                    .chain((wire_num << nv..identical.len()).map(|_| F::from_int(0, &self.encoder)))
+                    .collect::<Vec<_>>();
+                let permutation = self
+                    .prover_key
+                    .permutation
+                    .iter()
+                    .flat_map(|x| x.evals.iter().cloned())
+                    .chain(identical[wire_num << nv..].iter().cloned())
+                    .collect::<Vec<_>>();
 
-        // let r = [0; 2].map(|_| transcript.challenge_f(self.ctx));
+                let evals1 = witness_flatten
+                    .iter()
+                    .zip(identical.iter())
+                    .map(|(x, y)| F::add(&F::add(&beta, &x, &self.encoder), &F::mult(&gamma, &y, &self.encoder), &self.encoder))
+                    .collect::<Vec<_>>();
+                let evals2 = witness_flatten
+                    .iter()
+                    .zip(permutation.iter())
+                    .map(|(x, y)| F::add(&F::add(&beta, &x, &self.encoder), &F::mult(&gamma, &y, &self.encoder), &self.encoder))
+                    .collect::<Vec<_>>();
+                // both trees have the same size, so their leaves are claimed at the same point
+                let (mut prod_points, prod_check) = GrandProduct::prove_batch(
+                    vec![Leaves::Product(evals1), Leaves::Product(evals2)],
+                    params,
+                    ctx,
+                    encoder,
+                    &mut transcript,
+                );
+                (prod_points.swap_remove(0), PermutationProof::Product(prod_check), None)
+            }
+            PermutationCheck::LogDerivative => {
+                // 1 / f and 1 / g are committed after the witness, then one sumcheck proves
+                // their sums equal and the row identities, over the wires without padding
+                let identity = identity_permutation(wire_num, nv, encoder);
+                let permutation = self.prover_key.permutation.iter().map(|x| x.evals.clone()).collect::<Vec<_>>();
+                let inverses = permutation::inverses(&witness, &identity, &permutation, &beta, &gamma, encoder);
+                let (inverses_commit, inverses_pc) = PC::commit(pp, &inverses);
+                send_commitment::<PC>(&inverses_commit, nv, 2 * wire_num, encoder, &mut transcript, &mut pcs);
+                // batches the row identities
+                let lambda = transcript.challenge_f(encoder);
+                let (point, _, sumcheck) = Sumcheck::prove_masked(
+                    (0..wire_num)
+                        .flat_map(|j| {
+                            [&witness[j], &identity[j], &permutation[j], &inverses[j], &inverses[wire_num + j]]
+                        })
+                        .cloned()
+                        .chain([eq_r.evals.clone()])
+                        .collect(),
+                    3,
+                    |v: Vec<F>| {
+                        let [sum, rows] = permutation::identities(&v[..5 * wire_num], &beta, &gamma, &lambda, encoder);
+                        [sum, rows.mult(&v[5 * wire_num], encoder)]
+                    },
+                    mask(1),
+                    ctx,
+                    encoder,
+                    &mut transcript,
+                );
+                let proof = LogDerivativeProof {
+                    sumcheck,
+                    evals: inverses
+                        .iter()
+                        .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &self.encoder))
+                        .collect(),
+                    openings: vec![],
+                };
+                append_all(&mut transcript, &proof.evals, encoder);
+                (point, PermutationProof::LogDerivative(proof), Some((inverses_pc, inverses)))
+            }
+        };
 
-        let r_0 = r[0].clone().get_value(encoder)[0];
-        let evals1 = witness_flatten
+        let prod_witness = witness
             .iter()
-            .zip(identical.iter())
-            .map(|(x, y)| F::add(&F::add(&r[0], &x, &self.encoder), &F::mult(&r[1], &y, &self.encoder), &self.encoder))
+            .map(|x| MultiLinearPoly::eval_multilinear(x, &prod_point[..nv], &self.encoder))
             .collect::<Vec<_>>();
-        let evals2 = witness_flatten
+        let prod_permutation = self
+            .prover_key
+            .permutation
             .iter()
-            .zip(permutation.iter())
-            .map(|(x, y)| F::add(&F::add(&r[0], &x, &self.encoder), &F::mult(&r[1], &y, &self.encoder), &self.encoder))
+            .map(|x| MultiLinearPoly::eval_multilinear(&x.evals, &prod_point[..nv], &self.encoder))
             .collect::<Vec<_>>();
-        let (prod_point, prod_transcript, prod_total_sums) = ProdEqCheck::prove([evals1, evals2], params, ctx, encoder, oracle);
+        append_all(&mut transcript, prod_witness.iter().chain(&prod_permutation), encoder);
 
-        for i in 0..3 {
-            let v = MultiLinearPoly::eval_multilinear(
-                &witness[i],
-                &prod_point[..nv],
-                &self.encoder
+        // 3. logUp: the multiplicities and then the helper columns are committed after the
+        // witness, then one sumcheck proves the sum and the row identities
+        let lookup = self.prover_key.lookup.as_ref().map(|lookup| {
+            let k = lookup.columns.len();
+            // beta of its own, apart from those of the permutation check, drawn once m is
+            // bound, and gamma batching the row identities once the helper columns are too
+            let multiplicities = lookup.multiplicities(&witness, encoder);
+            let (m_commit, m_pc) = PC::commit(pp, std::slice::from_ref(&multiplicities));
+            send_commitment::<PC>(&m_commit, nv, 1, encoder, &mut transcript, &mut pcs);
+            let beta = transcript.challenge_f(encoder);
+            let values = lookup.values(&witness, multiplicities, &beta, encoder);
+            let (helper_commit, helper_pc) = PC::commit(pp, &values[k + 3..]);
+            send_commitment::<PC>(&helper_commit, nv, k + 1, encoder, &mut transcript, &mut pcs);
+            let lookup_pc = [m_pc, helper_pc];
+            let gamma = transcript.challenge_f(encoder);
+            let (lookup_point, evals, sumcheck) = Sumcheck::prove_vec(
+                values.iter().cloned().chain([eq_r.evals.clone()]).collect(),
+                3,
+                |v: Vec<F>| {
+                    let [sum, rows] = identities(&v[..value_num(k)], k, &beta, &gamma, encoder);
+                    [sum, rows.mult(&v[value_num(k)], encoder)]
+                },
+                ctx,
+                encoder,
+                &mut transcript,
             );
-            transcript.push(v);
-        }
-        for i in 0..3 {
-            let v = MultiLinearPoly::eval_multilinear(
-                &self.prover_key.permutation[i].evals,
-                &prod_point[..nv],
-                &self.encoder
-            );
-            transcript.push(v);
-        }
+            let proof = LookupProof {
+                sumcheck,
+                evals: evals[..value_num(k)].to_vec(),
+                openings: vec![],
+            };
+            append_all(&mut transcript, &proof.evals, encoder);
+            (lookup_pc, values, lookup_point, proof)
+        });
 
-        // let r: F = transcript.challenge_f(self.ctx);
-        let r = oracle.folding_challenges[0].clone();
-        let r2 = F::mult(&r, &r, &self.encoder);
-        let r3 = F::mult(&r2, &r, &self.encoder);
-        let r4 = F::mult(&r3, &r, &self.encoder);
-        let r5 = F::mult(&r4, &r, &self.encoder);
-        let (point, sc_evals, sc_total_sums) = Sumcheck::prove(
-            [
-                self.prover_key
-                    .selector
-                    .evals
-                    .iter()
-                    .zip(witness[0].iter())
-                    .zip(witness[1].iter())
-                    .zip(witness[2].iter())
-                    .map(|(((x1, x2), x3), x4)| {
-                        x1
-                            .add(&r.mult(x2, &self.encoder), &self.encoder)
-                            .add(&r2.mult(x3, &self.encoder), &self.encoder)
-                            .add(&r3.mult(x4, &self.encoder), &self.encoder)
-                            // + r.mul_base_elem(x2)
-                            // + r2.mul_base_elem(x3)
-                            // + r3.mul_base_elem(x4)
+        let r = transcript.challenge_f(encoder);
+        let pi_point = public_point(nv, public_num, &mut transcript, encoder);
+        let (point, sc_evals, sc_total_sums) = Sumcheck::prove_masked(
+            vec![
+                (0..1 << nv)
+                    .map(|x| {
+                        combine(
+                            self.prover_key
+                                .selectors
+                                .iter()
+                                .map(|p| &p.evals[x])
+                                .chain(witness.iter().map(|p| &p[x])),
+                            &r,
+                            encoder,
+                        )
                     })
                     .collect(),
-                self.prover_key.permutation[0]
-                    .evals
-                    .iter()
-                    .zip(self.prover_key.permutation[1].evals.iter())
-                    .zip(self.prover_key.permutation[2].evals.iter())
-                    .zip(witness[0].iter())
-                    .zip(witness[1].iter())
-                    .zip(witness[2].iter())
-                    .map(|(((((x1, x2), x3), x4), x5), x6)| {
-                        let mul_elem1 = F::mult(&r, &x2, encoder);
-                        let mul_elem2 = F::mult(&r2, &x3, encoder);
-                        let mul_elem3 = F::mult(&r3, &x4, encoder);
-                        let mul_elem4 = F::mult(&r4, &x5, encoder);
-                        let mul_elem5 = F::mult(&r5, &x6, encoder);
-                        [&mul_elem1, &mul_elem2, &mul_elem3, &mul_elem4, &mul_elem5].into_iter().fold(x1.clone(), |x, y| F::add(&x, &y, encoder))
-                        // F::from(x1)
-                        //     + r.mul_base_elem(x2)
-                        //     + r2.mul_base_elem(x3)
-                        //     + r3.mul_base_elem(x4)
-                        //     + r4.mul_base_elem(x5)
-                        //     + r5.mul_base_elem(x6)
+                (0..1 << nv)
+                    .map(|x| {
+                        combine(
+                            self.prover_key
+                                .permutation
+                                .iter()
+                                .map(|p| &p.evals[x])
+                                .chain(witness.iter().map(|p| &p[x]))
+                                .chain(inverses.iter().flat_map(|(_, q)| q.iter().map(move |p| &p[x]))),
+                            &r,
+                            encoder,
+                        )
                     })
                     .collect(),
                 MultiLinearPoly::new_eq(&sumcheck_point, params, ctx).evals,
                 MultiLinearPoly::new_eq(&prod_point[..nv].to_vec(), params, ctx).evals,
+                witness[0].clone(),
+                // without public inputs the third claim is zero
+                if public_num > 0 {
+                    MultiLinearPoly::new_eq(&pi_point, params, ctx).evals
+                } else {
+                    vec![F::from_int(0, encoder); 1 << nv]
+                },
+                // and so is the fourth without a lookup
+                match &lookup {
+                    Some((_, values, _, _)) => (0..1 << nv)
+                        .map(|x| combine(values.iter().map(|p| &p[x]), &r, encoder))
+                        .collect(),
+                    None => vec![F::from_int(0, encoder); 1 << nv],
+                },
+                match &lookup {
+                    Some((_, _, lookup_point, _)) => MultiLinearPoly::new_eq(lookup_point, params, ctx).evals,
+                    None => vec![F::from_int(0, encoder); 1 << nv],
+                },
             ],
             2,
-            // &mut transcript,
-            |v: [F; 4]| [F::mult(&v[0], &v[2], encoder), F::mult(&v[1], &v[3], encoder)],
+            |v: Vec<F>| [
+                F::mult(&v[0], &v[2], encoder),
+                F::mult(&v[1], &v[3], encoder),
+                F::mult(&v[4], &v[5], encoder),
+                F::mult(&v[6], &v[7], encoder),
+            ],
+            mask(2),
             self.ctx,
             &self.encoder,
-            self.oracle
+            &mut transcript,
         );
 
-        transcript.push(MultiLinearPoly::eval_multilinear(
-            &self.prover_key.selector.evals,
-            &point,
-            &self.encoder,
-        ));
-        for i in 0..3 {
-            transcript.push(MultiLinearPoly::eval_multilinear(
-                &self.prover_key.permutation[i].evals,
-                &point,
-                &self.encoder
-            ));
-        }
-        for i in 0..3 {
-            transcript.push(MultiLinearPoly::eval_multilinear(&witness[i], &point, &self.encoder));
+        let evals = HyperPlonkEvals {
+            gate_selectors: v[..selector_num].to_vec(),
+            gate_witness: v[selector_num..selector_num + wire_num].to_vec(),
+            prod_witness,
+            prod_permutation,
+            selectors: self
+                .prover_key
+                .selectors
+                .iter()
+                .map(|x| MultiLinearPoly::eval_multilinear(&x.evals, &point, &self.encoder))
+                .collect(),
+            permutation: self
+                .prover_key
+                .permutation
+                .iter()
+                .map(|x| MultiLinearPoly::eval_multilinear(&x.evals, &point, &self.encoder))
+                .collect(),
+            witness: witness.iter().map(|x| MultiLinearPoly::eval_multilinear(x, &point, &self.encoder)).collect(),
+        };
+
+        let k = self.prover_key.lookup.as_ref().map_or(0, |x| x.columns.len());
+        let lookup = lookup.map(|(lookup_pc, values, _, mut proof)| {
+            proof.openings = values[..2]
+                .iter()
+                .chain(&values[k + 2..])
+                .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &self.encoder))
+                .collect();
+            (lookup_pc, proof)
+        });
+
+        if let (PermutationProof::LogDerivative(proof), Some((_, inverses))) = (&mut permutation_check, &inverses) {
+            proof.openings = inverses
+                .iter()
+                .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &self.encoder))
+                .collect();
         }
+        let inverse_openings = match &permutation_check {
+            PermutationProof::LogDerivative(proof) => &proof.openings[..],
+            PermutationProof::Product(_) => &[][..],
+        };
+        let lookup_openings = lookup.as_ref().map_or(&[][..], |(_, proof)| &proof.openings[..]);
+        append_all(
+            &mut transcript,
+            evals
+                .selectors
+                .iter()
+                .chain(&evals.permutation)
+                .chain(&evals.witness)
+                .chain(inverse_openings)
+                .chain(lookup_openings),
+            encoder,
+        );
+
+        // the mask values at their sumcheck points, batched by mu, are one inner product
+        // with the committed coefficients, which a sumcheck brings down to one more opening
+        let zk = masks.map(|(masks, columns, mask_pc, sums, _)| {
+            let points = [&sumcheck_point[..], &prod_point[..nv], &point[..]];
+            let evals = masks
+                .iter()
+                .zip(points)
+                .flat_map(|(mask, point)| mask.evaluate(point, encoder))
+                .collect::<Vec<_>>();
+            append_all(&mut transcript, &evals, encoder);
+            let mu = transcript.challenge_f(encoder);
+            let column_num = columns.len();
+            let (mask_point, _, sumcheck) = Sumcheck::prove_vec(
+                columns.iter().chain(&basis_columns(gates, nv, points, &mu, encoder)).cloned().collect(),
+                2,
+                |v: Vec<F>| [
+                    v[..column_num]
+                        .iter()
+                        .zip(&v[column_num..])
+                        .fold(F::from_int(0, encoder), |acc, (c, u)| acc.add(&c.mult(u, encoder), encoder))
+                ],
+                ctx,
+                encoder,
+                &mut transcript,
+            );
+            let proof = ZkProof {
+                sums,
+                evals,
+                sumcheck,
+                openings: columns
+                    .iter()
+                    .map(|x| MultiLinearPoly::eval_multilinear(x, &mask_point, &self.encoder))
+                    .collect(),
+            };
+            append_all(&mut transcript, &proof.openings, encoder);
+            (mask_pc, mask_point, proof)
+        });
 
-        // PC::open(
-        //     pp,
-        //     vec![&self.prover_key.commitments, &witness_pc],
-        //     point,
-        //     &mut transcript,
-        // );
+        let opening = PC::open(
+            pp,
+            &[&self.prover_key.commitments, &witness_pc]
+                .into_iter()
+                .chain(inverses.iter().map(|(inverses_pc, _)| inverses_pc))
+                .chain(lookup.iter().flat_map(|(lookup_pc, _)| lookup_pc))
+                .collect::<Vec<_>>(),
+            &point,
+            &mut transcript,
+        );
+        send_opening::<PC>(&opening, &mut pcs);
+        if let Some((mask_pc, mask_point, _)) = &zk {
+            let opening = PC::open(pp, &[mask_pc], mask_point, &mut transcript);
+            send_opening::<PC>(&opening, &mut pcs);
+        }
 
-        (total_sums, prod_total_sums, prod_transcript, sc_total_sums, transcript)
+        HyperPlonkProof {
+            gate_sumcheck: total_sums,
+            permutation_check,
+            lookup: lookup.map(|(_, proof)| proof),
+            opening_sumcheck: sc_total_sums,
+            zk: zk.map(|(_, _, proof)| proof),
+            evals,
+            pcs: OpeningProof { bytes: pcs },
+        }
     }
 }
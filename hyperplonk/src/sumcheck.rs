@@ -1,6 +1,8 @@
 use arithmetic::field::{batch_inverse, dynamic_field::DynamicField, Field};
 use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
-use util::{fiat_shamir::{Proof, Transcript}, random_oracle::RandomOracle};
+use util::fiat_shamir::Transcript;
+
+use crate::zk::SumcheckMask;
 
 pub struct Sumcheck;
 
@@ -19,25 +21,64 @@ impl<'a> Sumcheck {
 
     // N: 5 (l, r, o, selector, eq; degree of polynomial+1); M: 1
     pub fn prove<const N: usize, const M: usize, FUNC: Fn([F; N]) -> [F; M]>(
-        mut evals: [Vec<F>; N],
+        evals: [Vec<F>; N],
         degree: usize,
-        // transcript: &mut Transcript,
         f: FUNC,
         ctx: &'a Context,
         encoder: &BFVEncoder,
-        oracle: &RandomOracle
+        transcript: &mut Transcript,
     ) -> (Vec<F>, [F; N], Vec<[Vec<Plaintext>; M]>) {
+        let (new_point, v, total_sums) = Self::prove_vec(
+            evals.into(),
+            degree,
+            |v: Vec<F>| f(v.try_into().unwrap()),
+            ctx,
+            encoder,
+            transcript,
+        );
+        (new_point, v.try_into().unwrap(), total_sums)
+    }
+
+    // the same with the number of polynomials known only at runtime
+    pub fn prove_vec<const M: usize, FUNC: Fn(Vec<F>) -> [F; M]>(
+        evals: Vec<Vec<F>>,
+        degree: usize,
+        f: FUNC,
+        ctx: &'a Context,
+        encoder: &BFVEncoder,
+        transcript: &mut Transcript,
+    ) -> (Vec<F>, Vec<F>, Vec<[Vec<Plaintext>; M]>) {
+        Self::prove_masked(evals, degree, f, None, ctx, encoder, transcript)
+    }
+
+    // with a mask, the rounds are those of f + rho * g: the verifier starts from the claim
+    // plus rho * sum(g) and ends with rho * g(point) on top of f. every round polynomial is
+    // absorbed before its challenge is drawn
+    pub fn prove_masked<const M: usize, FUNC: Fn(Vec<F>) -> [F; M]>(
+        mut evals: Vec<Vec<F>>,
+        degree: usize,
+        f: FUNC,
+        mask: Option<(&SumcheckMask, &F)>,
+        ctx: &'a Context,
+        encoder: &BFVEncoder,
+        transcript: &mut Transcript,
+    ) -> (Vec<F>, Vec<F>, Vec<[Vec<Plaintext>; M]>) {
+        if let Some((mask, _)) = mask {
+            assert_eq!(mask.polys.len(), M);
+            assert_eq!(mask.degree, degree);
+        }
+        let n = evals.len();
         let var_num = evals[0].len().ilog2() as usize;
         println!("var num: {}, prover M: {}", var_num, M);
         let mut new_point = vec![];
         let mut total_sums = vec![];
         for i in 0..var_num {
             let m = 1usize << (var_num - i);
-            let sums = (0..m).step_by(2).fold(
+            let mut sums = (0..m).step_by(2).fold(
                 [0; M].map(|_| vec![F::from_int(0, encoder); degree + 1]),
                 |mut acc, x| {
                     let mut extrapolations = vec![];
-                    for j in 0..N {
+                    for j in 0..n {
                         let v_0 = evals[j][x].clone();
                         let v_1 = evals[j][x + 1].clone();
                         let diff = F::sub(&v_1, &v_0, encoder);
@@ -49,10 +90,10 @@ impl<'a> Sumcheck {
                     }
                     for j in 0..degree + 1 {
                         let mut res = vec![extrapolations[0][j].clone()];
-                        for k in 1..N {
+                        for k in 1..n {
                             res.push(extrapolations[k][j].clone());
                         }
-                        let tmp = f(res.try_into().unwrap());
+                        let tmp = f(res);
                         for k in 0..M {
                             acc[k][j] = F::add(&acc[k][j], &tmp[k], encoder);
                         }
@@ -60,20 +101,24 @@ impl<'a> Sumcheck {
                     acc
                 },
             );
+            if let Some((mask, rho)) = mask {
+                for (sum, g) in sums.iter_mut().zip(mask.round(&new_point, encoder)) {
+                    for (x, y) in sum.iter_mut().zip(&g) {
+                        *x = x.add(&rho.mult(y, encoder), encoder);
+                    }
+                }
+            }
+            for x in sums.iter().flatten() {
+                transcript.append_f(x, encoder);
+            }
             total_sums.push(sums);
-            // for j in 0..M {
-            //     for k in &sums[j] {
-            //         transcript.append_f(k.clone());
-            //     }
-            // }
-            // let challenge = transcript.challenge_f(ctx);
-            let challenge = &oracle.folding_challenges[i];
-            new_point.push(challenge.clone());
+            let challenge = transcript.challenge_f(encoder);
             for j in evals.iter_mut() {
                 Self::fold_next_domain(j, m / 2, challenge.clone(), encoder)
             }
+            new_point.push(challenge);
         }
-        (new_point, evals.map(|x| x[0].clone()), total_sums)
+        (new_point, evals.into_iter().map(|x| x[0].clone()).collect(), total_sums)
     }
 
     fn init_base(n: usize, modulus: u64) -> Vec<T> {
@@ -127,84 +172,62 @@ impl<'a> Sumcheck {
         degree: usize,
         var_num: usize,
         total_sums: Vec<[Vec<F>; M]>,
-        // transcript: &mut Transcript,
-        // proof: &mut Proof,
         params: &'a EncryptionParameters,
         ctx: &'a Context,
         encoder: &BFVEncoder,
-        oracle: &RandomOracle,
-    ) -> (Vec<F>, [F; M]) {
+        transcript: &mut Transcript,
+    ) -> Option<(Vec<F>, [F; M])> {
+        if total_sums.len() != var_num || total_sums.iter().flatten().any(|x| x.len() != degree + 1) {
+            return None;
+        }
         let mut res = vec![];
         let modulus = params.get_plain_modulus().value();
         println!("modulus: {}", modulus);
         let base = Self::init_base(degree, modulus);
         println!("init base ok.");
         for i in 0..var_num {
-            // let sums = [0; M].map(|_| {
-            //     let mut sum = vec![];
-            //     for _ in 0..degree + 1 {
-            //         let x = proof.get_next_and_step(ctx);
-            //         transcript.append_f(x);
-            //         sum.push(x);
-            //     }
-            //     sum
-            // });
             println!("{} step entered, M is {}", i, M);
             let sums = total_sums[i].clone();
             for j in 0..M {
                 println!("j: {}", j);
-                assert_eq!(sums[j][0].add(&sums[j][1], encoder), y[j]);
+                if sums[j][0].add(&sums[j][1], encoder) != y[j] {
+                    return None;
+                }
+            }
+            for x in sums.iter().flatten() {
+                transcript.append_f(x, encoder);
             }
-            // let challenge: F = transcript.challenge_f(ctx);
-            let challenge = oracle.folding_challenges[i].clone();
+            let challenge = transcript.challenge_f(encoder);
             res.push(challenge.clone());
             for j in 0..M {
                 y[j] = Self::uni_extrapolate::<DynamicField>(&base, &sums[j], challenge.clone(), encoder);
                 println!("extrapolate ok.");
             }
         }
-        (res, y)
+        Some((res, y))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use arithmetic::{
-        field::Field, poly::MultiLinearPoly,
+        field::Field, poly::MultiLinearPoly, test_utils::gen_params_n_ctx,
     };
     use rand::thread_rng;
-    use seal_fhe::{BFVEncoder, BfvEncryptionParametersBuilder, CoefficientModulus, Context, EncryptionParameters, KeyGenerator, PlainModulus, Plaintext};
-    use util::random_oracle::RandomOracle;
+    use seal_fhe::{BFVEncoder, Plaintext};
+    use util::fiat_shamir::Transcript;
 
     use super::Sumcheck;
 
     type F = Plaintext;
 
     const VN: usize = 5;
-    const BATCH_SIZE: u64 = 4096;
-    const CIPHER_BIT_VEC: &[i32] = &[40, 30, 30];
-
-    fn gen_params_n_ctx() -> (EncryptionParameters, Context) {
-        let params = BfvEncryptionParametersBuilder::new()
-            .set_poly_modulus_degree(BATCH_SIZE)
-            .set_coefficient_modulus(
-                CoefficientModulus::create(BATCH_SIZE, CIPHER_BIT_VEC).unwrap(),
-            )
-            .set_plain_modulus(PlainModulus::batching(BATCH_SIZE, 20).unwrap())
-            .build()
-            .unwrap(); 
-
-        let ctx = Context::new(&params, false, seal_fhe::SecurityLevel::TC128).unwrap();
-        (params, ctx)
-    }
 
     #[test]
     fn test_sumcheck() {
         let mut rng = thread_rng();
         let (params, ctx) = gen_params_n_ctx();
         let encoder = BFVEncoder::new(&ctx, &params).unwrap();
-        let key_gen = KeyGenerator::new(&ctx).unwrap();
-        let oracle = RandomOracle::new(VN, 0, &ctx, &params, &key_gen);
 
         let a = (0..1<<VN)
             .map(|_| F::random_pt(&encoder))
@@ -218,7 +241,7 @@ mod tests {
         let d = (0..1<<VN)
             .map(|_| F::random_pt(&encoder))
             .collect::<Vec<_>>();
-        // let mut transcript = Transcript::new();
+        let mut transcript = Transcript::new();
         let (new_point, evals, total_sums) = Sumcheck::prove(
             [a.clone(), b.clone(), c.clone(), d.clone()],
             3,
@@ -229,7 +252,7 @@ mod tests {
             ],
             &ctx,
             &encoder,
-            &oracle,
+            &mut transcript,
         );
         let y = (0..1<<VN).fold([F::from_int(0, &encoder), F::from_int(0, &encoder)], |acc, x| {
             [
@@ -239,9 +262,11 @@ mod tests {
                 c[x].mult(&c[x], &encoder).mult(&d[x], &encoder).add(&acc[1], &encoder),
             ]
         });
-        // let mut proof = transcript.proof;
-        // let mut transcript = Transcript::new();
-        let (point, y) = Sumcheck::verify(y, 3, VN, total_sums, &params, &ctx, &encoder, &oracle);
+        let mut tampered = total_sums.clone();
+        tampered[1][0][0] = tampered[1][0][0].add(&F::from_int(1, &encoder), &encoder);
+        assert!(Sumcheck::verify(y.clone(), 3, VN, tampered, &params, &ctx, &encoder, &mut Transcript::new()).is_none());
+        let (point, y) = Sumcheck::verify(y, 3, VN, total_sums, &params, &ctx, &encoder, &mut Transcript::new()).unwrap();
+        assert_eq!(point, new_point);
         assert_eq!(
             MultiLinearPoly::eval_multilinear_ext(&a, &point, &encoder)
                 .mult(&MultiLinearPoly::eval_multilinear_ext(&b, &point, &encoder), &encoder)
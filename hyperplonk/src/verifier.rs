@@ -1,210 +1,428 @@
-use std::marker::PhantomData;
-
-use arithmetic::{field::Field, poly::MultiLinearPoly};
-use poly_commit::{CommitmentSerde, PolyCommitVerifier};
+use arithmetic::{field::{plaintext_size, Field}, poly::MultiLinearPoly};
+use poly_commit::{CommitmentSerde, HomomorphicCommitment, PolynomialCommitmentScheme, ProofSerde};
 use seal_fhe::{BFVEncoder, Ciphertext, Context, EncryptionParameters, Plaintext};
-use util::{fiat_shamir::{Proof, Transcript}, random_oracle::RandomOracle};
+use util::fiat_shamir::{Proof, Transcript};
 
-use crate::{prod_eq_check::ProdEqCheck, sumcheck::Sumcheck};
+use crate::{circuit::{column_var_num, wire_id}, gate::GateSet, grand_product::{GrandProduct, TreeKind}, lookup::identities, permutation::{self, PermutationCheck, PermutationProof}, proof::HyperPlonkProof, prover::{append_all, bind_public_inputs, combine, padded_public_num, public_point}, sumcheck::Sumcheck, zk::{basis_inner_product, mask_column_num}};
 
 type F = Plaintext;
 type Q = Ciphertext;
 
-pub struct VerifierKey<PC: PolyCommitVerifier> {
-    pub commitment: PC,
+pub struct VerifierKey<'a, PC: PolynomialCommitmentScheme<'a>> {
+    pub commitment: PC::Commitment,
+    pub gates: GateSet,
+    pub public_num: usize,
+    // the witness columns looked up, for circuits with a lookup
+    pub lookup_columns: Option<Vec<usize>>,
+    pub permutation_check: PermutationCheck,
+    pub zk: bool,
 }
 
-pub struct Verifier<'a, PC: PolyCommitVerifier> {
-    pub verifier_key: VerifierKey<PC>,
+// one opening of the preprocessed polynomials of many keys of the same shape at a shared
+// point: the key commitments fold into one with transcript challenges, so the check costs
+// as much as for a single key. `evals[k]` are the evaluations claimed under `keys[k]`, which
+// the prover sends first in `proof`, and `opening` opens the combination
+pub fn verify_preprocessed<'a, PC: HomomorphicCommitment<'a>>(
+    pp: &PC::Param,
+    keys: &[&VerifierKey<'a, PC>],
+    point: Vec<F>,
+    evals: &[Vec<F>],
+    encoder: &BFVEncoder,
+    transcript: &mut Transcript,
+    proof: &mut Proof,
+    opening: &PC::Proof,
+) -> bool {
+    if keys.is_empty() || keys.len() != evals.len() || evals.iter().any(|x| x.len() != evals[0].len()) {
+        return false;
+    }
+    for x in evals.iter().flatten() {
+        if proof.remaining() < plaintext_size(encoder) || proof.get_next_and_step(encoder) != *x {
+            return false;
+        }
+        transcript.append_f(x, encoder);
+    }
+    let coeffs = (0..keys.len()).map(|_| transcript.challenge_f(encoder)).collect::<Vec<_>>();
+    let commits = keys.iter().map(|x| x.commitment.clone()).collect::<Vec<_>>();
+    PC::verify_combined(pp, &commits, &coeffs, &point, evals, encoder, opening, transcript).is_ok()
+}
+
+pub struct Verifier<'a, PC: PolynomialCommitmentScheme<'a>> {
+    pub verifier_key: VerifierKey<'a, PC>,
     pub ctx: &'a Context,
     pub params: &'a EncryptionParameters,
     pub encoder: BFVEncoder<'a>,
-    pub oracle: &'a RandomOracle<'a>
 }
 
-impl<'a, PC: PolyCommitVerifier> Verifier<'a, PC> {
-    pub fn new(verifier_key: VerifierKey<PC>, params: &'a EncryptionParameters, ctx: &'a Context, oracle: &'a RandomOracle) -> Self {
+impl<'a, PC: PolynomialCommitmentScheme<'a>> Verifier<'a, PC> {
+    pub fn new(verifier_key: VerifierKey<'a, PC>, params: &'a EncryptionParameters, ctx: &'a Context) -> Self {
         Self {
             verifier_key,
             ctx,
             params,
             encoder: BFVEncoder::new(ctx, params).unwrap(),
-            oracle,
         }
     }
 
-    pub fn verify(&self, pp: &PC::Param, nv: usize, 
-        transcript: Vec<Plaintext>, total_sums: Vec<[Vec<F>; 1]>, 
-        prod_total_sums: Vec<Vec<[Vec<Plaintext>; 2]>>, sc_total_sums: Vec<[Vec<Plaintext>; 2]>, prod_transcript: Vec<F>) -> bool {
+    // in zk mode the sumchecks are masked and the masks opened after everything else, as
+    // laid out in the zk module. the public inputs are padded with zeros as in the prover
+    pub fn verify(&self, pp: &PC::Param, nv: usize, public_inputs: &[F], proof: &HyperPlonkProof) -> bool {
         let encoder = &self.encoder;
+        let gates = &self.verifier_key.gates;
+        let public_num = padded_public_num(self.verifier_key.public_num);
+        let lookup_columns = &self.verifier_key.lookup_columns;
+        let permutation_check = self.verifier_key.permutation_check;
+        let zk = self.verifier_key.zk;
+        if !proof.has_shape(nv, gates, permutation_check, lookup_columns.as_ref().map(|x| x.len()), zk)
+            || public_inputs.len() != self.verifier_key.public_num
+            || public_num > 1 << nv
+        {
+            return false;
+        }
+        let mut public_inputs = public_inputs.to_vec();
+        public_inputs.resize(public_num, F::from_int(0, encoder));
 
-        let rs = (0..nv)
-            .map(|i| self.oracle.folding_challenges[i].clone())
-            .collect::<Vec<_>>();
-        let (sumcheck_point, claim_y) =
-            Sumcheck::verify([F::from_int(0, encoder)], 4, nv, total_sums, self.params, self.ctx, &self.encoder, self.oracle);
-        let claim_s = transcript[0].clone();
-        let claim_w0 = transcript[1].clone();
-        let claim_w1 = transcript[2].clone();
-        let claim_w2 = transcript[3].clone();
+        let mut transcript = Transcript::new();
+        bind_public_inputs(&mut transcript, &public_inputs, encoder);
+        let mut pcs_proof = proof.pcs.reader();
+        let wire_num = gates.wire_num;
+        let Some(witness_pc) = receive_commitment::<PC>(nv, wire_num, encoder, &mut transcript, &mut pcs_proof) else {
+            return false;
+        };
+        // coefficients of the sumcheck masks, then the sums of the masks, then rho
+        let (mask_pc, rho) = match &proof.zk {
+            Some(x) => {
+                let poly_num = mask_column_num(nv, gates);
+                let Some(mask_pc) = receive_commitment::<PC>(nv, poly_num, encoder, &mut transcript, &mut pcs_proof) else {
+                    return false;
+                };
+                append_all(&mut transcript, &x.sums, encoder);
+                (Some(mask_pc), transcript.challenge_f(encoder))
+            }
+            None => (None, F::from_int(0, encoder)),
+        };
+
+        // mask output i adds rho times its sum to the claim and rho times its value to the
+        // final claim, which come off again before the checks
+        let masked = |y: F, i: usize| match &proof.zk {
+            Some(x) => y.add(&rho.mult(&x.sums[i], encoder), encoder),
+            None => y,
+        };
+        let unmasked = |y: &F, i: usize| match &proof.zk {
+            Some(x) => y.sub(&rho.mult(&x.evals[i], encoder), encoder),
+            None => y.clone(),
+        };
+
+        let rs = (0..nv).map(|_| transcript.challenge_f(encoder)).collect::<Vec<_>>();
+        let Some((sumcheck_point, claim_y)) = Sumcheck::verify(
+            [masked(F::from_int(0, encoder), 0)],
+            gates.degree() + 1,
+            nv,
+            proof.gate_sumcheck.clone(),
+            self.params,
+            self.ctx,
+            &self.encoder,
+            &mut transcript,
+        ) else {
+            return false;
+        };
+        let claim_s = &proof.evals.gate_selectors;
+        let claim_w = &proof.evals.gate_witness;
+        append_all(&mut transcript, claim_s.iter().chain(claim_w), encoder);
         let eq_v = MultiLinearPoly::eval_eq(&rs, &sumcheck_point, &self.encoder);
 
-        // let p1 = [&F::sub(&F::from_int(1, &self.encoder), &claim_s, &self.encoder), &F::add(&claim_w0, &claim_w1, &self.encoder)].into_iter()
-        //     .fold(eq_v.clone(), |x, y| F::mult(&x, y, &self.encoder));
-        // let p2 = [&claim_w0, &claim_w1].into_iter().fold(claim_s.clone(), |x, y| F::mult(&x, y, &self.encoder));
-        assert_eq!(
-            claim_y[0].clone(),
-            // [&p2, &claim_w2].into_iter().fold(p1.clone(), |x, y| x.add(y, &self.encoder))
-            F::from_int(1, encoder).sub(&claim_s, encoder)
-                .mult(&claim_w0.add(&claim_w1, encoder), encoder)
-                .add(&claim_s.mult(&claim_w0.mult(&claim_w1, encoder), encoder), encoder)
-                .add(&claim_w2, encoder)
-                .mult(&eq_v, encoder)
-            // eq_v * ((F::one() - claim_s) * (claim_w0 + claim_w1)
-            //     + claim_s * claim_w0 * claim_w1
-            //     + claim_w2)
-        );
+        if unmasked(&claim_y[0], 0) != gates.evaluate(claim_s, claim_w, encoder).mult(&eq_v, encoder) {
+            return false;
+        }
 
-        // let r_1: F = transcript.challenge_f(self.ctx);
-        // let r_2: F = transcript.challenge_f(self.ctx);
-        let r_1 = rs[0].clone();
-        let r_2 = rs[1].clone();
-
-        let (prod_point, y) = ProdEqCheck::verify(nv + 2, prod_transcript, prod_total_sums, self.params, self.ctx, &self.encoder, self.oracle);
-        let witness_eval = [0,1,2].map(|i| {
-            // let x: F = proof.get_next_and_step(self.ctx);
-            let x = transcript[4+i].clone();
-            // transcript.append_f(x);
-            x
-        });
-        let perm_eval = [0,1,2].map(|i| {
-            // let x: F = proof.get_next_and_step(self.ctx);
-            let x = transcript[7+i].clone();
-            // transcript.append_f(x);
-            x
-        });
-        let v = vec![
-            r_2.clone()
-                .mult(&MultiLinearPoly::eval_identical(&prod_point[..nv].to_vec(), F::from_int(0, encoder), encoder), encoder)
-                .add(&r_1, encoder)
-                .add(&witness_eval[0], encoder),
-            r_2.clone()
-                .mult(&MultiLinearPoly::eval_identical(&prod_point[..nv].to_vec(), F::from_int(1 << 29, encoder), encoder), encoder)
-                .add(&r_1, encoder)
-                .add(&witness_eval[1], encoder),
-            r_2.clone()
-                .mult(&MultiLinearPoly::eval_identical(&prod_point[..nv].to_vec(), F::from_int(1 << 30, encoder), encoder), encoder)
-                .add(&r_1, encoder)
-                .add(&witness_eval[2], encoder),
-            r_1.clone()
-        ];
-        assert_eq!(y[0], MultiLinearPoly::eval_multilinear_ext(&v, &prod_point[nv..], encoder));
-
-        let v1 = [&witness_eval[0], &F::mult(&r_2, &perm_eval[0], encoder)]
-            .into_iter().fold(r_1.clone(), |x, y| F::add(&x, y, encoder));
-        let v2 = [&witness_eval[1], &F::mult(&r_2, &perm_eval[1], encoder)]
-            .into_iter().fold(r_1.clone(), |x, y| F::add(&x, y, encoder));
-        let v3 = [&witness_eval[2], &F::mult(&r_2, &perm_eval[2], encoder)]
-            .into_iter().fold(r_1.clone(), |x, y| F::add(&x, y, encoder));
-        let vec = vec![v1, v2, v3, r_1.clone()];
-        assert_eq!(y[1], MultiLinearPoly::eval_multilinear_ext(&vec, &prod_point[nv..], &self.encoder));
-        let r = rs[0].clone();
-        let y1 = F::add(&claim_s, &F::mult(
-            &r, &F::add(
-                &claim_w0, &F::mult(
-                    &r, &F::add(
-                        &claim_w1,
-                        &F::mult(&r, &claim_w2, encoder), encoder),
-                        encoder), 
-                    encoder), 
-                encoder), 
-            encoder);
-        let y2 = F::add(&perm_eval[0], &F::mult(
-                &r, &F::add(
-                    &perm_eval[1], &F::mult(
-                        &r, &F::add(
-                            &perm_eval[2], &F::mult(
-                                &r, &F::add(
-                                    &witness_eval[0], &F::mult(
-                                        &r, &F::add(
-                                            &witness_eval[1], &F::mult(&r, &witness_eval[2], encoder), encoder),
-                                        encoder), 
-                                    encoder), 
-                                encoder), 
-                            encoder), 
-                        encoder),
-                    encoder),
-                encoder),
-            encoder);
-            // [
-            //     claim_s + r * (claim_w0 + r * (claim_w1 + r * claim_w2)),
-            //     perm_eval[0]
-            //         + r * (perm_eval[1]
-            //             + r * (perm_eval[2]
-            //                 + r * (witness_eval[0] + r * (witness_eval[1] + r * witness_eval[2])))),
-            // ]
-        let vec = [y1, y2];
-        let (point, y) = Sumcheck::verify(
+        let (beta, gamma) = (transcript.challenge_f(encoder), transcript.challenge_f(encoder));
+
+        let witness_eval = &proof.evals.prod_witness;
+        let perm_eval = &proof.evals.prod_permutation;
+        let (prod_point, inverses_pc) = match &proof.permutation_check {
+            PermutationProof::Product(prod_check) => {
+                // padding columns hold a zero witness under the identity permutation
+                let column_vars = column_var_num(wire_num);
+                let Some(claims) = GrandProduct::verify(
+                    &[(TreeKind::Product, nv + column_vars); 2],
+                    prod_check,
+                    self.params,
+                    self.ctx,
+                    &self.encoder,
+                    &mut transcript,
+                ) else {
+                    return false;
+                };
+                if claims[0].root != claims[1].root {
+                    return false;
+                }
+                let prod_point = &claims[0].point;
+                let y = [claims[0].leaves[0].clone(), claims[1].leaves[0].clone()];
+                let identity_eval = (0..1 << column_vars)
+                    .map(|j| {
+                        let offset = F::from_int(wire_id(j, 0, nv), encoder);
+                        MultiLinearPoly::eval_identical(&prod_point[..nv].to_vec(), offset, encoder)
+                    })
+                    .collect::<Vec<_>>();
+                let v = identity_eval
+                    .iter()
+                    .enumerate()
+                    .map(|(j, id)| {
+                        let x = gamma.mult(id, encoder).add(&beta, encoder);
+                        if j < wire_num { x.add(&witness_eval[j], encoder) } else { x }
+                    })
+                    .collect::<Vec<_>>();
+                if y[0] != MultiLinearPoly::eval_multilinear_ext(&v, &prod_point[nv..], encoder) {
+                    return false;
+                }
+
+                let vec = identity_eval
+                    .iter()
+                    .enumerate()
+                    .map(|(j, id)| {
+                        if j < wire_num {
+                            gamma.mult(&perm_eval[j], encoder).add(&beta, encoder).add(&witness_eval[j], encoder)
+                        } else {
+                            gamma.mult(id, encoder).add(&beta, encoder)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                if y[1] != MultiLinearPoly::eval_multilinear_ext(&vec, &prod_point[nv..], &self.encoder) {
+                    return false;
+                }
+                (claims[0].point.clone(), None)
+            }
+            PermutationProof::LogDerivative(log_derivative) => {
+                // 1 / f and 1 / g of every wire
+                let Some(inverses_pc) = receive_commitment::<PC>(nv, 2 * wire_num, encoder, &mut transcript, &mut pcs_proof) else {
+                    return false;
+                };
+                let lambda = transcript.challenge_f(encoder);
+                let zero = F::from_int(0, encoder);
+                let Some((point, y)) = Sumcheck::verify(
+                    [masked(zero.clone(), 1), masked(zero, 2)],
+                    3,
+                    nv,
+                    log_derivative.sumcheck.clone(),
+                    self.params,
+                    self.ctx,
+                    &self.encoder,
+                    &mut transcript,
+                ) else {
+                    return false;
+                };
+                let inverses = &log_derivative.evals;
+                append_all(&mut transcript, inverses, encoder);
+                let values = (0..wire_num)
+                    .flat_map(|j| {
+                        let offset = F::from_int(wire_id(j, 0, nv), encoder);
+                        [
+                            witness_eval[j].clone(),
+                            MultiLinearPoly::eval_identical(&point, offset, encoder),
+                            perm_eval[j].clone(),
+                            inverses[j].clone(),
+                            inverses[wire_num + j].clone(),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                let [sum, rows] = permutation::identities(&values, &beta, &gamma, &lambda, encoder);
+                if unmasked(&y[0], 1) != sum
+                    || unmasked(&y[1], 2) != rows.mult(&MultiLinearPoly::eval_eq(&rs, &point, encoder), encoder)
+                {
+                    return false;
+                }
+                (point, Some(inverses_pc))
+            }
+        };
+        append_all(&mut transcript, witness_eval.iter().chain(perm_eval), encoder);
+
+        // the lookup sumcheck leaves a claim on the lookup values at its point, which joins
+        // the opening sumcheck
+        let (lookup_pc, lookup_claim) = match (lookup_columns, &proof.lookup) {
+            (Some(columns), Some(lookup)) => {
+                // the multiplicities before beta, then the helper columns before gamma
+                let Some(m_pc) = receive_commitment::<PC>(nv, 1, encoder, &mut transcript, &mut pcs_proof) else {
+                    return false;
+                };
+                let beta = transcript.challenge_f(encoder);
+                let Some(helper_pc) = receive_commitment::<PC>(nv, columns.len() + 1, encoder, &mut transcript, &mut pcs_proof) else {
+                    return false;
+                };
+                let gamma = transcript.challenge_f(encoder);
+                let zero = F::from_int(0, encoder);
+                let Some((lookup_point, y)) = Sumcheck::verify(
+                    [zero.clone(), zero],
+                    3,
+                    nv,
+                    lookup.sumcheck.clone(),
+                    self.params,
+                    self.ctx,
+                    &self.encoder,
+                    &mut transcript,
+                ) else {
+                    return false;
+                };
+                append_all(&mut transcript, &lookup.evals, encoder);
+                let [sum, rows] = identities(&lookup.evals, columns.len(), &beta, &gamma, encoder);
+                if y[0] != sum || y[1] != rows.mult(&MultiLinearPoly::eval_eq(&rs, &lookup_point, encoder), encoder) {
+                    return false;
+                }
+                (Some([m_pc, helper_pc]), Some((lookup_point, &lookup.evals)))
+            }
+            _ => (None, None),
+        };
+        let r = transcript.challenge_f(encoder);
+        let y1 = combine(claim_s.iter().chain(claim_w), &r, encoder);
+        // the inverse columns of the log-derivative check join the permutation claim
+        let (inverse_evals, inverse_openings) = match &proof.permutation_check {
+            PermutationProof::Product(_) => (&[][..], &[][..]),
+            PermutationProof::LogDerivative(x) => (&x.evals[..], &x.openings[..]),
+        };
+        let y2 = combine(perm_eval.iter().chain(witness_eval).chain(inverse_evals), &r, encoder);
+        // a(X) agrees with the public inputs on their subcube iff it does at a random point of it
+        let pi_point = public_point(nv, public_num, &mut transcript, encoder);
+        let y3 = match public_num {
+            0 => F::from_int(0, encoder),
+            1 => public_inputs[0].clone(),
+            _ => MultiLinearPoly::eval_multilinear(&public_inputs, &pi_point[..public_num.ilog2() as usize], encoder),
+        };
+        let y4 = lookup_claim.as_ref().map_or(F::from_int(0, encoder), |(_, evals)| combine(evals.iter(), &r, encoder));
+        let mut vec = [y1, y2, y3, y4];
+        for (i, y) in vec.iter_mut().enumerate() {
+            *y = masked(y.clone(), 3 + i);
+        }
+        let Some((point, y)) = Sumcheck::verify(
             vec,
             2,
             nv,
-            sc_total_sums,
+            proof.opening_sumcheck.clone(),
             self.params,
             self.ctx,
             &self.encoder,
-            self.oracle
+            &mut transcript,
+        ) else {
+            return false;
+        };
+        let y = (0..4).map(|i| unmasked(&y[i], 3 + i)).collect::<Vec<_>>();
+        let claim_s = &proof.evals.selectors;
+        let perm_eval = &proof.evals.permutation;
+        let witness_eval = &proof.evals.witness;
+        // q and t are committed with the selectors, the lookup columns with the witness
+        let openings = proof.lookup.as_ref().map_or(&[][..], |x| &x.openings[..]);
+        append_all(
+            &mut transcript,
+            claim_s.iter().chain(perm_eval).chain(witness_eval).chain(inverse_openings).chain(openings),
+            encoder,
         );
-        // let claim_s: F = proof.get_next_and_step(self.ctx);
-        // transcript.append_f(claim_s);
-        let claim_s = transcript[10].clone();
-        let perm_eval = [0,1,2].map(|i| {
-            // let x: F = proof.get_next_and_step(self.ctx);
-            let x = transcript[11+i].clone();
-            // transcript.append_f(x);
-            x
-        });
-        let witness_eval = [0,1,2].map(|i| {
-            // let x: F = proof.get_next_and_step(self.ctx);
-            let x = transcript[14+i].clone();
-            // transcript.append_f(x);
-            x
-        });
-        assert_eq!(y[0],
-            witness_eval[2].clone()
-                .mult(&r, encoder)
-                .add(&witness_eval[1], encoder)
-                .mult(&r, encoder)
-                .add(&witness_eval[0], encoder)
-                .mult(&r, encoder)
-                .add(&claim_s, encoder)
+        if y[0]
+            != combine(claim_s.iter().chain(witness_eval), &r, encoder)
                 .mult(&MultiLinearPoly::eval_eq(&sumcheck_point, &point, encoder), encoder)
-        );
-        assert_eq!(
-            y[1],
-            witness_eval[2].clone()
-                .mult(&r, encoder)
-                .add(&witness_eval[1], encoder)
-                .mult(&r, encoder)
-                .add(&witness_eval[0], encoder)
-                .mult(&r, encoder)
-                .add(&perm_eval[2], encoder)
-                .mult(&r, encoder)
-                .add(&perm_eval[1], encoder)
-                .mult(&r, encoder)
-                .add(&perm_eval[0], encoder)
+        {
+            return false;
+        }
+        if y[1]
+            != combine(perm_eval.iter().chain(witness_eval).chain(inverse_openings), &r, encoder)
                 .mult(&MultiLinearPoly::eval_eq(&prod_point[..nv].to_vec(), &point, encoder), encoder)
-        );
-        // PC::verify(
-        //     pp,
-        //     vec![&self.verifier_key.commitment, &witness_pc],
-        //     point,
-        //     vec![
-        //         vec![claim_s, perm_eval[0], perm_eval[1], perm_eval[2]],
-        //         vec![witness_eval[0], witness_eval[1], witness_eval[2]],
-        //     ],
-        //     &mut transcript,
-        //     &mut proof,
-        // );
-        true
+        {
+            return false;
+        }
+        let expected = match public_num {
+            0 => F::from_int(0, encoder),
+            _ => witness_eval[0].mult(&MultiLinearPoly::eval_eq(&pi_point, &point, encoder), encoder),
+        };
+        if y[2] != expected {
+            return false;
+        }
+        let expected = match (lookup_columns, &lookup_claim) {
+            (Some(columns), Some((lookup_point, _))) => {
+                let values = openings[..2]
+                    .iter()
+                    .chain(columns.iter().map(|&c| &witness_eval[c]))
+                    .chain(&openings[2..]);
+                combine(values, &r, encoder)
+                    .mult(&MultiLinearPoly::eval_eq(lookup_point, &point, encoder), encoder)
+            }
+            _ => F::from_int(0, encoder),
+        };
+        if y[3] != expected {
+            return false;
+        }
+        let mut evals = vec![
+            claim_s.iter().chain(perm_eval).chain(openings.iter().take(2)).cloned().collect(),
+            witness_eval.to_vec(),
+        ];
+        if inverses_pc.is_some() {
+            evals.push(inverse_openings.to_vec());
+        }
+        if lookup_pc.is_some() {
+            evals.push(openings[2..3].to_vec());
+            evals.push(openings[3..].to_vec());
+        }
+        // the mask values, batched by mu, against the committed coefficients
+        let mask_point = match &proof.zk {
+            Some(x) => {
+                append_all(&mut transcript, &x.evals, encoder);
+                let mu = transcript.challenge_f(encoder);
+                let Some((mask_point, y)) = Sumcheck::verify(
+                    [combine(x.evals.iter(), &mu, encoder)],
+                    2,
+                    nv,
+                    x.sumcheck.clone(),
+                    self.params,
+                    self.ctx,
+                    &self.encoder,
+                    &mut transcript,
+                ) else {
+                    return false;
+                };
+                append_all(&mut transcript, &x.openings, encoder);
+                let points = [&sumcheck_point[..], &prod_point[..nv], &point[..]];
+                if y[0] != basis_inner_product(gates, nv, points, &mu, &x.openings, &mask_point, encoder) {
+                    return false;
+                }
+                Some(mask_point)
+            }
+            None => None,
+        };
+        let Some(opening) = PC::Proof::deserialize_from(&mut pcs_proof) else {
+            return false;
+        };
+        let commits = [&self.verifier_key.commitment, &witness_pc]
+            .into_iter()
+            .chain(inverses_pc.as_ref())
+            .chain(lookup_pc.iter().flatten())
+            .collect::<Vec<_>>();
+        if PC::verify(pp, &commits, &point, &evals, &opening, &mut transcript).is_err() {
+            return false;
+        }
+        if let (Some(mask_pc), Some(mask_point), Some(x)) = (&mask_pc, mask_point, &proof.zk) {
+            let Some(opening) = PC::Proof::deserialize_from(&mut pcs_proof) else {
+                return false;
+            };
+            let evals = [x.openings.clone()];
+            if PC::verify(pp, &[mask_pc], &mask_point, &evals, &opening, &mut transcript).is_err() {
+                return false;
+            }
+        }
+        pcs_proof.remaining() == 0
+    }
+}
+
+// reads a commitment to poly_num polynomials back from the pcs proof into the transcript,
+// as the prover's send_commitment wrote it
+fn receive_commitment<'a, PC: PolynomialCommitmentScheme<'a>>(
+    nv: usize,
+    poly_num: usize,
+    encoder: &BFVEncoder,
+    transcript: &mut Transcript,
+    pcs_proof: &mut Proof,
+) -> Option<PC::Commitment> {
+    let size = PC::Commitment::size(nv, poly_num, encoder);
+    if pcs_proof.remaining() < size {
+        return None;
     }
+    let commit = PC::Commitment::deserialize_from(pcs_proof, nv, poly_num, encoder);
+    let mut buffer = vec![0u8; size];
+    commit.serialize_into(&mut buffer, encoder);
+    transcript.append_u8_slice(&buffer, size);
+    Some(commit)
 }
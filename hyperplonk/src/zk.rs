@@ -0,0 +1,354 @@
+use seal_fhe::{BFVEncoder, Plaintext};
+
+use crate::{gate::GateSet, prover::combine};
+
+type F = Plaintext;
+
+// zero-knowledge mode, set by Circuit::zk. against the plain protocol
+// - the last ZK_ROWS rows are left to the prover, which fills the witness there with random
+//   values. their selectors are zero and every gate term has a selector, so the gates hold
+//   on them, and the permutation fixes them, so no copy cycle reaches them. every witness
+//   and inverse column evaluation the verifier sees then carries fresh randomness
+// - the gate, log-derivative and opening sumchecks run over f + rho * g, g a random mask
+//   committed after the witness, so their round polynomials say nothing about f. rho is
+//   drawn once the mask commitment and its sums are in the transcript
+// - the pcs has to be hiding, PolynomialCommitmentScheme::HIDING, for the openings not to leak
+// the verifier
+// - reads the mask commitment right after the witness commitment,
+// - starts each of the three sumchecks from its claim plus rho * sum(g), the sums coming
+//   with the proof, and subtracts rho * g(point) from the final claim before the usual
+//   checks, the mask values coming with the proof too,
+// - checks those mask values, batched by mu drawn after them, as one inner product with the
+//   committed coefficients: a sumcheck reduces it to the coefficient columns at its point,
+//   against which the basis columns are evaluated in closed form, and a second opening
+//   after the main one proves them.
+// lookups and the product-tree permutation check are not supported, their helper columns
+// and tree layers not being blinded
+//
+// each wire reveals its witness column at three points and its two inverse columns at two
+// points each, seven values computed from its blinding rows
+pub const ZK_ROWS: usize = 8;
+
+// outputs and round degree of the masked sumchecks: gate, log-derivative, opening
+pub(crate) fn mask_shapes(gates: &GateSet) -> [(usize, usize); 3] {
+    [(1, gates.degree() + 1), (2, 3), (4, 2)]
+}
+
+// columns of 2^nv holding the coefficients of the three masks, at least half of the cells
+// being random padding, so the coefficient evaluations opened at the end stay random too
+pub(crate) fn mask_column_num(nv: usize, gates: &GateSet) -> usize {
+    let coeff_num = mask_shapes(gates)
+        .iter()
+        .map(|(m, d)| m * nv * (d + 1))
+        .sum::<usize>();
+    (2 * coeff_num).div_ceil(1 << nv)
+}
+
+// g(X) = g_1(X_1) + ... + g_n(X_n) for every output of one sumcheck, each g_i of the round
+// degree with its coefficients in increasing powers
+#[derive(Debug, Clone, PartialEq)]
+pub struct SumcheckMask {
+    pub degree: usize,
+    pub polys: Vec<Vec<Vec<F>>>,
+}
+
+impl SumcheckMask {
+    pub fn random(output_num: usize, var_num: usize, degree: usize, encoder: &BFVEncoder) -> Self {
+        SumcheckMask {
+            degree,
+            polys: (0..output_num)
+                .map(|_| {
+                    (0..var_num)
+                        .map(|_| (0..degree + 1).map(|_| F::random_pt(encoder)).collect())
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    fn var_num(&self) -> usize {
+        self.polys[0].len()
+    }
+
+    // g_i(0) + g_i(1)
+    fn endpoints(g_i: &[F], encoder: &BFVEncoder) -> F {
+        g_i.iter().fold(g_i[0].clone(), |acc, x| acc.add(x, encoder))
+    }
+
+    // every output summed over the hypercube, 2^(n-1) * sum_i (g_i(0) + g_i(1))
+    pub fn sums(&self, encoder: &BFVEncoder) -> Vec<F> {
+        let half = F::from_int(1 << (self.var_num() - 1), encoder);
+        self.polys
+            .iter()
+            .map(|g| {
+                g.iter()
+                    .fold(F::from_int(0, encoder), |acc, g_i| acc.add(&Self::endpoints(g_i, encoder), encoder))
+                    .mult(&half, encoder)
+            })
+            .collect()
+    }
+
+    // the round polynomial of every output at 0..=degree, once `challenges` fixed the first
+    // variables
+    pub fn round(&self, challenges: &[F], encoder: &BFVEncoder) -> Vec<Vec<F>> {
+        let (n, i) = (self.var_num(), challenges.len());
+        let zero = F::from_int(0, encoder);
+        self.polys
+            .iter()
+            .map(|g| {
+                let fixed = g[..i]
+                    .iter()
+                    .zip(challenges)
+                    .fold(zero.clone(), |acc, (g_j, r)| acc.add(&combine(g_j.iter(), r, encoder), encoder));
+                // each later g_j sums to 2^(n-i-2) * (g_j(0) + g_j(1)) over the free variables
+                let rest = if i + 1 < n {
+                    g[i + 1..]
+                        .iter()
+                        .fold(zero.clone(), |acc, g_j| acc.add(&Self::endpoints(g_j, encoder), encoder))
+                        .mult(&F::from_int(1 << (n - i - 2), encoder), encoder)
+                } else {
+                    zero.clone()
+                };
+                let scale = F::from_int(1 << (n - i - 1), encoder);
+                (0..self.degree as u64 + 1)
+                    .map(|t| {
+                        let g_i = combine(g[i].iter(), &F::from_int(t, encoder), encoder);
+                        fixed.add(&g_i, encoder).mult(&scale, encoder).add(&rest, encoder)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn evaluate(&self, point: &[F], encoder: &BFVEncoder) -> Vec<F> {
+        self.polys
+            .iter()
+            .map(|g| {
+                g.iter()
+                    .zip(point)
+                    .fold(F::from_int(0, encoder), |acc, (g_i, r)| acc.add(&combine(g_i.iter(), r, encoder), encoder))
+            })
+            .collect()
+    }
+}
+
+// the coefficients of the gate, log-derivative and opening masks in columns of 2^nv, the
+// rest random
+pub(crate) fn mask_columns(masks: &[SumcheckMask; 3], nv: usize, column_num: usize, encoder: &BFVEncoder) -> Vec<Vec<F>> {
+    let mut cells = masks
+        .iter()
+        .flat_map(|x| x.polys.iter().flatten().flatten().cloned())
+        .collect::<Vec<_>>();
+    assert!(cells.len() <= column_num << nv);
+    cells.resize_with(column_num << nv, || F::random_pt(encoder));
+    cells.chunks(1 << nv).map(|x| x.to_vec()).collect()
+}
+
+// the cells of the basis columns before their zero padding, one per mask coefficient
+fn basis_cells(gates: &GateSet, points: [&[F]; 3], mu: &F, encoder: &BFVEncoder) -> Vec<F> {
+    let mut weight = F::from_int(1, encoder);
+    let mut cells = vec![];
+    for ((output_num, degree), point) in mask_shapes(gates).into_iter().zip(points) {
+        for _ in 0..output_num {
+            for r in point {
+                let mut power = weight.clone();
+                for _ in 0..degree + 1 {
+                    cells.push(power.clone());
+                    power = power.mult(r, encoder);
+                }
+            }
+            weight = weight.mult(mu, encoder);
+        }
+    }
+    cells
+}
+
+// columns u laid out as mask_columns, zero over the padding, with
+//     <coefficients, u> = sum_k mu^k * g_k(points of g_k)
+// over the outputs g_k of the three masks in turn
+pub(crate) fn basis_columns(gates: &GateSet, nv: usize, points: [&[F]; 3], mu: &F, encoder: &BFVEncoder) -> Vec<Vec<F>> {
+    let mut cells = basis_cells(gates, points, mu, encoder);
+    cells.resize(mask_column_num(nv, gates) << nv, F::from_int(0, encoder));
+    cells.chunks(1 << nv).map(|x| x.to_vec()).collect()
+}
+
+// sum_c openings[c] * u_c(z) over the basis columns, in time linear in the mask
+// coefficients rather than 2^nv. the nonzero cells of a column are its first n, so eq over
+// them is the table of the low log(n) variables times (1 - z_b) for the others
+pub(crate) fn basis_inner_product(
+    gates: &GateSet,
+    nv: usize,
+    points: [&[F]; 3],
+    mu: &F,
+    openings: &[F],
+    z: &[F],
+    encoder: &BFVEncoder,
+) -> F {
+    let one = F::from_int(1, encoder);
+    let zero = F::from_int(0, encoder);
+    basis_cells(gates, points, mu, encoder)
+        .chunks(1 << nv)
+        .zip(openings)
+        .fold(zero.clone(), |acc, (cells, c)| {
+            let m = cells.len().next_power_of_two().ilog2() as usize;
+            let mut eq = vec![one.clone()];
+            for b in z[..m].iter().rev() {
+                let nb = one.sub(b, encoder);
+                eq = eq.iter().flat_map(|x| [x.mult(&nb, encoder), x.mult(b, encoder)]).collect();
+            }
+            let high = z[m..].iter().fold(one.clone(), |acc, b| acc.mult(&one.sub(b, encoder), encoder));
+            let u = cells
+                .iter()
+                .zip(&eq)
+                .fold(zero.clone(), |acc, (x, e)| acc.add(&x.mult(e, encoder), encoder));
+            acc.add(&c.mult(&u.mult(&high, encoder), encoder), encoder)
+        })
+}
+
+// the masking part of a zero-knowledge proof, outputs of the three masks in turn
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZkProof {
+    // every mask output summed over the hypercube, sent before the sumchecks
+    pub sums: Vec<F>,
+    // every mask output at the point of its sumcheck
+    pub evals: Vec<F>,
+    // round polynomials of the inner product of the coefficient columns and the basis
+    pub sumcheck: Vec<[Vec<F>; 1]>,
+    // the coefficient columns at the point of that sumcheck
+    pub openings: Vec<F>,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use arithmetic::{poly::MultiLinearPoly, test_utils::gen_params_n_ctx};
+    use poly_commit::deepfold::{DeepFoldConfig, DeepFoldParam, HidingDeepFoldProver};
+    use seal_fhe::{BFVEncoder, Plaintext};
+    use util::fiat_shamir::{Proof, Transcript};
+
+    use crate::{
+        builder::CircuitBuilder, gate::GateSet, proof::HyperPlonkProof, prover::Prover,
+        sumcheck::Sumcheck, verifier::Verifier,
+    };
+
+    use super::{basis_columns, basis_inner_product, mask_column_num, SumcheckMask, ZK_ROWS};
+
+    type F = Plaintext;
+
+    #[test]
+    fn masked_sumcheck() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+        let nv = 3;
+
+        // a * b and a over the hypercube
+        let a = (0..1 << nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let b = (0..1 << nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let mask = SumcheckMask::random(2, nv, 2, &encoder);
+        let rho = F::random_pt(&encoder);
+        let (_, _, rounds) = Sumcheck::prove_masked(
+            vec![a.clone(), b.clone()],
+            2,
+            |v: Vec<F>| [v[0].mult(&v[1], &encoder), v[0].clone()],
+            Some((&mask, &rho)),
+            &ctx,
+            &encoder,
+            &mut Transcript::new(),
+        );
+        let sums = mask.sums(&encoder);
+        let y = (0..1 << nv).fold(
+            [sums[0].mult(&rho, &encoder), sums[1].mult(&rho, &encoder)],
+            |acc, x| [acc[0].add(&a[x].mult(&b[x], &encoder), &encoder), acc[1].add(&a[x], &encoder)],
+        );
+        let (point, y) = Sumcheck::verify(y, 2, nv, rounds, &params, &ctx, &encoder, &mut Transcript::new()).unwrap();
+        let g = mask.evaluate(&point, &encoder);
+        let (a, b) = (
+            MultiLinearPoly::eval_multilinear_ext(&a, &point, &encoder),
+            MultiLinearPoly::eval_multilinear_ext(&b, &point, &encoder),
+        );
+        assert_eq!(y[0].sub(&rho.mult(&g[0], &encoder), &encoder), a.mult(&b, &encoder));
+        assert_eq!(y[1].sub(&rho.mult(&g[1], &encoder), &encoder), a);
+    }
+
+    #[test]
+    fn basis() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+        let (gates, nv) = (GateSet::plonk(), 4);
+
+        // the closed form agrees with evaluating every column over the hypercube
+        let random = |n: usize| (0..n).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let points = [random(nv), random(nv), random(nv)];
+        let points = [&points[0][..], &points[1][..], &points[2][..]];
+        let (mu, z) = (F::random_pt(&encoder), random(nv));
+        let openings = random(mask_column_num(nv, &gates));
+        let expected = basis_columns(&gates, nv, points, &mu, &encoder)
+            .iter()
+            .zip(&openings)
+            .fold(F::from_int(0, &encoder), |acc, (u, c)| {
+                acc.add(&c.mult(&MultiLinearPoly::eval_multilinear_ext(u, &z, &encoder), &encoder), &encoder)
+            });
+        assert_eq!(basis_inner_product(&gates, nv, points, &mu, &openings, &z, &encoder), expected);
+    }
+
+    #[test]
+    fn zero_knowledge() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        // out = x * y + x, with out public
+        let mut builder = CircuitBuilder::new(&encoder);
+        builder.zero_knowledge();
+        let x = builder.alloc_variable(F::random_pt(&encoder));
+        let y = builder.alloc_variable(F::random_pt(&encoder));
+        let xy = builder.mul_gate(x, y);
+        let out_value = builder.value(xy).add(builder.value(x), &encoder);
+        let out = builder.alloc_public(out_value);
+        let sum = builder.add_gate(xy, x);
+        builder.assert_equal(sum, out);
+        let (circuit, witness) = builder.build().unwrap();
+        assert!(circuit.zk);
+        let nv = witness[0].len().ilog2() as usize;
+        let public_inputs = witness[0][..circuit.public_num].to_vec();
+        assert!(circuit.public_num + 3 + ZK_ROWS <= 1 << nv);
+        assert_eq!(circuit.check_witness(&witness, &encoder, 1), Ok(()));
+
+        let config = DeepFoldConfig {
+            log_blowup: 2,
+            security_bits: 32,
+            final_poly_log_size: 2,
+            hiding: true,
+            ..DeepFoldConfig::new(nv)
+        };
+        let pp = DeepFoldParam::new(config, &ctx, &params).0;
+        let (pk, vk) = circuit.setup::<HidingDeepFoldProver>(&pp, &params, &ctx);
+        let prover = Prover::new(pk, &ctx, &params);
+        let verifier = Verifier::new(vk, &params, &ctx);
+        let proof = prover.prove(&pp, nv, witness.clone(), &params, &ctx, &encoder);
+        assert!(proof.zk.is_some());
+        assert!(verifier.verify(&pp, nv, &public_inputs, &proof));
+
+        let mut buffer = vec![0u8; proof.size()];
+        proof.serialize_into(&mut buffer);
+        let mut bytes = Proof::default();
+        bytes.append_u8_slice(&buffer, buffer.len());
+        assert_eq!(HyperPlonkProof::deserialize_from(&mut bytes, &ctx).as_ref(), Some(&proof));
+
+        // the same witness is blinded afresh, so the evaluations and rounds differ
+        let other = prover.prove(&pp, nv, witness.clone(), &params, &ctx, &encoder);
+        assert!(verifier.verify(&pp, nv, &public_inputs, &other));
+        assert_ne!(other.evals.witness, proof.evals.witness);
+        assert_ne!(other.gate_sumcheck, proof.gate_sumcheck);
+
+        // a mask value off by one no longer cancels, nor matches the committed coefficients
+        let mut forged = proof.clone();
+        let mask = forged.zk.as_mut().unwrap();
+        mask.evals[0] = mask.evals[0].add(&F::from_int(1, &encoder), &encoder);
+        assert!(!verifier.verify(&pp, nv, &public_inputs, &forged));
+
+        // and a wrong public output breaks the copy constraint as before
+        let mut wrong_inputs = public_inputs.clone();
+        wrong_inputs[0] = wrong_inputs[0].add(&F::from_int(1, &encoder), &encoder);
+        assert!(!verifier.verify(&pp, nv, &wrong_inputs, &proof));
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::Instant;
+
+use arithmetic::{poly::MultiLinearPoly, test_utils::gen_params_n_ctx};
+use poly_commit::{
+    deepfold::{DeepFoldConfig, DeepFoldParam, DeepFoldProver},
+    whir::{WhirParam, WhirProver},
+    CommitmentSerde, PolynomialCommitmentScheme, ProofSerde,
+};
+use seal_fhe::{BFVEncoder, Plaintext};
+use util::fiat_shamir::Transcript;
+
+type F = Plaintext;
+
+fn bench<'a, P: PolynomialCommitmentScheme<'a>>(
+    name: &str,
+    pp: &P::Param,
+    polies: &[Vec<F>],
+    point: &[F],
+    encoder: &BFVEncoder,
+) {
+    let nv = point.len();
+    let poly_num = polies.len();
+    let evals = vec![polies
+        .iter()
+        .map(|x| MultiLinearPoly::eval_multilinear(x, point, encoder))
+        .collect::<Vec<_>>()];
+    let size = P::Commitment::size(nv, poly_num, encoder);
+    let start = Instant::now();
+    let (commitment, data) = P::commit(pp, polies);
+    let mut buffer = vec![0u8; size];
+    commitment.serialize_into(&mut buffer, encoder);
+    let transcript = || {
+        let mut transcript = Transcript::new();
+        transcript.append_u8_slice(&buffer, size);
+        for i in evals[0].iter() {
+            transcript.append_f(i, encoder);
+        }
+        transcript
+    };
+    let proof = P::open(pp, &[&data], point, &mut transcript());
+    println!(
+        "{} prover: {} ms, size: {} B",
+        name,
+        start.elapsed().as_millis(),
+        proof.size()
+    );
+
+    let start = Instant::now();
+    assert_eq!(
+        P::verify(pp, &[&commitment], point, &evals, &proof, &mut transcript()),
+        Ok(())
+    );
+    println!("{} verifier: {} us", name, start.elapsed().as_micros());
+}
+
+fn main() {
+    let (params, ctx) = gen_params_n_ctx();
+    let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+    let nv = 10;
+    let batch = 3;
+    let code_rate = 1;
+    let security_bits = 100;
+    let polies = (0..(1 << batch))
+        .map(|_| {
+            (0..(1 << nv))
+                .map(|_| F::random_pt(&encoder))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let point = (0..nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+
+    let config = DeepFoldConfig {
+        log_blowup: code_rate,
+        security_bits,
+        folding_factor: 2,
+        ..DeepFoldConfig::new(nv)
+    };
+    let (pp, report) = DeepFoldParam::new(config, &ctx, &params);
+    println!("{}", report);
+    bench::<DeepFoldProver>("deepfold", &pp, &polies, &point, &encoder);
+
+    let pp = WhirParam::search(nv, code_rate, security_bits, 1 << batch, &ctx, &params);
+    println!(
+        "whir folding factor: {}, queries: {:?}, estimated size: {} B",
+        pp.folding_factor,
+        pp.query_nums,
+        pp.estimated_proof_size(1 << batch)
+    );
+    bench::<WhirProver>("whir", &pp, &polies, &point, &encoder);
+}
@@ -1,91 +1,188 @@
-use std::{collections::HashMap, marker::PhantomData};
+use std::{collections::HashMap, marker::PhantomData, mem::size_of};
 
 use arithmetic::{
-    field::{as_bytes_vec, FftField, Field},
+    field::{as_bytes_vec, plaintext_size},
     mul_group::Radix2Group,
     poly::MultiLinearPoly,
 };
+use rand::RngCore;
+use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
 use util::{
     fiat_shamir::Proof,
     merkle_tree::{MerkleTreeProver, MerkleTreeVerifier, HASH_SIZE},
 };
 
-use crate::Transcript;
-
-use super::{CommitmentSerde, PolyCommitProver, PolyCommitVerifier};
-
-#[derive(Debug, Clone, Default)]
-pub struct MerkleRoot([u8; HASH_SIZE]);
+use crate::{
+    ensure_remaining,
+    iopp::{fold_codeword, fold_fiber},
+    soundness::{DecodingRegime, SoundnessReport},
+    MerkleRoot, OpeningProof, PcsError, PolynomialCommitmentScheme, ProofSerde, Transcript,
+};
 
-impl CommitmentSerde for MerkleRoot {
-    fn size(nv: usize, np: usize) -> usize {
-        HASH_SIZE
-    }
+type F = Plaintext;
 
-    fn serialize_into(&self, buffer: &mut [u8]) {
-        buffer.copy_from_slice(&self.0);
-    }
+// the choices behind a BaseFold instance; the query count follows from them.
+// basefold's proximity gaps are proven in the unique decoding regime only
+#[derive(Debug, Clone, Copy)]
+pub struct BaseFoldConfig {
+    pub variable_num: usize,
+    pub log_blowup: usize,
+    pub security_bits: usize,
+    // log2 of the folding arity
+    pub folding_factor: usize,
+    // grinding before the queries, each bit of work saving query bits; 0 skips it
+    pub pow_bits: usize,
+}
 
-    fn deserialize_from(proof: &mut Proof, nv: usize, np: usize) -> Self {
-        let root = proof.get_next_hash();
-        Self(root)
+impl BaseFoldConfig {
+    // rate 1/2, 100 bits, binary folding, no grinding
+    pub fn new(variable_num: usize) -> Self {
+        BaseFoldConfig {
+            variable_num,
+            log_blowup: 1,
+            security_bits: 100,
+            folding_factor: 1,
+            pow_bits: 0,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct BaseFoldParam<F: FftField> {
-    pub mult_subgroups: Vec<Radix2Group<F::FftBaseField>>,
+pub struct BaseFoldParam<'a> {
+    pub mult_subgroups: Vec<Radix2Group>,
     pub variable_num: usize,
     pub query_num: usize,
+    pub pow_bits: usize,
+    // log2 of the folding arity, each round folds this many variables with one merkle tree
+    pub folding_factor: usize,
+    pub ctx: &'a Context,
+    pub params: &'a EncryptionParameters,
+}
+
+impl<'a> BaseFoldParam<'a> {
+    pub fn new(
+        config: BaseFoldConfig,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+    ) -> (Self, SoundnessReport) {
+        let variable_num = config.variable_num;
+        let modulus = params.get_plain_modulus().value();
+        let mut mult_subgroups = vec![Radix2Group::new(
+            (variable_num + config.log_blowup) as u32,
+            modulus,
+        )];
+        for i in 1..variable_num {
+            mult_subgroups.push(mult_subgroups[i - 1].exp(2));
+        }
+        let pow_bits = config.pow_bits;
+        let query_num =
+            DecodingRegime::Unique.query_num(config.security_bits, config.log_blowup, pow_bits);
+        let report = SoundnessReport::new(
+            DecodingRegime::Unique,
+            variable_num,
+            config.log_blowup,
+            modulus.ilog2() as usize,
+            config.security_bits,
+            query_num,
+            pow_bits,
+        );
+        let pp = BaseFoldParam {
+            mult_subgroups,
+            variable_num,
+            query_num,
+            pow_bits,
+            folding_factor: config.folding_factor,
+            ctx,
+            params,
+        };
+        (pp, report)
+    }
+
+    pub fn encoder(&self) -> BFVEncoder<'a> {
+        BFVEncoder::new(self.ctx, self.params).unwrap()
+    }
+
+    pub fn folded_var_num(&self) -> usize {
+        self.variable_num
+    }
+
+    pub fn round_num(&self) -> usize {
+        (self.variable_num + self.folding_factor - 1) / self.folding_factor
+    }
+
+    // only the last round may fold fewer than `folding_factor` variables
+    pub fn round_folding(&self, round: usize) -> usize {
+        self.folding_factor
+            .min(self.variable_num - round * self.folding_factor)
+    }
+
+    // parameters for points of `nv` variables, reusing the tail of the subgroup chain
+    pub fn trim(&self, nv: usize) -> Self {
+        assert!(nv <= self.variable_num);
+        BaseFoldParam {
+            mult_subgroups: self.mult_subgroups[self.variable_num - nv..].to_vec(),
+            variable_num: nv,
+            query_num: self.query_num,
+            pow_bits: self.pow_bits,
+            folding_factor: self.folding_factor,
+            ctx: self.ctx,
+            params: self.params,
+        }
+    }
 }
 
 #[derive(Clone)]
-pub struct QueryResult<F: Field> {
+pub struct QueryResult {
     pub proof_bytes: Vec<u8>,
     pub proof_values: HashMap<usize, F>,
 }
 
-impl<F: Field> QueryResult<F> {
+impl QueryResult {
+    // the opened leaves of `round` against its root
     pub fn verify_merkle_tree(
         &self,
+        round: usize,
         leaf_indices: &Vec<usize>,
         leaf_size: usize,
         merkle_verifier: &MerkleTreeVerifier,
-    ) -> bool {
+        encoder: &BFVEncoder,
+    ) -> Result<(), PcsError> {
         let len = merkle_verifier.leave_number;
-        let leaves: Vec<Vec<u8>> = leaf_indices
+        let leaves = leaf_indices
             .iter()
             .map(|i| {
-                as_bytes_vec(
-                    &(0..leaf_size)
-                        .map(|j| self.proof_values.get(&(i + j * len)).unwrap().clone())
-                        .collect::<Vec<_>>(),
-                )
+                (0..leaf_size)
+                    .map(|j| self.proof_values.get(&(i + j * len)).cloned())
+                    .collect::<Option<Vec<_>>>()
+                    .map(|x| as_bytes_vec(&x, encoder))
             })
-            .collect();
-        let res = merkle_verifier.verify(self.proof_bytes.clone(), leaf_indices, &leaves);
-        assert!(res);
-        res
+            .collect::<Option<Vec<_>>>()
+            .ok_or(PcsError::MalformedProof)?;
+        if !merkle_verifier.verify(self.proof_bytes.clone(), leaf_indices, &leaves) {
+            return Err(PcsError::MerklePath { round });
+        }
+        Ok(())
     }
 }
 
 #[derive(Clone)]
-pub struct InterpolateValue<F: Field> {
+pub struct InterpolateValue {
     pub value: Vec<F>,
     leaf_size: usize,
     merkle_tree: MerkleTreeProver,
 }
 
-impl<F: FftField> InterpolateValue<F> {
-    pub fn new(value: Vec<F>, leaf_size: usize) -> Self {
+impl InterpolateValue {
+    pub fn new(value: Vec<F>, leaf_size: usize, encoder: &BFVEncoder) -> Self {
         let len = value.len() / leaf_size;
         let merkle_tree = MerkleTreeProver::new(
             (0..len)
                 .map(|i| {
-                    as_bytes_vec::<F>(
+                    as_bytes_vec(
                         &(0..leaf_size)
-                            .map(|j| value[len * j + i])
+                            .map(|j| value[len * j + i].clone())
                             .collect::<Vec<_>>(),
+                        encoder,
                     )
                 })
                 .collect(),
@@ -112,7 +209,7 @@ impl<F: FftField> InterpolateValue<F> {
             .flat_map(|i| {
                 leaf_indices
                     .iter()
-                    .map(|j| self.value[j.clone() + i * len])
+                    .map(|j| self.value[j + i * len].clone())
                     .collect::<Vec<_>>()
             })
             .collect();
@@ -122,223 +219,140 @@ impl<F: FftField> InterpolateValue<F> {
 }
 
 #[derive(Clone)]
-pub struct BasefoldProver<F: FftField> {
-    pub interpolation: InterpolateValue<F::FftBaseField>,
-    poly: Vec<Vec<F::BaseField>>,
-}
-
-impl<F: FftField> BasefoldProver<F> {
-    fn evaluate_next_domain(
-        last_interpolation: &Vec<F>,
-        pp: &BaseFoldParam<F>,
-        round: usize,
-        challenge: F,
-    ) -> Vec<F> {
-        let mut res = vec![];
-        let len = pp.mult_subgroups[round].size();
-        let subgroup = &pp.mult_subgroups[round];
-        for i in 0..(len / 2) {
-            let x = last_interpolation[i];
-            let nx = last_interpolation[i + len / 2];
-            let sum = x + nx;
-            let new_v = sum + challenge * ((x - nx) * F::from(subgroup.element_inv_at(i)) - sum);
-            res.push(new_v.mul_base_elem(<F as Field>::BaseField::inv_2()));
-        }
-        res
-    }
+pub struct BasefoldProver<'a> {
+    pub interpolation: InterpolateValue,
+    poly: Vec<Vec<F>>,
+    _data: PhantomData<&'a Context>,
 }
 
-impl<F: FftField> PolyCommitProver<F> for BasefoldProver<F> {
-    type Param = BaseFoldParam<F>;
-    type Commitment = MerkleRoot;
-
-    fn new(pp: &Self::Param, poly: &[Vec<F::BaseField>]) -> Self {
+impl<'a> BasefoldProver<'a> {
+    pub fn new(pp: &BaseFoldParam<'a>, poly: &[Vec<F>]) -> Self {
+        let encoder = pp.encoder();
         let values = poly
             .iter()
-            .flat_map(|x| pp.mult_subgroups[0].fft(x.clone()))
+            .flat_map(|x| pp.mult_subgroups[0].fft(x.clone(), &encoder))
             .collect::<Vec<_>>();
         BasefoldProver {
-            interpolation: InterpolateValue::new(values, 2 * poly.len()),
-            poly: poly.iter().map(|x| x.clone()).collect(),
+            interpolation: InterpolateValue::new(
+                values,
+                (1 << pp.round_folding(0)) * poly.len(),
+                &encoder,
+            ),
+            poly: poly.to_vec(),
+            _data: PhantomData,
         }
     }
 
-    fn commit(&self) -> Self::Commitment {
+    pub fn root(&self) -> MerkleRoot {
         MerkleRoot(self.interpolation.commit())
     }
-
-    fn open(pp: &Self::Param, provers: Vec<&Self>, point: Vec<F>, transcript: &mut Transcript) {
-        let mut interpolations: Vec<InterpolateValue<F>> = vec![];
-        let r: F = transcript.challenge_f();
-        let mut poly_evals = provers[0].poly[0]
-            .iter()
-            .map(|x| F::from(*x))
-            .collect::<Vec<_>>();
-        for i in 0..provers.len() {
-            let start = if i == 0 { 1 } else { 0 };
-            for j in start..provers[i].poly.len() {
-                for k in 0..poly_evals.len() {
-                    poly_evals[k] *= r;
-                    poly_evals[k].add_assign_base_elem(provers[i].poly[j][k]);
-                }
-            }
-        }
-        let len = pp.mult_subgroups[0].size();
-        let mut poly_interpolations = (0..len).map(|_| F::zero()).collect::<Vec<_>>();
-        for i in 0..provers.len() {
-            for j in 0..len {
-                for k in 0..provers[i].poly.len() {
-                    poly_interpolations[j] *= r;
-                    poly_interpolations[j] += F::from(provers[i].interpolation.value[j + len * k]);
-                }
-            }
-        }
-        for i in 0..pp.variable_num {
-            let mut new_point = point[i..].to_vec();
-            new_point[0].add_assign_base_elem(F::BaseField::one());
-            transcript.append_f(MultiLinearPoly::eval_multilinear_ext(
-                &poly_evals,
-                &new_point,
-            ));
-            let challenge: F = transcript.challenge_f();
-            let new_len = poly_evals.len() / 2;
-            for j in 0..new_len {
-                poly_evals[j] =
-                    poly_evals[j * 2] + (poly_evals[j * 2 + 1] - poly_evals[j * 2]) * challenge;
-            }
-            poly_evals.truncate(new_len);
-            let next_evaluation = Self::evaluate_next_domain(
-                if i == 0 {
-                    &poly_interpolations
-                } else {
-                    &interpolations[i - 1].value
-                },
-                pp,
-                i,
-                challenge,
-            );
-            if i < pp.variable_num - 1 {
-                let new_interpolation = InterpolateValue::new(next_evaluation, 2);
-                transcript.append_u8_slice(&new_interpolation.commit(), HASH_SIZE);
-                interpolations.push(new_interpolation);
-            } else {
-                transcript.append_f(next_evaluation[0]);
-            }
-        }
-        let mut leaf_indices = transcript.challenge_usizes(pp.query_num);
-        for i in 0..pp.variable_num {
-            let len = pp.mult_subgroups[i].size();
-            leaf_indices = leaf_indices.iter_mut().map(|v| *v % (len >> 1)).collect();
-            leaf_indices.sort();
-            leaf_indices.dedup();
-            if i == 0 {
-                let query = provers
-                    .iter()
-                    .map(|j| j.interpolation.query(&leaf_indices))
-                    .collect::<Vec<_>>();
-                for q in query {
-                    transcript.append_u8_slice(&q.0, q.0.len());
-                    for j in q.1 {
-                        transcript.append_f(j);
-                    }
-                }
-            } else {
-                let query = interpolations[i - 1].query(&leaf_indices);
-                transcript.append_u8_slice(&query.0, query.0.len());
-                for j in query.1 {
-                    transcript.append_f(j);
-                }
-            }
-        }
-    }
 }
 
 #[derive(Clone)]
-pub struct BaseFoldVerifier<F: FftField> {
+pub struct BaseFoldVerifier<'a> {
     commit: MerkleTreeVerifier,
     poly_num: usize,
-    _data: PhantomData<F>,
+    _data: PhantomData<&'a Context>,
 }
 
-impl<F: FftField> PolyCommitVerifier<F> for BaseFoldVerifier<F> {
-    type Param = BaseFoldParam<F>;
-    type Commitment = MerkleRoot;
-
-    fn new(pp: &Self::Param, commit: Self::Commitment, poly_num: usize) -> Self {
+impl<'a> BaseFoldVerifier<'a> {
+    pub fn new(pp: &BaseFoldParam<'a>, commit: MerkleRoot, poly_num: usize) -> Self {
         BaseFoldVerifier {
-            commit: MerkleTreeVerifier::new(pp.mult_subgroups[0].size() / 2, commit.0),
+            commit: MerkleTreeVerifier::new(
+                pp.mult_subgroups[0].size() >> pp.round_folding(0),
+                commit.0,
+            ),
             poly_num,
-            _data: PhantomData::default(),
+            _data: PhantomData,
         }
     }
 
-    fn verify(
-        pp: &Self::Param,
-        verifiers: Vec<&Self>,
+    // verify reporting why a proof is rejected; a short proof is rejected, not a panic
+    pub fn check(
+        pp: &BaseFoldParam<'a>,
+        verifiers: &[&Self],
         point: Vec<F>,
         evals: Vec<Vec<F>>,
         transcript: &mut Transcript,
         proof: &mut Proof,
-    ) -> bool {
-        let r = transcript.challenge_f();
-        let mut eval = F::zero();
+    ) -> Result<(), PcsError> {
+        let encoder = pp.encoder();
+        let f_size = plaintext_size(&encoder);
+        let zero = F::from_int(0, &encoder);
+        let r = transcript.challenge_f(&encoder);
+        let mut eval = zero.clone();
         for i in evals {
             for j in i {
-                eval *= r;
-                eval += j;
+                eval = F::add(&F::mult(&eval, &r, &encoder), &j, &encoder);
             }
         }
         let mut challenges = vec![];
         let mut commits = vec![];
-        for i in 0..point.len() {
-            let next_eval = proof.get_next_and_step::<F>();
-            transcript.append_f(next_eval);
-            let challenge = transcript.challenge_f::<F>();
+        let folded_var_num = pp.folded_var_num();
+        for i in 0..folded_var_num {
+            ensure_remaining(proof, f_size)?;
+            let next_eval = proof.get_next_and_step(&encoder);
+            transcript.append_f(&next_eval, &encoder);
+            let challenge = transcript.challenge_f(&encoder);
 
-            eval += (challenge - point[i]) * (next_eval - eval);
+            let step = F::sub(&challenge, &point[i], &encoder);
+            let diff = F::sub(&next_eval, &eval, &encoder);
+            eval = F::add(&eval, &F::mult(&step, &diff, &encoder), &encoder);
             challenges.push(challenge);
-            if i < pp.variable_num - 1 {
+            if i == folded_var_num - 1 {
+                ensure_remaining(proof, f_size)?;
+                let final_value = proof.get_next_and_step(&encoder);
+                transcript.append_f(&final_value, &encoder);
+                if final_value != eval {
+                    return Err(PcsError::Evaluation);
+                }
+            } else if (i + 1) % pp.folding_factor == 0 {
+                ensure_remaining(proof, HASH_SIZE)?;
                 let merkle_root = proof.get_next_hash();
                 transcript.append_u8_slice(&merkle_root, HASH_SIZE);
                 commits.push(MerkleTreeVerifier::new(
-                    pp.mult_subgroups[i + 1].size() / 2,
+                    pp.mult_subgroups[i + 1].size()
+                        >> pp.round_folding((i + 1) / pp.folding_factor),
                     merkle_root,
                 ));
-            } else {
-                let final_value = proof.get_next_and_step::<F>();
-                transcript.append_f(final_value);
-                if final_value != eval {
-                    return false;
-                }
             }
         }
 
+        if pp.pow_bits > 0 {
+            ensure_remaining(proof, size_of::<u64>())?;
+        }
+        if !transcript.verify_grind(proof, pp.pow_bits) {
+            return Err(PcsError::ProofOfWork);
+        }
         let mut leaf_indices = transcript.challenge_usizes(pp.query_num);
         let mut indices = leaf_indices.clone();
         let mut query_results = vec![];
-        for i in 0..pp.variable_num {
-            let len = pp.mult_subgroups[i].size();
-            leaf_indices = leaf_indices.iter_mut().map(|v| *v % (len >> 1)).collect();
+        for i in 0..pp.round_num() {
+            let arity = 1 << pp.round_folding(i);
+            let leaf_num = pp.mult_subgroups[i * pp.folding_factor].size() / arity;
+            leaf_indices = leaf_indices.iter_mut().map(|v| *v % leaf_num).collect();
             leaf_indices.sort();
             leaf_indices.dedup();
+            let query_num = leaf_indices.len();
 
             if i == 0 {
                 let mut poly_values = vec![];
                 for j in 0..verifiers.len() {
-                    let proof_bytes =
-                        proof.get_next_slice(verifiers[j].commit.proof_length(&leaf_indices));
-                    let proof_values = (0..leaf_indices.len() * 2 * verifiers[j].poly_num)
-                        .map(|_| proof.get_next_and_step::<F::FftBaseField>())
+                    let proof_length = verifiers[j].commit.proof_length(&leaf_indices);
+                    let value_num = query_num * arity * verifiers[j].poly_num;
+                    ensure_remaining(proof, proof_length + f_size * value_num)?;
+                    let proof_bytes = proof.get_next_slice(proof_length);
+                    let proof_values = (0..value_num)
+                        .map(|_| proof.get_next_and_step(&encoder))
                         .collect::<Vec<_>>();
                     transcript.append_u8_slice(&proof_bytes, proof_bytes.len());
                     for k in &proof_values {
-                        transcript.append_f(*k);
+                        transcript.append_f(k, &encoder);
                     }
                     poly_values.append(
                         &mut (0..verifiers[j].poly_num)
                             .map(|k| {
-                                (&proof_values
-                                    [k * leaf_indices.len() * 2..(k + 1) * leaf_indices.len() * 2])
+                                (&proof_values[k * query_num * arity..(k + 1) * query_num * arity])
                                     .to_vec()
                             })
                             .collect::<Vec<_>>(),
@@ -350,26 +364,26 @@ impl<F: FftField> PolyCommitVerifier<F> for BaseFoldVerifier<F> {
                             .enumerate()
                             .map(|(idx, x)| {
                                 (
-                                    leaf_indices[idx % leaf_indices.len()]
-                                        + (len / 2) * (idx / leaf_indices.len()),
+                                    leaf_indices[idx % query_num] + leaf_num * (idx / query_num),
                                     x,
                                 )
                             })
                             .collect(),
                     };
-                    assert!(query.verify_merkle_tree(
+                    query.verify_merkle_tree(
+                        i,
                         &leaf_indices,
-                        2 * verifiers[j].poly_num,
-                        &verifiers[j].commit
-                    ));
+                        arity * verifiers[j].poly_num,
+                        &verifiers[j].commit,
+                        &encoder,
+                    )?;
                 }
-                let poly_values = (0..leaf_indices.len() * 2)
+                let poly_values = (0..query_num * arity)
                     .into_iter()
                     .map(|j| {
-                        let mut x = F::zero();
+                        let mut x = zero.clone();
                         for k in 0..poly_values.len() {
-                            x *= r;
-                            x += F::from(poly_values[k][j]);
+                            x = F::add(&F::mult(&x, &r, &encoder), &poly_values[k][j], &encoder);
                         }
                         x
                     })
@@ -377,65 +391,390 @@ impl<F: FftField> PolyCommitVerifier<F> for BaseFoldVerifier<F> {
 
                 query_results.push(QueryResult {
                     proof_bytes: vec![],
-                    proof_values: leaf_indices
-                        .iter()
-                        .map(|&x| x)
-                        .chain(leaf_indices.iter().map(|&x| x + len / 2))
+                    proof_values: (0..arity)
+                        .flat_map(|m| leaf_indices.iter().map(move |&x| x + m * leaf_num))
                         .zip(poly_values)
                         .collect(),
                 })
             } else {
-                let proof_bytes = proof.get_next_slice(commits[i - 1].proof_length(&leaf_indices));
-                let proof_values = (0..leaf_indices.len() * 2)
-                    .map(|_| proof.get_next_and_step::<F>())
+                let proof_length = commits[i - 1].proof_length(&leaf_indices);
+                ensure_remaining(proof, proof_length + f_size * query_num * arity)?;
+                let proof_bytes = proof.get_next_slice(proof_length);
+                let proof_values = (0..query_num * arity)
+                    .map(|_| proof.get_next_and_step(&encoder))
                     .collect::<Vec<_>>();
                 transcript.append_u8_slice(&proof_bytes, proof_bytes.len());
                 for j in &proof_values {
-                    transcript.append_f(*j);
+                    transcript.append_f(j, &encoder);
                 }
                 let query = QueryResult {
                     proof_bytes,
-                    proof_values: leaf_indices
-                        .iter()
-                        .map(|&x| x)
-                        .chain(leaf_indices.iter().map(|x| x + len / 2))
+                    proof_values: (0..arity)
+                        .flat_map(|m| leaf_indices.iter().map(move |&x| x + m * leaf_num))
                         .zip(proof_values.into_iter())
                         .collect(),
                 };
-                query.verify_merkle_tree(&leaf_indices, 2, &commits[i - 1]);
+                query.verify_merkle_tree(i, &leaf_indices, arity, &commits[i - 1], &encoder)?;
                 query_results.push(query);
             }
         }
         drop(leaf_indices);
-        for i in 0..pp.variable_num {
-            let len = pp.mult_subgroups[i].size();
-            indices = indices.iter_mut().map(|v| *v % (len >> 1)).collect();
+        for i in 0..pp.round_num() {
+            let start = i * pp.folding_factor;
+            let folding = pp.round_folding(i);
+            let leaf_num = pp.mult_subgroups[start].size() >> folding;
+            indices = indices.iter_mut().map(|v| *v % leaf_num).collect();
             indices.sort();
             indices.dedup();
 
             for j in indices.iter() {
-                let x = query_results[i].proof_values.get(&j).unwrap().clone();
-                let nx = query_results[i]
-                    .proof_values
-                    .get(&(j + len / 2))
-                    .unwrap()
-                    .clone();
-                let sum = x + nx;
-                let new_v = sum
-                    + challenges[i]
-                        * ((x - nx) * F::from(pp.mult_subgroups[i].element_inv_at(*j)) - sum);
-                if i < pp.variable_num - 1 {
-                    if new_v != query_results[i + 1].proof_values[j].double() {
-                        println!("{} {}", file!(), line!());
-                        return false;
+                let values = (0..(1 << folding))
+                    .map(|m| query_results[i].proof_values[&(j + m * leaf_num)].clone())
+                    .collect();
+                let new_v = fold_fiber(
+                    values,
+                    *j,
+                    &pp.mult_subgroups[start..],
+                    &challenges[start..start + folding],
+                    &encoder,
+                );
+                if i < pp.round_num() - 1 {
+                    if new_v != query_results[i + 1].proof_values[j] {
+                        return Err(PcsError::Folding { round: i });
                     }
-                } else {
-                    if new_v.mul_base_elem(<F as Field>::BaseField::inv_2()) != eval {
-                        return false;
+                } else if new_v != eval {
+                    return Err(PcsError::Folding { round: i });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> PolynomialCommitmentScheme<'a> for BasefoldProver<'a> {
+    type Param = BaseFoldParam<'a>;
+    type Commitment = MerkleRoot;
+    type ProverData = Self;
+    type Proof = OpeningProof;
+
+    // transparent, the rng is not needed
+    fn setup(
+        max_nv: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+        _rng: &mut impl RngCore,
+    ) -> Self::Param {
+        BaseFoldParam::new(BaseFoldConfig::new(max_nv), ctx, params).0
+    }
+
+    fn trim(pp: &Self::Param, nv: usize) -> Self::Param {
+        pp.trim(nv)
+    }
+
+    fn commit(pp: &Self::Param, polies: &[Vec<F>]) -> (Self::Commitment, Self) {
+        let prover = Self::new(pp, polies);
+        (prover.root(), prover)
+    }
+
+    fn open(
+        pp: &Self::Param,
+        provers: &[&Self],
+        point: &[F],
+        transcript: &mut Transcript,
+    ) -> Self::Proof {
+        let proof_start = transcript.proof.bytes.len();
+        let encoder = pp.encoder();
+        let mut interpolations: Vec<InterpolateValue> = vec![];
+        let r = transcript.challenge_f(&encoder);
+        let mut poly_evals = provers[0].poly[0].clone();
+        for i in 0..provers.len() {
+            let start = if i == 0 { 1 } else { 0 };
+            for j in start..provers[i].poly.len() {
+                for k in 0..poly_evals.len() {
+                    poly_evals[k] = F::add(
+                        &F::mult(&poly_evals[k], &r, &encoder),
+                        &provers[i].poly[j][k],
+                        &encoder,
+                    );
+                }
+            }
+        }
+        let len = pp.mult_subgroups[0].size();
+        let mut poly_interpolations = vec![F::from_int(0, &encoder); len];
+        for i in 0..provers.len() {
+            for j in 0..len {
+                for k in 0..provers[i].poly.len() {
+                    poly_interpolations[j] = F::add(
+                        &F::mult(&poly_interpolations[j], &r, &encoder),
+                        &provers[i].interpolation.value[j + len * k],
+                        &encoder,
+                    );
+                }
+            }
+        }
+        let one = F::from_int(1, &encoder);
+        let folded_var_num = pp.folded_var_num();
+        for i in 0..folded_var_num {
+            let mut new_point = point[i..].to_vec();
+            new_point[0] = F::add(&new_point[0], &one, &encoder);
+            transcript.append_f(
+                &MultiLinearPoly::eval_multilinear_ext(&poly_evals, &new_point, &encoder),
+                &encoder,
+            );
+            let challenge = transcript.challenge_f(&encoder);
+            let new_len = poly_evals.len() / 2;
+            for j in 0..new_len {
+                let diff = F::sub(&poly_evals[j * 2 + 1], &poly_evals[j * 2], &encoder);
+                poly_evals[j] = F::add(
+                    &poly_evals[j * 2],
+                    &F::mult(&diff, &challenge, &encoder),
+                    &encoder,
+                );
+            }
+            poly_evals.truncate(new_len);
+            poly_interpolations = fold_codeword(
+                &poly_interpolations,
+                &pp.mult_subgroups[i],
+                &challenge,
+                &encoder,
+            );
+            if i == folded_var_num - 1 {
+                transcript.append_f(&poly_interpolations[0], &encoder);
+            } else if (i + 1) % pp.folding_factor == 0 {
+                let new_interpolation = InterpolateValue::new(
+                    poly_interpolations.clone(),
+                    1 << pp.round_folding((i + 1) / pp.folding_factor),
+                    &encoder,
+                );
+                transcript.append_u8_slice(&new_interpolation.commit(), HASH_SIZE);
+                interpolations.push(new_interpolation);
+            }
+        }
+        transcript.grind(pp.pow_bits);
+        let mut leaf_indices = transcript.challenge_usizes(pp.query_num);
+        for i in 0..pp.round_num() {
+            let len = pp.mult_subgroups[i * pp.folding_factor].size();
+            leaf_indices = leaf_indices
+                .iter_mut()
+                .map(|v| *v % (len >> pp.round_folding(i)))
+                .collect();
+            leaf_indices.sort();
+            leaf_indices.dedup();
+            if i == 0 {
+                let query = provers
+                    .iter()
+                    .map(|j| j.interpolation.query(&leaf_indices))
+                    .collect::<Vec<_>>();
+                for q in query {
+                    transcript.append_u8_slice(&q.0, q.0.len());
+                    for j in q.1.iter() {
+                        transcript.append_f(j, &encoder);
                     }
                 }
+            } else {
+                let query = interpolations[i - 1].query(&leaf_indices);
+                transcript.append_u8_slice(&query.0, query.0.len());
+                for j in query.1.iter() {
+                    transcript.append_f(j, &encoder);
+                }
+            }
+        }
+        OpeningProof {
+            bytes: transcript.proof.bytes[proof_start..].to_vec(),
+        }
+    }
+
+    fn verify(
+        pp: &Self::Param,
+        commits: &[&Self::Commitment],
+        point: &[F],
+        evals: &[Vec<F>],
+        proof: &Self::Proof,
+        transcript: &mut Transcript,
+    ) -> Result<(), PcsError> {
+        if commits.len() != evals.len() || point.len() != pp.variable_num {
+            return Err(PcsError::Claim);
+        }
+        let verifiers = commits
+            .iter()
+            .zip(evals.iter())
+            .map(|(commit, evals)| BaseFoldVerifier::new(pp, (*commit).clone(), evals.len()))
+            .collect::<Vec<_>>();
+        let mut reader = proof.reader();
+        BaseFoldVerifier::check(
+            pp,
+            &verifiers.iter().collect::<Vec<_>>(),
+            point.to_vec(),
+            evals.to_vec(),
+            transcript,
+            &mut reader,
+        )?;
+        if reader.remaining() > 0 {
+            return Err(PcsError::MalformedProof);
+        }
+        Ok(())
+    }
+
+    // one commitment of `poly_num` polynomials, counting every query as its own merkle path
+    fn proof_size(pp: &Self::Param, nv: usize, poly_num: usize) -> usize {
+        let pp = pp.trim(nv);
+        let f_size = plaintext_size(&pp.encoder());
+        let mut size = f_size * (pp.folded_var_num() + 1)
+            + HASH_SIZE * (pp.round_num() - 1)
+            + if pp.pow_bits > 0 { size_of::<u64>() } else { 0 };
+        for i in 0..pp.round_num() {
+            let arity = 1 << pp.round_folding(i);
+            let leaf_num = pp.mult_subgroups[i * pp.folding_factor].size() / arity;
+            let value_size = if i == 0 { f_size * poly_num } else { f_size };
+            size += pp.query_num * (value_size * arity + HASH_SIZE * leaf_num.ilog2() as usize);
+        }
+        OpeningProof::default().size() + size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arithmetic::{poly::MultiLinearPoly, test_utils::gen_params_n_ctx};
+    use seal_fhe::Plaintext;
+    use util::fiat_shamir::Transcript;
+
+    use crate::{
+        CommitmentSerde, MerkleRoot, OpeningProof, PcsError, PolynomialCommitmentScheme,
+        ProofSerde,
+    };
+
+    use super::{BaseFoldConfig, BaseFoldParam, BasefoldProver};
+
+    type F = Plaintext;
+
+    fn commit_open_verify(pp: &BaseFoldParam, poly_num: usize) {
+        let encoder = pp.encoder();
+        let nv = pp.variable_num;
+        let polies = (0..poly_num)
+            .map(|_| {
+                (0..(1 << nv))
+                    .map(|_| F::random_pt(&encoder))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let point = (0..nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let evals = vec![polies
+            .iter()
+            .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &encoder))
+            .collect::<Vec<_>>()];
+
+        let (commitment, prover) = BasefoldProver::commit(pp, &polies);
+        let size = MerkleRoot::size(nv, poly_num, &encoder);
+        let mut buffer = vec![0u8; size];
+        commitment.serialize_into(&mut buffer, &encoder);
+        let transcript = || {
+            let mut transcript = Transcript::new();
+            transcript.append_u8_slice(&buffer, size);
+            for i in &evals[0] {
+                transcript.append_f(i, &encoder);
             }
+            transcript
+        };
+        let proof = BasefoldProver::open(pp, &[&prover], &point, &mut transcript());
+        assert_eq!(
+            BasefoldProver::verify(
+                pp,
+                &[&commitment],
+                &point,
+                &evals,
+                &proof,
+                &mut transcript()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn folding_factor() {
+        let (params, ctx) = gen_params_n_ctx();
+        for folding_factor in 1..=4 {
+            let config = BaseFoldConfig {
+                log_blowup: 2,
+                security_bits: 32,
+                folding_factor,
+                ..BaseFoldConfig::new(7)
+            };
+            let (pp, report) = BaseFoldParam::new(config, &ctx, &params);
+            assert_eq!((report.query_num, report.pow_bits), (pp.query_num, pp.pow_bits));
+            commit_open_verify(&pp, 2);
         }
-        true
+    }
+
+    #[test]
+    fn grinding() {
+        let (params, ctx) = gen_params_n_ctx();
+        let config = BaseFoldConfig {
+            log_blowup: 2,
+            security_bits: 32,
+            ..BaseFoldConfig::new(7)
+        };
+        let (plain, _) = BaseFoldParam::new(config, &ctx, &params);
+        let (pp, report) = BaseFoldParam::new(
+            BaseFoldConfig {
+                pow_bits: 8,
+                ..config
+            },
+            &ctx,
+            &params,
+        );
+        // every bit of work takes a bit off the queries
+        assert_eq!(report.pow_bits, 8);
+        assert!(pp.query_num < plain.query_num);
+        commit_open_verify(&pp, 2);
+    }
+
+    #[test]
+    fn scheme() {
+        type Scheme<'a> = BasefoldProver<'a>;
+        let (params, ctx) = gen_params_n_ctx();
+        let mut rng = rand::thread_rng();
+        let pp = Scheme::setup(8, &ctx, &params, &mut rng);
+        let pp = <Scheme as PolynomialCommitmentScheme>::trim(&pp, 6);
+        let encoder = pp.encoder();
+        let polies = (0..2)
+            .map(|_| {
+                (0..(1 << 6))
+                    .map(|_| F::random_pt(&encoder))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let point = (0..6).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let evals = vec![polies
+            .iter()
+            .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &encoder))
+            .collect::<Vec<_>>()];
+
+        let (commitment, data) = <Scheme as PolynomialCommitmentScheme>::commit(&pp, &polies);
+        let proof = <Scheme as PolynomialCommitmentScheme>::open(
+            &pp,
+            &[&data],
+            &point,
+            &mut Transcript::new(),
+        );
+        assert!(proof.size() <= Scheme::proof_size(&pp, 6, 2));
+        let verify = |evals: &[Vec<F>], proof: &OpeningProof| {
+            <Scheme as PolynomialCommitmentScheme>::verify(
+                &pp,
+                &[&commitment],
+                &point,
+                evals,
+                proof,
+                &mut Transcript::new(),
+            )
+        };
+        assert_eq!(verify(&evals, &proof), Ok(()));
+
+        let mut wrong_evals = evals.clone();
+        wrong_evals[0][0] = F::add(&wrong_evals[0][0], &F::from_int(1, &encoder), &encoder);
+        assert!(verify(&wrong_evals, &proof).is_err());
+        let truncated = OpeningProof {
+            bytes: proof.bytes[..proof.bytes.len() / 2].to_vec(),
+        };
+        assert_eq!(verify(&evals, &truncated), Err(PcsError::MalformedProof));
     }
 }
@@ -1,92 +1,306 @@
-use std::{collections::HashMap, marker::PhantomData};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    mem::size_of,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use arithmetic::{
-    field::{as_bytes_vec, FftField, Field},
+    field::{as_bytes_vec, from_bytes, plaintext_size},
     mul_group::Radix2Group,
     poly::MultiLinearPoly,
 };
+use rand::RngCore;
+use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
 use util::{
     fiat_shamir::Proof,
-    merkle_tree::{MerkleTreeProver, MerkleTreeVerifier, HASH_SIZE},
+    merkle_tree::{DiskMerkleTree, MerkleTreeProver, MerkleTreeVerifier, HASH_SIZE},
 };
 
-use crate::Transcript;
-
-use super::{CommitmentSerde, PolyCommitProver, PolyCommitVerifier};
+use crate::{
+    ensure_remaining,
+    iopp::{check_folding, fold_codeword, round_indices},
+    soundness::{DecodingRegime, SoundnessReport},
+    MerkleRoot, OpeningProof, PcsError, PolynomialCommitmentScheme, ProofSerde, Transcript,
+};
 
-#[derive(Debug, Clone, Default)]
-pub struct MerkleRoot([u8; HASH_SIZE]);
+use super::OpeningClaim;
 
-impl CommitmentSerde for MerkleRoot {
-    fn size(nv: usize, np: usize) -> usize {
-        HASH_SIZE
-    }
+type F = Plaintext;
 
-    fn serialize_into(&self, buffer: &mut [u8]) {
-        buffer.copy_from_slice(&self.0);
-    }
+// the choices behind a DeepFold instance; the query count follows from them
+#[derive(Debug, Clone, Copy)]
+pub struct DeepFoldConfig {
+    pub variable_num: usize,
+    pub log_blowup: usize,
+    pub security_bits: usize,
+    // the proximity regime the query count is computed for
+    pub regime: DecodingRegime,
+    // folding stops once this many variables are left and the rest is sent in the clear
+    pub final_poly_log_size: usize,
+    // log2 of the folding arity
+    pub folding_factor: usize,
+    // salted leaves and masked openings, see DeepFoldParam::new
+    pub hiding: bool,
+    // grinding before the queries, each bit of work saving query bits; 0 skips it
+    pub pow_bits: usize,
+}
 
-    fn deserialize_from(proof: &mut Proof, nv: usize, np: usize) -> Self {
-        let root = proof.get_next_hash();
-        Self(root)
+impl DeepFoldConfig {
+    // rate 1/2, 100 bits up to the johnson bound, binary folding down to a constant, no
+    // grinding
+    pub fn new(variable_num: usize) -> Self {
+        DeepFoldConfig {
+            variable_num,
+            log_blowup: 1,
+            security_bits: 100,
+            regime: DecodingRegime::Johnson,
+            final_poly_log_size: 0,
+            folding_factor: 1,
+            hiding: false,
+            pow_bits: 0,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct DeepFoldParam<F: FftField> {
-    pub mult_subgroups: Vec<Radix2Group<F::FftBaseField>>,
+pub struct DeepFoldParam<'a> {
+    pub mult_subgroups: Vec<Radix2Group>,
     pub variable_num: usize,
     pub query_num: usize,
+    pub pow_bits: usize,
+    // log2 of the folding arity, each round folds this many variables with one merkle tree
+    pub folding_factor: usize,
+    // folding stops once this many variables are left and the rest is sent in the clear
+    pub final_poly_log_size: usize,
+    pub hiding: bool,
+    pub ctx: &'a Context,
+    pub params: &'a EncryptionParameters,
+}
+
+impl<'a> DeepFoldParam<'a> {
+    // in hiding mode openings reveal nothing beyond the claimed evaluations: every polynomial
+    // gets a random top half under an extra variable that is opened at 0, so the fewer than
+    // 2^nv opened codeword values are uniform; each commitment also carries a random masking
+    // polynomial that is batched into the fold, and merkle leaves are salted.
+    // `config.variable_num` does not count the extra variable
+    pub fn new(
+        config: DeepFoldConfig,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+    ) -> (Self, SoundnessReport) {
+        let variable_num = config.variable_num + config.hiding as usize;
+        assert!(config.final_poly_log_size < variable_num);
+        let modulus = params.get_plain_modulus().value();
+        let mut mult_subgroups = vec![Radix2Group::new(
+            (variable_num + config.log_blowup) as u32,
+            modulus,
+        )];
+        for i in 1..variable_num {
+            mult_subgroups.push(mult_subgroups[i - 1].exp(2));
+        }
+        let pow_bits = config.pow_bits;
+        let query_num = config
+            .regime
+            .query_num(config.security_bits, config.log_blowup, pow_bits);
+        let report = SoundnessReport::new(
+            config.regime,
+            variable_num,
+            config.log_blowup,
+            modulus.ilog2() as usize,
+            config.security_bits,
+            query_num,
+            pow_bits,
+        );
+        let pp = DeepFoldParam {
+            mult_subgroups,
+            variable_num,
+            query_num,
+            pow_bits,
+            final_poly_log_size: config.final_poly_log_size,
+            folding_factor: config.folding_factor,
+            hiding: config.hiding,
+            ctx,
+            params,
+        };
+        (pp, report)
+    }
+
+    pub fn encoder(&self) -> BFVEncoder<'a> {
+        BFVEncoder::new(self.ctx, self.params).unwrap()
+    }
+
+    // length of the points the caller opens at
+    pub fn opened_var_num(&self) -> usize {
+        self.variable_num - self.hiding as usize
+    }
+
+    pub fn folded_var_num(&self) -> usize {
+        self.variable_num - self.final_poly_log_size
+    }
+
+    pub fn round_num(&self) -> usize {
+        (self.folded_var_num() + self.folding_factor - 1) / self.folding_factor
+    }
+
+    // only the last round may fold fewer than `folding_factor` variables
+    pub fn round_folding(&self, round: usize) -> usize {
+        self.folding_factor
+            .min(self.folded_var_num() - round * self.folding_factor)
+    }
+
+    // parameters for points of `nv` variables, reusing the tail of the subgroup chain
+    pub fn trim(&self, nv: usize) -> Self {
+        assert!(nv <= self.opened_var_num());
+        let variable_num = nv + self.hiding as usize;
+        DeepFoldParam {
+            mult_subgroups: self.mult_subgroups[self.variable_num - variable_num..].to_vec(),
+            variable_num,
+            query_num: self.query_num,
+            pow_bits: self.pow_bits,
+            folding_factor: self.folding_factor,
+            final_poly_log_size: self.final_poly_log_size.min(variable_num - 1),
+            hiding: self.hiding,
+            ctx: self.ctx,
+            params: self.params,
+        }
+    }
+}
+
+// claim c is weighted by r^(n - 1 - c), the same as folding the claims with horner in order
+fn claim_weights(r: &F, claim_num: usize, encoder: &BFVEncoder) -> Vec<F> {
+    let mut weights = vec![F::from_int(1, encoder); claim_num];
+    for i in (0..claim_num.saturating_sub(1)).rev() {
+        weights[i] = F::mult(&weights[i + 1], r, encoder);
+    }
+    weights
+}
+
+// claims on the polynomials extended with the hiding variable, followed by one claim on the
+// masking polynomial of each commitment
+fn hiding_claims(claims: &[OpeningClaim], poly_nums: &[usize]) -> Vec<OpeningClaim> {
+    let mut var_nums = vec![0; poly_nums.len()];
+    let mut res = claims
+        .iter()
+        .map(|x| {
+            var_nums[x.commit_index] = x.var_num + 1;
+            OpeningClaim {
+                var_num: x.var_num + 1,
+                ..*x
+            }
+        })
+        .collect::<Vec<_>>();
+    for (i, &poly_num) in poly_nums.iter().enumerate() {
+        res.push(OpeningClaim {
+            commit_index: i,
+            poly_index: poly_num,
+            var_num: var_nums[i],
+        });
+    }
+    res
+}
+
+// the variable at which each commitment joins the fold, every commitment needs a claim
+fn entry_rounds(
+    pp: &DeepFoldParam,
+    claims: &[OpeningClaim],
+    commit_num: usize,
+) -> Result<Vec<usize>, PcsError> {
+    let mut res = vec![None; commit_num];
+    for claim in claims {
+        if claim.commit_index >= commit_num
+            || claim.var_num <= pp.final_poly_log_size
+            || claim.var_num > pp.variable_num
+        {
+            return Err(PcsError::Claim);
+        }
+        let entry = pp.variable_num - claim.var_num;
+        if !entry.is_multiple_of(pp.folding_factor)
+            || res[claim.commit_index].unwrap_or(entry) != entry
+        {
+            return Err(PcsError::Claim);
+        }
+        res[claim.commit_index] = Some(entry);
+    }
+    res.into_iter().map(|x| x.ok_or(PcsError::Claim)).collect()
 }
 
 #[derive(Clone)]
-pub struct QueryResult<F: Field> {
+pub struct QueryResult {
     pub proof_bytes: Vec<u8>,
     pub proof_values: HashMap<usize, F>,
+    // one per queried leaf, empty for unsalted trees
+    pub salts: Vec<u8>,
 }
 
-impl<F: Field> QueryResult<F> {
+impl QueryResult {
+    // the opened leaves of `round` against its root
     pub fn verify_merkle_tree(
         &self,
+        round: usize,
         leaf_indices: &Vec<usize>,
         leaf_size: usize,
         merkle_verifier: &MerkleTreeVerifier,
-    ) -> bool {
+        encoder: &BFVEncoder,
+    ) -> Result<(), PcsError> {
         let len = merkle_verifier.leave_number;
-        let leaves: Vec<Vec<u8>> = leaf_indices
-            .iter()
-            .map(|i| {
-                as_bytes_vec(
-                    &(0..leaf_size)
-                        .map(|j| self.proof_values.get(&(i + j * len)).unwrap().clone())
-                        .collect::<Vec<_>>(),
-                )
-            })
-            .collect();
-        let res = merkle_verifier.verify(self.proof_bytes.clone(), leaf_indices, &leaves);
-        assert!(res);
-        res
+        let mut leaves = vec![];
+        for (idx, i) in leaf_indices.iter().enumerate() {
+            let values = (0..leaf_size)
+                .map(|j| self.proof_values.get(&(i + j * len)).cloned())
+                .collect::<Option<Vec<_>>>()
+                .ok_or(PcsError::MalformedProof)?;
+            let mut leaf = as_bytes_vec(&values, encoder);
+            if !self.salts.is_empty() {
+                leaf.extend_from_slice(&self.salts[idx * SALT_SIZE..(idx + 1) * SALT_SIZE]);
+            }
+            leaves.push(leaf);
+        }
+        if !merkle_verifier.verify(self.proof_bytes.clone(), leaf_indices, &leaves) {
+            return Err(PcsError::MerklePath { round });
+        }
+        Ok(())
     }
 }
 
+pub const SALT_SIZE: usize = 16;
+
 #[derive(Clone)]
-pub struct InterpolateValue<F: Field> {
+pub struct InterpolateValue {
     pub value: Vec<F>,
     leaf_size: usize,
     merkle_tree: MerkleTreeProver,
+    salts: Vec<[u8; SALT_SIZE]>,
 }
 
-impl<F: FftField> InterpolateValue<F> {
-    pub fn new(value: Vec<F>, leaf_size: usize) -> Self {
+impl InterpolateValue {
+    pub fn new(value: Vec<F>, leaf_size: usize, salted: bool, encoder: &BFVEncoder) -> Self {
         let len = value.len() / leaf_size;
+        let salts: Vec<[u8; SALT_SIZE]> = if salted {
+            (0..len).map(|_| rand::random()).collect()
+        } else {
+            vec![]
+        };
         let merkle_tree = MerkleTreeProver::new(
             (0..len)
                 .map(|i| {
-                    as_bytes_vec::<F>(
+                    let mut leaf = as_bytes_vec(
                         &(0..leaf_size)
-                            .map(|j| value[len * j + i])
+                            .map(|j| value[len * j + i].clone())
                             .collect::<Vec<_>>(),
-                    )
+                        encoder,
+                    );
+                    if salted {
+                        leaf.extend_from_slice(&salts[i]);
+                    }
+                    leaf
                 })
                 .collect(),
         );
@@ -94,6 +308,7 @@ impl<F: FftField> InterpolateValue<F> {
             value,
             leaf_size,
             merkle_tree,
+            salts,
         }
     }
 
@@ -105,6 +320,7 @@ impl<F: FftField> InterpolateValue<F> {
         self.merkle_tree.commit()
     }
 
+    // salts of the queried leaves follow the merkle proof
     pub fn query(&self, leaf_indices: &Vec<usize>) -> (Vec<u8>, Vec<F>) {
         let len = self.merkle_tree.leave_num();
         assert_eq!(len * self.leaf_size, self.value.len());
@@ -112,330 +328,1815 @@ impl<F: FftField> InterpolateValue<F> {
             .flat_map(|i| {
                 leaf_indices
                     .iter()
-                    .map(|j| self.value[j.clone() + i * len])
+                    .map(|j| self.value[j + i * len].clone())
                     .collect::<Vec<_>>()
             })
             .collect();
-        let proof_bytes = self.merkle_tree.open(&leaf_indices);
+        let mut proof_bytes = self.merkle_tree.open(&leaf_indices);
+        if !self.salts.is_empty() {
+            for &i in leaf_indices {
+                proof_bytes.extend_from_slice(&self.salts[i]);
+            }
+        }
         (proof_bytes, proof_values)
     }
 }
 
-#[derive(Clone)]
-pub struct DeepFoldProver<F: FftField> {
-    pub interpolation: InterpolateValue<F::FftBaseField>,
-    poly: Vec<Vec<F::BaseField>>,
+static CACHE_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn read_values(mut file: &File, offset: usize, len: usize, encoder: &BFVEncoder) -> Vec<F> {
+    let size = plaintext_size(encoder);
+    let mut buffer = vec![0u8; len * size];
+    file.seek(SeekFrom::Start((offset * size) as u64)).unwrap();
+    file.read_exact(&mut buffer).unwrap();
+    buffer.chunks(size).map(|x| from_bytes(x, encoder)).collect()
 }
 
-impl<F: FftField> DeepFoldProver<F> {
-    fn evaluate_next_domain(
-        last_interpolation: &Vec<F>,
-        pp: &DeepFoldParam<F>,
-        round: usize,
-        challenge: F,
-    ) -> Vec<F> {
-        let mut res = vec![];
-        let len = pp.mult_subgroups[round].size();
-        let subgroup = &pp.mult_subgroups[round];
-        for i in 0..(len / 2) {
-            let x = last_interpolation[i];
-            let nx = last_interpolation[i + len / 2];
-            let sum = x + nx;
-            let new_v = sum + challenge * ((x - nx) * F::from(subgroup.element_inv_at(i)) - sum);
-            res.push(new_v.mul_base_elem(<F as Field>::BaseField::inv_2()));
+// codewords and evaluations of a streamed commitment, stored one polynomial after another
+// and removed on drop
+struct DiskCache {
+    codewords: File,
+    evals: File,
+    paths: [PathBuf; 2],
+    poly_num: usize,
+    poly_len: usize,
+    codeword_len: usize,
+    arity: usize,
+    merkle_tree: DiskMerkleTree,
+}
+
+impl DiskCache {
+    // leaves hashed per chunk when building the tree
+    const CHUNK: usize = 1 << 8;
+
+    fn new<P: IntoIterator<Item = F>>(
+        pp: &DeepFoldParam,
+        polies: impl IntoIterator<Item = P>,
+        cache_dir: &Path,
+    ) -> Self {
+        let encoder = pp.encoder();
+        let id = format!(
+            "deepfold-{}-{}",
+            std::process::id(),
+            CACHE_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let paths = ["codewords", "evals"].map(|x| cache_dir.join(format!("{}.{}", id, x)));
+        let [codewords, evals] = paths.clone().map(|x| {
+            File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(x)
+                .unwrap()
+        });
+
+        let mut codeword_writer = BufWriter::new(&codewords);
+        let mut eval_writer = BufWriter::new(&evals);
+        let mut poly_num = 0;
+        let mut poly_len = 0;
+        for poly in polies {
+            let poly = poly.into_iter().collect::<Vec<_>>();
+            assert!(poly_num == 0 || poly.len() == poly_len);
+            poly_len = poly.len();
+            let start = pp.variable_num - poly_len.ilog2() as usize;
+            eval_writer
+                .write_all(&as_bytes_vec(&poly, &encoder))
+                .unwrap();
+            codeword_writer
+                .write_all(&as_bytes_vec(
+                    &pp.mult_subgroups[start].fft(poly, &encoder),
+                    &encoder,
+                ))
+                .unwrap();
+            poly_num += 1;
+        }
+        codeword_writer.flush().unwrap();
+        eval_writer.flush().unwrap();
+        drop(codeword_writer);
+        drop(eval_writer);
+
+        let start = pp.variable_num - poly_len.ilog2() as usize;
+        assert_eq!(start % pp.folding_factor, 0);
+        let codeword_len = pp.mult_subgroups[start].size();
+        let arity = 1 << pp.round_folding(start / pp.folding_factor);
+        let leaf_num = codeword_len / arity;
+        let chunk = Self::CHUNK.min(leaf_num);
+        // same leaf layout as InterpolateValue over the concatenated codewords
+        let encoder = &encoder;
+        let codeword_file = &codewords;
+        let leaves = (0..leaf_num).step_by(chunk).flat_map(|offset| {
+            let columns = (0..poly_num * arity)
+                .map(|j| {
+                    read_values(
+                        codeword_file,
+                        (j / arity) * codeword_len + (j % arity) * leaf_num + offset,
+                        chunk,
+                        encoder,
+                    )
+                })
+                .collect::<Vec<_>>();
+            (0..chunk).map(move |i| {
+                as_bytes_vec(
+                    &columns.iter().map(|x| x[i].clone()).collect::<Vec<_>>(),
+                    encoder,
+                )
+            })
+        });
+        let merkle_tree = DiskMerkleTree::new(cache_dir.join(format!("{}.merkle", id)), leaves);
+        DiskCache {
+            codewords,
+            evals,
+            paths,
+            poly_num,
+            poly_len,
+            codeword_len,
+            arity,
+            merkle_tree,
         }
-        res
+    }
+
+    fn query(&self, leaf_indices: &Vec<usize>, encoder: &BFVEncoder) -> (Vec<u8>, Vec<F>) {
+        let leaf_num = self.codeword_len / self.arity;
+        let proof_values = (0..self.poly_num * self.arity)
+            .flat_map(|j| {
+                leaf_indices.iter().map(move |i| {
+                    read_values(
+                        &self.codewords,
+                        (j / self.arity) * self.codeword_len + (j % self.arity) * leaf_num + i,
+                        1,
+                        encoder,
+                    )
+                    .swap_remove(0)
+                })
+            })
+            .collect();
+        (self.merkle_tree.open(leaf_indices), proof_values)
     }
 }
 
-impl<F: FftField> PolyCommitProver<F> for DeepFoldProver<F> {
-    type Param = DeepFoldParam<F>;
-    type Commitment = MerkleRoot;
+impl Drop for DiskCache {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
 
-    fn new(pp: &Self::Param, poly: &[Vec<F::BaseField>]) -> Self {
+#[derive(Clone)]
+enum Storage {
+    Memory {
+        interpolation: InterpolateValue,
+        poly: Vec<Vec<F>>,
+    },
+    Disk(Arc<DiskCache>),
+}
+
+#[derive(Clone)]
+pub struct DeepFoldProver<'a> {
+    storage: Storage,
+    _data: PhantomData<&'a Context>,
+}
+
+impl<'a> DeepFoldProver<'a> {
+    // commits polynomials streamed one at a time, so a single codeword is in memory at once.
+    // codewords, evaluations and merkle layers go to files under `cache_dir`, and the opening
+    // pages them back in per polynomial while folding one combined codeword
+    pub fn new_streaming<P: IntoIterator<Item = F>>(
+        pp: &DeepFoldParam<'a>,
+        polies: impl IntoIterator<Item = P>,
+        cache_dir: &Path,
+    ) -> Self {
+        assert!(!pp.hiding);
+        DeepFoldProver {
+            storage: Storage::Disk(Arc::new(DiskCache::new(pp, polies, cache_dir))),
+            _data: PhantomData,
+        }
+    }
+
+    // PolynomialCommitmentScheme::commit for polynomials streamed through `cache_dir`, the
+    // prover data opens and verifies like an in-memory one
+    pub fn commit_streaming<P: IntoIterator<Item = F>>(
+        pp: &DeepFoldParam<'a>,
+        polies: impl IntoIterator<Item = P>,
+        cache_dir: &Path,
+    ) -> (MerkleRoot, Self) {
+        let prover = Self::new_streaming(pp, polies, cache_dir);
+        (prover.root(), prover)
+    }
+
+    fn poly_num(&self) -> usize {
+        match &self.storage {
+            Storage::Memory { poly, .. } => poly.len(),
+            Storage::Disk(cache) => cache.poly_num,
+        }
+    }
+
+    fn poly(&self, index: usize, encoder: &BFVEncoder) -> Cow<'_, [F]> {
+        match &self.storage {
+            Storage::Memory { poly, .. } => Cow::Borrowed(&poly[index]),
+            Storage::Disk(cache) => Cow::Owned(read_values(
+                &cache.evals,
+                index * cache.poly_len,
+                cache.poly_len,
+                encoder,
+            )),
+        }
+    }
+
+    fn codeword(&self, index: usize, encoder: &BFVEncoder) -> Cow<'_, [F]> {
+        match &self.storage {
+            Storage::Memory {
+                interpolation,
+                poly,
+            } => {
+                let len = interpolation.value.len() / poly.len();
+                Cow::Borrowed(&interpolation.value[index * len..(index + 1) * len])
+            }
+            Storage::Disk(cache) => Cow::Owned(read_values(
+                &cache.codewords,
+                index * cache.codeword_len,
+                cache.codeword_len,
+                encoder,
+            )),
+        }
+    }
+
+    fn query(&self, leaf_indices: &Vec<usize>, encoder: &BFVEncoder) -> (Vec<u8>, Vec<F>) {
+        match &self.storage {
+            Storage::Memory { interpolation, .. } => interpolation.query(leaf_indices),
+            Storage::Disk(cache) => cache.query(leaf_indices, encoder),
+        }
+    }
+}
+
+impl<'a> DeepFoldProver<'a> {
+    pub fn new(pp: &DeepFoldParam<'a>, poly: &[Vec<F>]) -> Self {
+        let encoder = pp.encoder();
+        let poly = if pp.hiding {
+            let len = poly[0].len() * 2;
+            // random top halves, then the masking polynomial which is random throughout
+            let mut poly = poly.to_vec();
+            poly.push(vec![]);
+            for x in poly.iter_mut() {
+                x.extend((x.len()..len).map(|_| F::random_pt(&encoder)));
+            }
+            poly
+        } else {
+            poly.to_vec()
+        };
+        // smaller polynomials are encoded on the domain of the round they join the fold at
+        let start = pp.variable_num - poly[0].len().ilog2() as usize;
+        assert_eq!(start % pp.folding_factor, 0);
         let values = poly
             .iter()
-            .flat_map(|x| pp.mult_subgroups[0].fft(x.clone()))
+            .flat_map(|x| pp.mult_subgroups[start].fft(x.clone(), &encoder))
             .collect::<Vec<_>>();
         DeepFoldProver {
-            interpolation: InterpolateValue::new(values, 2 * poly.len()),
-            poly: poly.iter().map(|x| x.clone()).collect(),
+            storage: Storage::Memory {
+                interpolation: InterpolateValue::new(
+                    values,
+                    (1 << pp.round_folding(start / pp.folding_factor)) * poly.len(),
+                    pp.hiding,
+                    &encoder,
+                ),
+                poly,
+            },
+            _data: PhantomData,
         }
     }
 
-    fn commit(&self) -> Self::Commitment {
-        MerkleRoot(self.interpolation.commit())
+    pub fn root(&self) -> MerkleRoot {
+        match &self.storage {
+            Storage::Memory { interpolation, .. } => MerkleRoot(interpolation.commit()),
+            Storage::Disk(cache) => MerkleRoot(cache.merkle_tree.commit()),
+        }
     }
 
-    fn open(pp: &Self::Param, provers: Vec<&Self>, point: Vec<F>, transcript: &mut Transcript) {
-        let mut interpolations: Vec<InterpolateValue<F>> = vec![];
-        let r: F = transcript.challenge_f();
-        let mut poly_evals = provers[0].poly[0]
-            .iter()
-            .map(|x| F::from(*x))
-            .collect::<Vec<_>>();
-        for i in 0..provers.len() {
-            let start = if i == 0 { 1 } else { 0 };
-            for j in start..provers[i].poly.len() {
-                for k in 0..poly_evals.len() {
-                    poly_evals[k] *= r;
-                    poly_evals[k].add_assign_base_elem(provers[i].poly[j][k]);
-                }
+    // the messages of PolynomialCommitmentScheme::batch_open, written to the transcript
+    fn prove(
+        pp: &DeepFoldParam<'a>,
+        provers: &[&Self],
+        claims: &[OpeningClaim],
+        point: Vec<F>,
+        transcript: &mut Transcript,
+    ) {
+        assert_eq!(point.len(), pp.opened_var_num());
+        let encoder = pp.encoder();
+        let zero = F::from_int(0, &encoder);
+        let one = F::from_int(1, &encoder);
+        let (claims, point) = if pp.hiding {
+            let poly_nums = provers.iter().map(|x| x.poly_num() - 1).collect::<Vec<_>>();
+            let claims = hiding_claims(claims, &poly_nums);
+            let mut point = point;
+            point.push(zero.clone());
+            for claim in &claims[claims.len() - provers.len()..] {
+                transcript.append_f(
+                    &MultiLinearPoly::eval_multilinear(
+                        &provers[claim.commit_index]
+                            .poly(claim.poly_index, &encoder)
+                            .to_vec(),
+                        &point[pp.variable_num - claim.var_num..],
+                        &encoder,
+                    ),
+                    &encoder,
+                );
             }
-        }
-        let len = pp.mult_subgroups[0].size();
-        let mut poly_interpolations = (0..len).map(|_| F::zero()).collect::<Vec<_>>();
-        for i in 0..provers.len() {
-            for j in 0..len {
-                for k in 0..provers[i].poly.len() {
-                    poly_interpolations[j] *= r;
-                    poly_interpolations[j] += F::from(provers[i].interpolation.value[j + len * k]);
+            (claims, point)
+        } else {
+            (claims.to_vec(), point)
+        };
+        let mut interpolations: Vec<InterpolateValue> = vec![];
+        let r = transcript.challenge_f(&encoder);
+        let weights = claim_weights(&r, claims.len(), &encoder);
+        let entry_rounds = entry_rounds(pp, &claims, provers.len()).unwrap();
+        let mut poly_evals = vec![zero.clone(); 1 << pp.variable_num];
+        let mut poly_interpolations = vec![zero.clone(); pp.mult_subgroups[0].size()];
+        let folded_var_num = pp.folded_var_num();
+        for i in 0..folded_var_num {
+            for (claim, weight) in claims.iter().zip(weights.iter()) {
+                if entry_rounds[claim.commit_index] != i {
+                    continue;
+                }
+                let prover = provers[claim.commit_index];
+                for (k, x) in prover.poly(claim.poly_index, &encoder).iter().enumerate() {
+                    poly_evals[k] = F::add(&poly_evals[k], &F::mult(weight, x, &encoder), &encoder);
+                }
+                for (k, x) in prover
+                    .codeword(claim.poly_index, &encoder)
+                    .iter()
+                    .enumerate()
+                {
+                    poly_interpolations[k] = F::add(
+                        &poly_interpolations[k],
+                        &F::mult(weight, x, &encoder),
+                        &encoder,
+                    );
                 }
             }
-        }
-        for i in 0..pp.variable_num {
             let mut new_point = point[i..].to_vec();
-            new_point[0].add_assign_base_elem(F::BaseField::one());
-            transcript.append_f(MultiLinearPoly::eval_multilinear_ext(
-                &poly_evals,
-                &new_point,
-            ));
-            let challenge: F = transcript.challenge_f();
-            let new_len = poly_evals.len() / 2;
-            for j in 0..new_len {
-                poly_evals[j] =
-                    poly_evals[j * 2] + (poly_evals[j * 2 + 1] - poly_evals[j * 2]) * challenge;
-            }
-            poly_evals.truncate(new_len);
-            let next_evaluation = Self::evaluate_next_domain(
-                if i == 0 {
-                    &poly_interpolations
-                } else {
-                    &interpolations[i - 1].value
-                },
-                pp,
-                i,
-                challenge,
+            new_point[0] = F::add(&new_point[0], &one, &encoder);
+            transcript.append_f(
+                &MultiLinearPoly::eval_multilinear_ext(&poly_evals, &new_point, &encoder),
+                &encoder,
             );
-            if i < pp.variable_num - 1 {
-                let new_interpolation = InterpolateValue::new(next_evaluation, 2);
+            let challenge = transcript.challenge_f(&encoder);
+            fold_lowest(&mut poly_evals, &challenge, &encoder);
+            poly_interpolations = fold_codeword(
+                &poly_interpolations,
+                &pp.mult_subgroups[i],
+                &challenge,
+                &encoder,
+            );
+            if i == folded_var_num - 1 {
+                for j in poly_evals.iter() {
+                    transcript.append_f(j, &encoder);
+                }
+            } else if (i + 1) % pp.folding_factor == 0 {
+                let new_interpolation = InterpolateValue::new(
+                    poly_interpolations.clone(),
+                    1 << pp.round_folding((i + 1) / pp.folding_factor),
+                    pp.hiding,
+                    &encoder,
+                );
                 transcript.append_u8_slice(&new_interpolation.commit(), HASH_SIZE);
                 interpolations.push(new_interpolation);
-            } else {
-                transcript.append_f(next_evaluation[0]);
             }
         }
-        let mut leaf_indices = transcript.challenge_usizes(pp.query_num);
-        for i in 0..pp.variable_num {
-            let len = pp.mult_subgroups[i].size();
-            leaf_indices = leaf_indices.iter_mut().map(|v| *v % (len >> 1)).collect();
-            leaf_indices.sort();
-            leaf_indices.dedup();
-            if i == 0 {
-                let query = provers
-                    .iter()
-                    .map(|j| j.interpolation.query(&leaf_indices))
-                    .collect::<Vec<_>>();
-                for q in query {
-                    transcript.append_u8_slice(&q.0, q.0.len());
-                    for j in q.1 {
-                        transcript.append_f(j);
-                    }
-                }
-            } else {
+        transcript.grind(pp.pow_bits);
+        let indices = transcript.challenge_usizes(pp.query_num);
+        for i in 0..pp.round_num() {
+            let start = i * pp.folding_factor;
+            let leaf_indices = round_indices(pp, &indices, i);
+            if i > 0 {
                 let query = interpolations[i - 1].query(&leaf_indices);
                 transcript.append_u8_slice(&query.0, query.0.len());
-                for j in query.1 {
-                    transcript.append_f(j);
+                for j in query.1.iter() {
+                    transcript.append_f(j, &encoder);
+                }
+            }
+            for (j, prover) in provers.iter().enumerate() {
+                if entry_rounds[j] == start {
+                    let query = prover.query(&leaf_indices, &encoder);
+                    transcript.append_u8_slice(&query.0, query.0.len());
+                    for k in query.1.iter() {
+                        transcript.append_f(k, &encoder);
+                    }
                 }
             }
         }
     }
 }
 
+// DeepFold in hiding mode as a scheme of its own, for callers that require a hiding scheme
+// through PolynomialCommitmentScheme::HIDING. it only accepts parameters built with `hiding`
+// set and verifies like plain DeepFold
 #[derive(Clone)]
-pub struct DeepFoldVerifier<F: FftField> {
+pub struct HidingDeepFoldProver<'a>(DeepFoldProver<'a>);
+
+impl<'a> PolynomialCommitmentScheme<'a> for HidingDeepFoldProver<'a> {
+    const HIDING: bool = true;
+
+    type Param = DeepFoldParam<'a>;
+    type Commitment = MerkleRoot;
+    type ProverData = Self;
+    type Proof = OpeningProof;
+
+    fn setup(
+        max_nv: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+        _rng: &mut impl RngCore,
+    ) -> Self::Param {
+        let config = DeepFoldConfig {
+            hiding: true,
+            ..DeepFoldConfig::new(max_nv)
+        };
+        DeepFoldParam::new(config, ctx, params).0
+    }
+
+    fn trim(pp: &Self::Param, nv: usize) -> Self::Param {
+        pp.trim(nv)
+    }
+
+    fn commit(pp: &Self::Param, polies: &[Vec<F>]) -> (Self::Commitment, Self) {
+        assert!(pp.hiding);
+        let prover = DeepFoldProver::new(pp, polies);
+        (prover.root(), HidingDeepFoldProver(prover))
+    }
+
+    fn open(
+        pp: &Self::Param,
+        data: &[&Self],
+        point: &[F],
+        transcript: &mut Transcript,
+    ) -> Self::Proof {
+        let provers = data.iter().map(|x| &x.0).collect::<Vec<_>>();
+        DeepFoldProver::open(pp, &provers, point, transcript)
+    }
+
+    fn batch_open(
+        pp: &Self::Param,
+        data: &[&Self],
+        claims: &[OpeningClaim],
+        point: &[F],
+        transcript: &mut Transcript,
+    ) -> Self::Proof {
+        let provers = data.iter().map(|x| &x.0).collect::<Vec<_>>();
+        DeepFoldProver::batch_open(pp, &provers, claims, point, transcript)
+    }
+
+    fn verify(
+        pp: &Self::Param,
+        commits: &[&Self::Commitment],
+        point: &[F],
+        evals: &[Vec<F>],
+        proof: &Self::Proof,
+        transcript: &mut Transcript,
+    ) -> Result<(), PcsError> {
+        DeepFoldProver::verify(pp, commits, point, evals, proof, transcript)
+    }
+
+    fn batch_verify(
+        pp: &Self::Param,
+        commits: &[&Self::Commitment],
+        poly_nums: &[usize],
+        claims: &[OpeningClaim],
+        point: &[F],
+        evals: &[F],
+        proof: &Self::Proof,
+        transcript: &mut Transcript,
+    ) -> Result<(), PcsError> {
+        DeepFoldProver::batch_verify(
+            pp, commits, poly_nums, claims, point, evals, proof, transcript,
+        )
+    }
+
+    fn proof_size(pp: &Self::Param, nv: usize, poly_num: usize) -> usize {
+        DeepFoldProver::proof_size(pp, nv, poly_num)
+    }
+}
+
+#[derive(Clone)]
+pub struct DeepFoldVerifier<'a> {
     commit: MerkleTreeVerifier,
     poly_num: usize,
-    _data: PhantomData<F>,
+    _data: PhantomData<&'a Context>,
 }
 
-impl<F: FftField> PolyCommitVerifier<F> for DeepFoldVerifier<F> {
-    type Param = DeepFoldParam<F>;
-    type Commitment = MerkleRoot;
-
-    fn new(pp: &Self::Param, commit: Self::Commitment, poly_num: usize) -> Self {
+impl<'a> DeepFoldVerifier<'a> {
+    pub fn new(pp: &DeepFoldParam<'a>, commit: MerkleRoot, poly_num: usize) -> Self {
         DeepFoldVerifier {
-            commit: MerkleTreeVerifier::new(pp.mult_subgroups[0].size() / 2, commit.0),
-            poly_num,
-            _data: PhantomData::default(),
+            commit: MerkleTreeVerifier::new(
+                pp.mult_subgroups[0].size() >> pp.round_folding(0),
+                commit.0,
+            ),
+            // the masking polynomial is committed alongside
+            poly_num: poly_num + pp.hiding as usize,
+            _data: PhantomData,
         }
     }
 
-    fn verify(
-        pp: &Self::Param,
-        verifiers: Vec<&Self>,
+    // batch_verify on the messages read from `proof`; a short proof is rejected, not a panic
+    pub fn check_batch(
+        pp: &DeepFoldParam<'a>,
+        verifiers: &[&Self],
+        claims: &[OpeningClaim],
         point: Vec<F>,
-        evals: Vec<Vec<F>>,
+        evals: Vec<F>,
         transcript: &mut Transcript,
         proof: &mut Proof,
-    ) -> bool {
-        let r = transcript.challenge_f();
-        let mut eval = F::zero();
-        for i in evals {
-            for j in i {
-                eval *= r;
-                eval += j;
-            }
+    ) -> Result<(), PcsError> {
+        if point.len() != pp.opened_var_num()
+            || claims.len() != evals.len()
+            || claims.iter().any(|x| {
+                x.commit_index >= verifiers.len()
+                    || x.poly_index + pp.hiding as usize >= verifiers[x.commit_index].poly_num
+            })
+        {
+            return Err(PcsError::Claim);
         }
+        let encoder = pp.encoder();
+        let f_size = plaintext_size(&encoder);
+        let zero = F::from_int(0, &encoder);
+        let (claims, point, evals) = if pp.hiding {
+            let poly_nums = verifiers.iter().map(|x| x.poly_num - 1).collect::<Vec<_>>();
+            let claims = hiding_claims(claims, &poly_nums);
+            let mut point = point;
+            point.push(zero.clone());
+            let mut evals = evals;
+            ensure_remaining(proof, f_size * verifiers.len())?;
+            for _ in 0..verifiers.len() {
+                let mask_eval = proof.get_next_and_step(&encoder);
+                transcript.append_f(&mask_eval, &encoder);
+                evals.push(mask_eval);
+            }
+            (claims, point, evals)
+        } else {
+            (claims.to_vec(), point, evals)
+        };
+        let r = transcript.challenge_f(&encoder);
+        let weights = claim_weights(&r, claims.len(), &encoder);
+        let entry_rounds = entry_rounds(pp, &claims, verifiers.len())?;
+        let mut eval = zero.clone();
         let mut challenges = vec![];
         let mut commits = vec![];
-        for i in 0..point.len() {
-            let next_eval = proof.get_next_and_step::<F>();
-            transcript.append_f(next_eval);
-            let challenge = transcript.challenge_f::<F>();
+        let mut final_poly = vec![];
+        let folded_var_num = pp.folded_var_num();
+        for i in 0..folded_var_num {
+            for (c, claim) in claims.iter().enumerate() {
+                if entry_rounds[claim.commit_index] == i {
+                    eval = F::add(&eval, &F::mult(&weights[c], &evals[c], &encoder), &encoder);
+                }
+            }
+            ensure_remaining(proof, f_size)?;
+            let next_eval = proof.get_next_and_step(&encoder);
+            transcript.append_f(&next_eval, &encoder);
+            let challenge = transcript.challenge_f(&encoder);
 
-            eval += (challenge - point[i]) * (next_eval - eval);
+            let step = F::sub(&challenge, &point[i], &encoder);
+            let diff = F::sub(&next_eval, &eval, &encoder);
+            eval = F::add(&eval, &F::mult(&step, &diff, &encoder), &encoder);
             challenges.push(challenge);
-            if i < pp.variable_num - 1 {
+            if i == folded_var_num - 1 {
+                ensure_remaining(proof, f_size << pp.final_poly_log_size)?;
+                final_poly = (0..(1 << pp.final_poly_log_size))
+                    .map(|_| proof.get_next_and_step(&encoder))
+                    .collect::<Vec<_>>();
+                for j in final_poly.iter() {
+                    transcript.append_f(j, &encoder);
+                }
+                if MultiLinearPoly::eval_multilinear_ext(&final_poly, &point[i + 1..], &encoder)
+                    != eval
+                {
+                    return Err(PcsError::Evaluation);
+                }
+            } else if (i + 1) % pp.folding_factor == 0 {
+                ensure_remaining(proof, HASH_SIZE)?;
                 let merkle_root = proof.get_next_hash();
                 transcript.append_u8_slice(&merkle_root, HASH_SIZE);
                 commits.push(MerkleTreeVerifier::new(
-                    pp.mult_subgroups[i + 1].size() / 2,
+                    pp.mult_subgroups[i + 1].size()
+                        >> pp.round_folding((i + 1) / pp.folding_factor),
                     merkle_root,
                 ));
-            } else {
-                let final_value = proof.get_next_and_step::<F>();
-                transcript.append_f(final_value);
-                if final_value != eval {
-                    return false;
-                }
             }
         }
 
-        let mut leaf_indices = transcript.challenge_usizes(pp.query_num);
-        let mut indices = leaf_indices.clone();
+        if pp.pow_bits > 0 {
+            ensure_remaining(proof, size_of::<u64>())?;
+        }
+        if !transcript.verify_grind(proof, pp.pow_bits) {
+            return Err(PcsError::ProofOfWork);
+        }
+        let indices = transcript.challenge_usizes(pp.query_num);
+        // folded values as committed in each round, and with the joining polynomials added
+        let mut committed_values = vec![];
         let mut query_results = vec![];
-        for i in 0..pp.variable_num {
-            let len = pp.mult_subgroups[i].size();
-            leaf_indices = leaf_indices.iter_mut().map(|v| *v % (len >> 1)).collect();
-            leaf_indices.sort();
-            leaf_indices.dedup();
-
-            if i == 0 {
-                let mut poly_values = vec![];
-                for j in 0..verifiers.len() {
-                    let proof_bytes =
-                        proof.get_next_slice(verifiers[j].commit.proof_length(&leaf_indices));
-                    let proof_values = (0..leaf_indices.len() * 2 * verifiers[j].poly_num)
-                        .map(|_| proof.get_next_and_step::<F::FftBaseField>())
-                        .collect::<Vec<_>>();
-                    transcript.append_u8_slice(&proof_bytes, proof_bytes.len());
-                    for k in &proof_values {
-                        transcript.append_f(*k);
-                    }
-                    poly_values.append(
-                        &mut (0..verifiers[j].poly_num)
-                            .map(|k| {
-                                (&proof_values
-                                    [k * leaf_indices.len() * 2..(k + 1) * leaf_indices.len() * 2])
-                                    .to_vec()
-                            })
-                            .collect::<Vec<_>>(),
-                    );
-                    let query = QueryResult {
-                        proof_bytes,
-                        proof_values: proof_values
-                            .into_iter()
-                            .enumerate()
-                            .map(|(idx, x)| {
-                                (
-                                    leaf_indices[idx % leaf_indices.len()]
-                                        + (len / 2) * (idx / leaf_indices.len()),
-                                    x,
-                                )
-                            })
-                            .collect(),
-                    };
-                    assert!(query.verify_merkle_tree(
-                        &leaf_indices,
-                        2 * verifiers[j].poly_num,
-                        &verifiers[j].commit
-                    ));
-                }
-                let poly_values = (0..leaf_indices.len() * 2)
-                    .into_iter()
-                    .map(|j| {
-                        let mut x = F::zero();
-                        for k in 0..poly_values.len() {
-                            x *= r;
-                            x += F::from(poly_values[k][j]);
-                        }
-                        x
-                    })
-                    .collect::<Vec<_>>();
+        for i in 0..pp.round_num() {
+            let start = i * pp.folding_factor;
+            let arity = 1 << pp.round_folding(i);
+            let leaf_num = pp.mult_subgroups[start].size() / arity;
+            let leaf_indices = round_indices(pp, &indices, i);
+            let query_num = leaf_indices.len();
+            let keys = (0..arity)
+                .flat_map(|m| leaf_indices.iter().map(move |&x| x + m * leaf_num))
+                .collect::<Vec<_>>();
 
-                query_results.push(QueryResult {
-                    proof_bytes: vec![],
-                    proof_values: leaf_indices
-                        .iter()
-                        .map(|&x| x)
-                        .chain(leaf_indices.iter().map(|&x| x + len / 2))
-                        .zip(poly_values)
-                        .collect(),
-                })
-            } else {
-                let proof_bytes = proof.get_next_slice(commits[i - 1].proof_length(&leaf_indices));
-                let proof_values = (0..leaf_indices.len() * 2)
-                    .map(|_| proof.get_next_and_step::<F>())
+            let salt_size = if pp.hiding { SALT_SIZE * query_num } else { 0 };
+            let mut values: HashMap<usize, F> = keys.iter().map(|&k| (k, zero.clone())).collect();
+            if i > 0 {
+                let proof_length = commits[i - 1].proof_length(&leaf_indices);
+                ensure_remaining(
+                    proof,
+                    proof_length + salt_size + f_size * query_num * arity,
+                )?;
+                let proof_bytes = proof.get_next_slice(proof_length);
+                let salts = proof.get_next_slice(salt_size);
+                let proof_values = (0..query_num * arity)
+                    .map(|_| proof.get_next_and_step(&encoder))
                     .collect::<Vec<_>>();
                 transcript.append_u8_slice(&proof_bytes, proof_bytes.len());
+                transcript.append_u8_slice(&salts, salt_size);
                 for j in &proof_values {
-                    transcript.append_f(*j);
+                    transcript.append_f(j, &encoder);
+                }
+                let query = QueryResult {
+                    proof_bytes,
+                    proof_values: keys.iter().map(|&k| k).zip(proof_values).collect(),
+                    salts,
+                };
+                query.verify_merkle_tree(i, &leaf_indices, arity, &commits[i - 1], &encoder)?;
+                for (k, v) in query.proof_values.iter() {
+                    let value = values.get_mut(k).unwrap();
+                    *value = F::add(value, v, &encoder);
+                }
+                committed_values.push(query.proof_values);
+            }
+            for j in 0..verifiers.len() {
+                if entry_rounds[j] != start {
+                    continue;
+                }
+                let commit = MerkleTreeVerifier::new(leaf_num, verifiers[j].commit.merkle_root);
+                let proof_length = commit.proof_length(&leaf_indices);
+                let value_num = query_num * arity * verifiers[j].poly_num;
+                ensure_remaining(proof, proof_length + salt_size + f_size * value_num)?;
+                let proof_bytes = proof.get_next_slice(proof_length);
+                let salts = proof.get_next_slice(salt_size);
+                let proof_values = (0..value_num)
+                    .map(|_| proof.get_next_and_step(&encoder))
+                    .collect::<Vec<_>>();
+                transcript.append_u8_slice(&proof_bytes, proof_bytes.len());
+                transcript.append_u8_slice(&salts, salt_size);
+                for k in &proof_values {
+                    transcript.append_f(k, &encoder);
                 }
                 let query = QueryResult {
                     proof_bytes,
-                    proof_values: leaf_indices
+                    proof_values: proof_values
                         .iter()
-                        .map(|&x| x)
-                        .chain(leaf_indices.iter().map(|x| x + len / 2))
-                        .zip(proof_values.into_iter())
+                        .enumerate()
+                        .map(|(idx, x)| {
+                            (
+                                leaf_indices[idx % query_num] + leaf_num * (idx / query_num),
+                                x.clone(),
+                            )
+                        })
                         .collect(),
+                    salts,
                 };
-                query.verify_merkle_tree(&leaf_indices, 2, &commits[i - 1]);
-                query_results.push(query);
-            }
-        }
-        drop(leaf_indices);
-        for i in 0..pp.variable_num {
-            let len = pp.mult_subgroups[i].size();
-            indices = indices.iter_mut().map(|v| *v % (len >> 1)).collect();
-            indices.sort();
-            indices.dedup();
-
-            for j in indices.iter() {
-                let x = query_results[i].proof_values.get(&j).unwrap().clone();
-                let nx = query_results[i]
-                    .proof_values
-                    .get(&(j + len / 2))
-                    .unwrap()
-                    .clone();
-                let sum = x + nx;
-                let new_v = sum
-                    + challenges[i]
-                        * ((x - nx) * F::from(pp.mult_subgroups[i].element_inv_at(*j)) - sum);
-                if i < pp.variable_num - 1 {
-                    if new_v != query_results[i + 1].proof_values[j].double() {
-                        println!("{} {}", file!(), line!());
-                        return false;
+                query.verify_merkle_tree(
+                    i,
+                    &leaf_indices,
+                    arity * verifiers[j].poly_num,
+                    &commit,
+                    &encoder,
+                )?;
+                for (c, claim) in claims.iter().enumerate() {
+                    if claim.commit_index != j {
+                        continue;
                     }
-                } else {
-                    if new_v.mul_base_elem(<F as Field>::BaseField::inv_2()) != eval {
-                        return false;
+                    for (idx, k) in keys.iter().enumerate() {
+                        let value = values.get_mut(k).unwrap();
+                        let x = &proof_values[claim.poly_index * arity * query_num + idx];
+                        *value = F::add(value, &F::mult(&weights[c], x, &encoder), &encoder);
                     }
                 }
             }
+            query_results.push(values);
         }
-        true
+        check_folding(
+            pp,
+            &indices,
+            &challenges,
+            &query_results,
+            &committed_values,
+            &final_poly,
+            &encoder,
+        )
+    }
+}
+
+impl<'a> PolynomialCommitmentScheme<'a> for DeepFoldProver<'a> {
+    type Param = DeepFoldParam<'a>;
+    type Commitment = MerkleRoot;
+    type ProverData = Self;
+    type Proof = OpeningProof;
+
+    // transparent, the rng is not needed
+    fn setup(
+        max_nv: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+        _rng: &mut impl RngCore,
+    ) -> Self::Param {
+        DeepFoldParam::new(DeepFoldConfig::new(max_nv), ctx, params).0
+    }
+
+    fn trim(pp: &Self::Param, nv: usize) -> Self::Param {
+        pp.trim(nv)
+    }
+
+    fn commit(pp: &Self::Param, polies: &[Vec<F>]) -> (Self::Commitment, Self) {
+        let prover = Self::new(pp, polies);
+        (prover.root(), prover)
+    }
+
+    fn open(
+        pp: &Self::Param,
+        provers: &[&Self],
+        point: &[F],
+        transcript: &mut Transcript,
+    ) -> Self::Proof {
+        let claims = provers
+            .iter()
+            .enumerate()
+            .flat_map(|(i, prover)| {
+                (0..prover.poly_num() - pp.hiding as usize).map(move |j| OpeningClaim {
+                    commit_index: i,
+                    poly_index: j,
+                    var_num: pp.opened_var_num(),
+                })
+            })
+            .collect::<Vec<_>>();
+        Self::batch_open(pp, provers, &claims, point, transcript)
+    }
+
+    fn batch_open(
+        pp: &Self::Param,
+        provers: &[&Self],
+        claims: &[OpeningClaim],
+        point: &[F],
+        transcript: &mut Transcript,
+    ) -> Self::Proof {
+        let start = transcript.proof.bytes.len();
+        Self::prove(pp, provers, claims, point.to_vec(), transcript);
+        OpeningProof {
+            bytes: transcript.proof.bytes[start..].to_vec(),
+        }
+    }
+
+    fn verify(
+        pp: &Self::Param,
+        commits: &[&Self::Commitment],
+        point: &[F],
+        evals: &[Vec<F>],
+        proof: &Self::Proof,
+        transcript: &mut Transcript,
+    ) -> Result<(), PcsError> {
+        let poly_nums = evals.iter().map(|x| x.len()).collect::<Vec<_>>();
+        let claims = evals
+            .iter()
+            .enumerate()
+            .flat_map(|(i, evals)| {
+                (0..evals.len()).map(move |j| OpeningClaim {
+                    commit_index: i,
+                    poly_index: j,
+                    var_num: point.len(),
+                })
+            })
+            .collect::<Vec<_>>();
+        Self::batch_verify(
+            pp,
+            commits,
+            &poly_nums,
+            &claims,
+            point,
+            &evals.concat(),
+            proof,
+            transcript,
+        )
+    }
+
+    fn batch_verify(
+        pp: &Self::Param,
+        commits: &[&Self::Commitment],
+        poly_nums: &[usize],
+        claims: &[OpeningClaim],
+        point: &[F],
+        evals: &[F],
+        proof: &Self::Proof,
+        transcript: &mut Transcript,
+    ) -> Result<(), PcsError> {
+        if commits.len() != poly_nums.len() {
+            return Err(PcsError::Claim);
+        }
+        let verifiers = commits
+            .iter()
+            .zip(poly_nums)
+            .map(|(commit, &poly_num)| DeepFoldVerifier::new(pp, (*commit).clone(), poly_num))
+            .collect::<Vec<_>>();
+        let mut reader = proof.reader();
+        DeepFoldVerifier::check_batch(
+            pp,
+            &verifiers.iter().collect::<Vec<_>>(),
+            claims,
+            point.to_vec(),
+            evals.to_vec(),
+            transcript,
+            &mut reader,
+        )?;
+        if reader.remaining() > 0 {
+            return Err(PcsError::MalformedProof);
+        }
+        Ok(())
+    }
+
+    // one commitment of `poly_num` polynomials, counting every query as its own merkle path
+    fn proof_size(pp: &Self::Param, nv: usize, poly_num: usize) -> usize {
+        let pp = pp.trim(nv);
+        let f_size = plaintext_size(&pp.encoder());
+        let poly_num = poly_num + pp.hiding as usize;
+        let salt_size = if pp.hiding { SALT_SIZE } else { 0 };
+        let mut size = f_size * (pp.hiding as usize + pp.folded_var_num())
+            + HASH_SIZE * (pp.round_num() - 1)
+            + (f_size << pp.final_poly_log_size)
+            + if pp.pow_bits > 0 { size_of::<u64>() } else { 0 };
+        for i in 0..pp.round_num() {
+            let arity = 1 << pp.round_folding(i);
+            let leaf_num = pp.mult_subgroups[i * pp.folding_factor].size() / arity;
+            let path_size = HASH_SIZE * leaf_num.ilog2() as usize;
+            // the commitment is opened in the first round, the folded codewords after it
+            let value_num = if i == 0 { poly_num } else { 1 };
+            size += pp.query_num * (f_size * value_num * arity + salt_size + path_size);
+        }
+        OpeningProof::default().size() + size
+    }
+}
+
+// an opening whose proof is deferred: polynomial `poly_index` of commitment `commit_index`
+// evaluates to `value` at `point`
+#[derive(Debug, Clone)]
+pub struct DeferredClaim {
+    pub commit_index: usize,
+    pub poly_index: usize,
+    pub point: Vec<F>,
+    pub value: F,
+}
+
+// eq(point, x) over the hypercube, point[0] being the lowest bit of x
+fn eq_evals(point: &[F], encoder: &BFVEncoder) -> Vec<F> {
+    let mut res = vec![F::from_int(1, encoder)];
+    for p in point {
+        res = res
+            .iter()
+            .map(|x| F::sub(x, &F::mult(x, p, encoder), encoder))
+            .chain(res.iter().map(|x| F::mult(x, p, encoder)))
+            .collect();
+    }
+    res
+}
+
+fn eval_eq(a: &[F], b: &[F], encoder: &BFVEncoder) -> F {
+    let one = F::from_int(1, encoder);
+    a.iter().zip(b.iter()).fold(one.clone(), |acc, (x, y)| {
+        let both = F::mult(x, y, encoder);
+        let neither = F::mult(&F::sub(&one, x, encoder), &F::sub(&one, y, encoder), encoder);
+        F::mult(&acc, &F::add(&both, &neither, encoder), encoder)
+    })
+}
+
+fn fold_lowest(evals: &mut Vec<F>, challenge: &F, encoder: &BFVEncoder) {
+    let new_len = evals.len() / 2;
+    for j in 0..new_len {
+        let diff = F::sub(&evals[j * 2 + 1], &evals[j * 2], encoder);
+        evals[j] = F::add(&evals[j * 2], &F::mult(&diff, challenge, encoder), encoder);
+    }
+    evals.truncate(new_len);
+}
+
+// quadratic through (0, v[0]), (1, v[1]), (2, v[2]) evaluated at x
+fn extrapolate(v: &[F; 3], x: &F, encoder: &BFVEncoder) -> F {
+    let d1 = F::sub(&v[1], &v[0], encoder);
+    let d2 = F::sub(&F::sub(&v[2], &v[1], encoder), &d1, encoder);
+    let x_x1 = F::mult(x, &F::sub(x, &F::from_int(1, encoder), encoder), encoder);
+    let half = F::mult(&x_x1, &F::inverse_2(encoder), encoder);
+    F::add(
+        &F::add(&v[0], &F::mult(x, &d1, encoder), encoder),
+        &F::mult(&half, &d2, encoder),
+        encoder,
+    )
+}
+
+fn single_point(claims: &[DeferredClaim]) -> bool {
+    claims.iter().all(|x| x.point == claims[0].point)
+}
+
+// the statement of a deferred claim, absorbed before any challenge can depend on it
+fn absorb_claim(
+    transcript: &mut Transcript,
+    root: &MerkleRoot,
+    poly_index: usize,
+    point: &[F],
+    value: &F,
+    encoder: &BFVEncoder,
+) {
+    transcript.append_u8_slice(&root.0, HASH_SIZE);
+    transcript.append_u8_slice(&(poly_index as u64).to_be_bytes(), size_of::<u64>());
+    for x in point {
+        transcript.append_f(x, encoder);
+    }
+    transcript.append_f(value, encoder);
+}
+
+// the prover half of an accumulator: keeps the committed codewords until the claims on them
+// are discharged. claims at different points are reduced to one point with the sumcheck
+// over sum_i eta^i * eq(z_i, X) * f_i(X), after which every polynomial of every commitment
+// is claimed at the sumcheck point. every deferred claim goes into the transcript as it is
+// deferred, and the messages of each merge and of the discharge come back as a proof
+#[derive(Clone, Default)]
+pub struct DeepFoldProverAccumulator<'a> {
+    provers: Vec<DeepFoldProver<'a>>,
+    roots: Vec<MerkleRoot>,
+    claims: Vec<DeferredClaim>,
+}
+
+impl<'a> DeepFoldProverAccumulator<'a> {
+    pub fn new() -> Self {
+        DeepFoldProverAccumulator {
+            provers: vec![],
+            roots: vec![],
+            claims: vec![],
+        }
+    }
+
+    pub fn claims(&self) -> &[DeferredClaim] {
+        &self.claims
+    }
+
+    fn commit_index(&mut self, prover: &DeepFoldProver<'a>, root: MerkleRoot) -> usize {
+        match self.roots.iter().position(|x| x.0 == root.0) {
+            Some(i) => i,
+            None => {
+                self.provers.push(prover.clone());
+                self.roots.push(root);
+                self.roots.len() - 1
+            }
+        }
+    }
+
+    pub fn defer(
+        &mut self,
+        prover: &DeepFoldProver<'a>,
+        poly_index: usize,
+        point: Vec<F>,
+        value: F,
+        transcript: &mut Transcript,
+        encoder: &BFVEncoder,
+    ) {
+        let root = prover.root();
+        absorb_claim(transcript, &root, poly_index, &point, &value, encoder);
+        let commit_index = self.commit_index(prover, root);
+        self.claims.push(DeferredClaim {
+            commit_index,
+            poly_index,
+            point,
+            value,
+        });
+    }
+
+    // takes over the claims of `other` and reduces everything to claims at one point
+    pub fn merge(
+        &mut self,
+        other: Self,
+        transcript: &mut Transcript,
+        encoder: &BFVEncoder,
+    ) -> OpeningProof {
+        let indices = other
+            .provers
+            .iter()
+            .zip(other.roots)
+            .map(|(prover, root)| self.commit_index(prover, root))
+            .collect::<Vec<_>>();
+        self.claims
+            .extend(other.claims.into_iter().map(|x| DeferredClaim {
+                commit_index: indices[x.commit_index],
+                ..x
+            }));
+        let start = transcript.proof.bytes.len();
+        self.reduce(transcript, encoder);
+        OpeningProof {
+            bytes: transcript.proof.bytes[start..].to_vec(),
+        }
+    }
+
+    fn reduce(&mut self, transcript: &mut Transcript, encoder: &BFVEncoder) {
+        if single_point(&self.claims) {
+            return;
+        }
+        let nv = self.claims[0].point.len();
+        let zero = F::from_int(0, encoder);
+        let eta = transcript.challenge_f(encoder);
+        let mut weight = F::from_int(1, encoder);
+        // claims sharing a point share one eq table: g_z = sum_{i: z_i = z} eta^i * f_i
+        let mut points: Vec<&Vec<F>> = vec![];
+        let mut combined: Vec<Vec<F>> = vec![];
+        for claim in self.claims.iter() {
+            assert_eq!(claim.point.len(), nv);
+            let k = points
+                .iter()
+                .position(|&x| *x == claim.point)
+                .unwrap_or_else(|| {
+                    points.push(&claim.point);
+                    combined.push(vec![zero.clone(); 1 << nv]);
+                    points.len() - 1
+                });
+            let poly = self.provers[claim.commit_index].poly(claim.poly_index, encoder);
+            for (g, f) in combined[k].iter_mut().zip(poly.iter()) {
+                *g = F::add(g, &F::mult(&weight, f, encoder), encoder);
+            }
+            weight = F::mult(&weight, &eta, encoder);
+        }
+        let mut eqs = points
+            .into_iter()
+            .map(|x| eq_evals(x, encoder))
+            .collect::<Vec<_>>();
+
+        let mut point = vec![];
+        for i in 0..nv {
+            let m = 1 << (nv - i);
+            let mut sums = [0; 3].map(|_| zero.clone());
+            for (eq, g) in eqs.iter().zip(combined.iter()) {
+                for x in (0..m).step_by(2) {
+                    let e2 = F::sub(&F::add(&eq[x + 1], &eq[x + 1], encoder), &eq[x], encoder);
+                    let g2 = F::sub(&F::add(&g[x + 1], &g[x + 1], encoder), &g[x], encoder);
+                    sums[0] = F::add(&sums[0], &F::mult(&eq[x], &g[x], encoder), encoder);
+                    sums[1] = F::add(&sums[1], &F::mult(&eq[x + 1], &g[x + 1], encoder), encoder);
+                    sums[2] = F::add(&sums[2], &F::mult(&e2, &g2, encoder), encoder);
+                }
+            }
+            for x in sums.iter() {
+                transcript.append_f(x, encoder);
+            }
+            let challenge = transcript.challenge_f(encoder);
+            for x in eqs.iter_mut().chain(combined.iter_mut()) {
+                fold_lowest(x, &challenge, encoder);
+            }
+            point.push(challenge);
+        }
+
+        self.claims = vec![];
+        for (i, prover) in self.provers.iter().enumerate() {
+            for j in 0..prover.poly_num() {
+                let value = MultiLinearPoly::eval_multilinear(
+                    &prover.poly(j, encoder).to_vec(),
+                    &point,
+                    encoder,
+                );
+                transcript.append_f(&value, encoder);
+                self.claims.push(DeferredClaim {
+                    commit_index: i,
+                    poly_index: j,
+                    point: point.clone(),
+                    value,
+                });
+            }
+        }
+    }
+
+    // one opening for every deferred claim, the points having pp.variable_num variables
+    pub fn discharge(
+        mut self,
+        pp: &DeepFoldParam<'a>,
+        transcript: &mut Transcript,
+    ) -> OpeningProof {
+        assert!(!pp.hiding);
+        if self.claims.is_empty() {
+            return OpeningProof::default();
+        }
+        let start = transcript.proof.bytes.len();
+        self.reduce(transcript, &pp.encoder());
+        let point = self.claims[0].point.clone();
+        let claims = self
+            .claims
+            .iter()
+            .map(|x| OpeningClaim {
+                commit_index: x.commit_index,
+                poly_index: x.poly_index,
+                var_num: point.len(),
+            })
+            .collect::<Vec<_>>();
+        let provers = self.provers.iter().collect::<Vec<_>>();
+        DeepFoldProver::prove(pp, &provers, &claims, point, transcript);
+        OpeningProof {
+            bytes: transcript.proof.bytes[start..].to_vec(),
+        }
+    }
+}
+
+// the verifier half of an accumulator, mirroring DeepFoldProverAccumulator
+#[derive(Clone, Default)]
+pub struct DeepFoldAccumulator {
+    roots: Vec<MerkleRoot>,
+    poly_nums: Vec<usize>,
+    claims: Vec<DeferredClaim>,
+}
+
+impl DeepFoldAccumulator {
+    pub fn new() -> Self {
+        DeepFoldAccumulator {
+            roots: vec![],
+            poly_nums: vec![],
+            claims: vec![],
+        }
+    }
+
+    pub fn claims(&self) -> &[DeferredClaim] {
+        &self.claims
+    }
+
+    pub fn commit_num(&self) -> usize {
+        self.roots.len()
+    }
+
+    // a root seen before must come with the same number of polynomials
+    fn commit_index(&mut self, root: &MerkleRoot, poly_num: usize) -> Result<usize, PcsError> {
+        match self.roots.iter().position(|x| x.0 == root.0) {
+            Some(i) if self.poly_nums[i] != poly_num => Err(PcsError::Claim),
+            Some(i) => Ok(i),
+            None => {
+                self.roots.push(root.clone());
+                self.poly_nums.push(poly_num);
+                Ok(self.roots.len() - 1)
+            }
+        }
+    }
+
+    // `poly_num` is the number of polynomials under `commit`
+    pub fn defer(
+        &mut self,
+        commit: &MerkleRoot,
+        poly_num: usize,
+        poly_index: usize,
+        point: Vec<F>,
+        value: F,
+        transcript: &mut Transcript,
+        encoder: &BFVEncoder,
+    ) -> Result<(), PcsError> {
+        if poly_index >= poly_num {
+            return Err(PcsError::Claim);
+        }
+        absorb_claim(transcript, commit, poly_index, &point, &value, encoder);
+        let commit_index = self.commit_index(commit, poly_num)?;
+        self.claims.push(DeferredClaim {
+            commit_index,
+            poly_index,
+            point,
+            value,
+        });
+        Ok(())
+    }
+
+    pub fn merge(
+        &mut self,
+        other: Self,
+        transcript: &mut Transcript,
+        proof: &OpeningProof,
+        encoder: &BFVEncoder,
+    ) -> Result<(), PcsError> {
+        let indices = other
+            .roots
+            .iter()
+            .zip(other.poly_nums)
+            .map(|(root, poly_num)| self.commit_index(root, poly_num))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.claims
+            .extend(other.claims.into_iter().map(|x| DeferredClaim {
+                commit_index: indices[x.commit_index],
+                ..x
+            }));
+        let mut reader = proof.reader();
+        self.reduce(transcript, &mut reader, encoder)?;
+        if reader.remaining() > 0 {
+            return Err(PcsError::MalformedProof);
+        }
+        Ok(())
+    }
+
+    fn reduce(
+        &mut self,
+        transcript: &mut Transcript,
+        proof: &mut Proof,
+        encoder: &BFVEncoder,
+    ) -> Result<(), PcsError> {
+        if single_point(&self.claims) {
+            return Ok(());
+        }
+        let nv = self.claims[0].point.len();
+        let f_size = plaintext_size(encoder);
+        let eta = transcript.challenge_f(encoder);
+        let mut weights = vec![F::from_int(1, encoder)];
+        let mut y = F::from_int(0, encoder);
+        for claim in self.claims.iter() {
+            if claim.point.len() != nv {
+                return Err(PcsError::Claim);
+            }
+            let weight = weights.last().unwrap().clone();
+            y = F::add(&y, &F::mult(&weight, &claim.value, encoder), encoder);
+            weights.push(F::mult(&weight, &eta, encoder));
+        }
+
+        let mut point = vec![];
+        for _ in 0..nv {
+            ensure_remaining(proof, f_size * 3)?;
+            let sums = [0; 3].map(|_| proof.get_next_and_step(encoder));
+            for x in sums.iter() {
+                transcript.append_f(x, encoder);
+            }
+            if F::add(&sums[0], &sums[1], encoder) != y {
+                return Err(PcsError::Evaluation);
+            }
+            let challenge = transcript.challenge_f(encoder);
+            y = extrapolate(&sums, &challenge, encoder);
+            point.push(challenge);
+        }
+
+        ensure_remaining(proof, f_size * self.poly_nums.iter().sum::<usize>())?;
+        let mut values = vec![];
+        for &poly_num in self.poly_nums.iter() {
+            let v = (0..poly_num)
+                .map(|_| proof.get_next_and_step(encoder))
+                .collect::<Vec<_>>();
+            for x in v.iter() {
+                transcript.append_f(x, encoder);
+            }
+            values.push(v);
+        }
+        let mut expected = F::from_int(0, encoder);
+        for (claim, weight) in self.claims.iter().zip(weights) {
+            let term = F::mult(
+                &F::mult(&weight, &eval_eq(&claim.point, &point, encoder), encoder),
+                &values[claim.commit_index][claim.poly_index],
+                encoder,
+            );
+            expected = F::add(&expected, &term, encoder);
+        }
+        if expected != y {
+            return Err(PcsError::Evaluation);
+        }
+
+        self.claims = values
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, v)| {
+                let point = &point;
+                v.into_iter()
+                    .enumerate()
+                    .map(move |(j, value)| DeferredClaim {
+                        commit_index: i,
+                        poly_index: j,
+                        point: point.clone(),
+                        value,
+                    })
+            })
+            .collect();
+        Ok(())
+    }
+
+    pub fn discharge(
+        mut self,
+        pp: &DeepFoldParam,
+        transcript: &mut Transcript,
+        proof: &OpeningProof,
+    ) -> Result<(), PcsError> {
+        assert!(!pp.hiding);
+        let mut reader = proof.reader();
+        if !self.claims.is_empty() {
+            self.reduce(transcript, &mut reader, &pp.encoder())?;
+            self.check(pp, transcript, &mut reader)?;
+        }
+        if reader.remaining() > 0 {
+            return Err(PcsError::MalformedProof);
+        }
+        Ok(())
+    }
+
+    // the opening of the claims once they share a point
+    fn check(
+        &self,
+        pp: &DeepFoldParam,
+        transcript: &mut Transcript,
+        proof: &mut Proof,
+    ) -> Result<(), PcsError> {
+        let point = self.claims[0].point.clone();
+        let verifiers = self
+            .roots
+            .iter()
+            .zip(self.poly_nums.iter())
+            .map(|(root, &poly_num)| DeepFoldVerifier::new(pp, root.clone(), poly_num))
+            .collect::<Vec<_>>();
+        let claims = self
+            .claims
+            .iter()
+            .map(|x| OpeningClaim {
+                commit_index: x.commit_index,
+                poly_index: x.poly_index,
+                var_num: point.len(),
+            })
+            .collect::<Vec<_>>();
+        DeepFoldVerifier::check_batch(
+            pp,
+            &verifiers.iter().collect::<Vec<_>>(),
+            &claims,
+            point,
+            self.claims.iter().map(|x| x.value.clone()).collect(),
+            transcript,
+            proof,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arithmetic::{poly::MultiLinearPoly, test_utils::gen_params_n_ctx};
+    use seal_fhe::{BFVEncoder, Plaintext};
+    use util::fiat_shamir::Transcript;
+
+    use crate::{
+        CommitmentSerde, MerkleRoot, OpeningClaim, OpeningProof, PcsError,
+        PolynomialCommitmentScheme, ProofSerde,
+    };
+
+    use super::{
+        DeepFoldAccumulator, DeepFoldConfig, DeepFoldParam, DeepFoldProver,
+        DeepFoldProverAccumulator, HidingDeepFoldProver,
+    };
+
+    type F = Plaintext;
+
+    // low security keeps the query count small at these sizes
+    fn config(variable_num: usize) -> DeepFoldConfig {
+        DeepFoldConfig {
+            log_blowup: 2,
+            security_bits: 32,
+            final_poly_log_size: 2,
+            ..DeepFoldConfig::new(variable_num)
+        }
+    }
+
+    fn random_polies(poly_num: usize, nv: usize, encoder: &BFVEncoder) -> Vec<Vec<F>> {
+        (0..poly_num)
+            .map(|_| (0..(1 << nv)).map(|_| F::random_pt(encoder)).collect())
+            .collect()
+    }
+
+    fn commit_open_verify(pp: &DeepFoldParam, poly_num: usize) {
+        let encoder = pp.encoder();
+        let nv = pp.opened_var_num();
+        let polies = random_polies(poly_num, nv, &encoder);
+        let point = (0..nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let evals = vec![polies
+            .iter()
+            .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &encoder))
+            .collect::<Vec<_>>()];
+
+        let (commitment, prover) = DeepFoldProver::commit(pp, &polies);
+        let size = MerkleRoot::size(nv, poly_num, &encoder);
+        let mut buffer = vec![0u8; size];
+        commitment.serialize_into(&mut buffer, &encoder);
+        let transcript = || {
+            let mut transcript = Transcript::new();
+            transcript.append_u8_slice(&buffer, size);
+            for i in &evals[0] {
+                transcript.append_f(i, &encoder);
+            }
+            transcript
+        };
+        let proof = DeepFoldProver::open(pp, &[&prover], &point, &mut transcript());
+        assert_eq!(
+            DeepFoldProver::verify(
+                pp,
+                &[&commitment],
+                &point,
+                &evals,
+                &proof,
+                &mut transcript()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn final_poly() {
+        let (params, ctx) = gen_params_n_ctx();
+        for (final_poly_log_size, pow_bits) in [(0, 0), (3, 8)] {
+            let config = DeepFoldConfig {
+                final_poly_log_size,
+                pow_bits,
+                ..config(7)
+            };
+            let (pp, report) = DeepFoldParam::new(config, &ctx, &params);
+            assert_eq!((report.query_num, report.pow_bits), (pp.query_num, pp.pow_bits));
+            commit_open_verify(&pp, 2);
+        }
+    }
+
+    #[test]
+    fn folding_factor() {
+        let (params, ctx) = gen_params_n_ctx();
+        for folding_factor in 2..=4 {
+            let config = DeepFoldConfig {
+                folding_factor,
+                ..config(7)
+            };
+            let pp = DeepFoldParam::new(config, &ctx, &params).0;
+            commit_open_verify(&pp, 2);
+        }
+    }
+
+    fn batch_open_verify(pp: &DeepFoldParam, var_nums: &[usize]) {
+        let encoder = pp.encoder();
+        let nv = pp.opened_var_num();
+        let polies = var_nums
+            .iter()
+            .map(|&n| random_polies(2, n, &encoder))
+            .collect::<Vec<_>>();
+        let point = (0..nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        // only the second polynomial of each commitment is opened
+        let claims = var_nums
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| OpeningClaim {
+                commit_index: i,
+                poly_index: 1,
+                var_num: n,
+            })
+            .collect::<Vec<_>>();
+        let evals = claims
+            .iter()
+            .map(|c| {
+                MultiLinearPoly::eval_multilinear(
+                    &polies[c.commit_index][c.poly_index],
+                    &point[nv - c.var_num..],
+                    &encoder,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let (commits, provers): (Vec<_>, Vec<_>) =
+            polies.iter().map(|x| DeepFoldProver::commit(pp, x)).unzip();
+        let proof = DeepFoldProver::batch_open(
+            pp,
+            &provers.iter().collect::<Vec<_>>(),
+            &claims,
+            &point,
+            &mut Transcript::new(),
+        );
+
+        let verify = |evals: &[F]| {
+            DeepFoldProver::batch_verify(
+                pp,
+                &commits.iter().collect::<Vec<_>>(),
+                &vec![2; commits.len()],
+                &claims,
+                &point,
+                evals,
+                &proof,
+                &mut Transcript::new(),
+            )
+        };
+        assert_eq!(verify(&evals), Ok(()));
+        let mut wrong_evals = evals.clone();
+        wrong_evals[1] = F::add(&wrong_evals[1], &F::from_int(1, &encoder), &encoder);
+        assert!(verify(&wrong_evals).is_err());
+        // a claim on a polynomial the commitment does not hold
+        let mut wrong_claims = claims.clone();
+        wrong_claims[0].poly_index = 2;
+        assert_eq!(
+            DeepFoldProver::batch_verify(
+                pp,
+                &commits.iter().collect::<Vec<_>>(),
+                &vec![2; commits.len()],
+                &wrong_claims,
+                &point,
+                &evals,
+                &proof,
+                &mut Transcript::new(),
+            ),
+            Err(PcsError::Claim)
+        );
+    }
+
+    #[test]
+    fn batch_different_sizes() {
+        let (params, ctx) = gen_params_n_ctx();
+        let pp = DeepFoldParam::new(config(8), &ctx, &params).0;
+        batch_open_verify(&pp, &[8, 6, 3]);
+        let config = DeepFoldConfig {
+            folding_factor: 2,
+            ..config(8)
+        };
+        let pp = DeepFoldParam::new(config, &ctx, &params).0;
+        batch_open_verify(&pp, &[6, 8, 4]);
+    }
+
+    #[test]
+    fn hiding() {
+        let (params, ctx) = gen_params_n_ctx();
+        for folding_factor in 1..=2 {
+            let config = DeepFoldConfig {
+                folding_factor,
+                hiding: true,
+                ..config(7)
+            };
+            let pp = DeepFoldParam::new(config, &ctx, &params).0;
+            commit_open_verify(&pp, 2);
+        }
+        let config = DeepFoldConfig {
+            folding_factor: 2,
+            hiding: true,
+            ..config(7)
+        };
+        let pp = DeepFoldParam::new(config, &ctx, &params).0;
+        batch_open_verify(&pp, &[7, 5, 3]);
+
+        // the same polynomial commits to a fresh root every time, and the same query opens
+        // to different codeword values
+        let encoder = pp.encoder();
+        let poly = random_polies(1, 7, &encoder);
+        let provers = [0; 2].map(|_| HidingDeepFoldProver::commit(&pp, &poly));
+        assert!(HidingDeepFoldProver::HIDING);
+        assert_ne!(provers[0].0, provers[1].0);
+        let leaf_indices = vec![0, 5];
+        let queries = provers
+            .each_ref()
+            .map(|x| x.1 .0.query(&leaf_indices, &encoder));
+        assert_ne!(queries[0].1, queries[1].1);
+
+        // and the hiding prover opens like the plain one
+        let point = (0..7).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let eval = MultiLinearPoly::eval_multilinear(&poly[0], &point, &encoder);
+        let proof =
+            HidingDeepFoldProver::open(&pp, &[&provers[0].1], &point, &mut Transcript::new());
+        assert_eq!(
+            DeepFoldProver::verify(
+                &pp,
+                &[&provers[0].0],
+                &point,
+                &[vec![eval]],
+                &proof,
+                &mut Transcript::new()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn streaming() {
+        let (params, ctx) = gen_params_n_ctx();
+        let config = DeepFoldConfig {
+            folding_factor: 2,
+            ..config(8)
+        };
+        let pp = DeepFoldParam::new(config, &ctx, &params).0;
+        let encoder = pp.encoder();
+        let point = (0..8).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let polies = [8, 6].map(|n| random_polies(3, n, &encoder));
+        let claims = (0..2)
+            .flat_map(|i| {
+                (0..3).map(move |j| OpeningClaim {
+                    commit_index: i,
+                    poly_index: j,
+                    var_num: 8 - i * 2,
+                })
+            })
+            .collect::<Vec<_>>();
+        let evals = claims
+            .iter()
+            .map(|c| {
+                MultiLinearPoly::eval_multilinear(
+                    &polies[c.commit_index][c.poly_index],
+                    &point[8 - c.var_num..],
+                    &encoder,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let (commits, provers): (Vec<_>, Vec<_>) = polies
+            .iter()
+            .map(|x| DeepFoldProver::commit_streaming(&pp, x.clone(), &std::env::temp_dir()))
+            .unzip();
+        for (commit, poly) in commits.iter().zip(polies.iter()) {
+            assert_eq!(*commit, DeepFoldProver::new(&pp, poly).root());
+        }
+        let proof = DeepFoldProver::batch_open(
+            &pp,
+            &provers.iter().collect::<Vec<_>>(),
+            &claims,
+            &point,
+            &mut Transcript::new(),
+        );
+        assert_eq!(
+            DeepFoldProver::batch_verify(
+                &pp,
+                &commits.iter().collect::<Vec<_>>(),
+                &[3, 3],
+                &claims,
+                &point,
+                &evals,
+                &proof,
+                &mut Transcript::new()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn scheme() {
+        type Scheme<'a> = DeepFoldProver<'a>;
+        let (params, ctx) = gen_params_n_ctx();
+        let mut rng = rand::thread_rng();
+        let pp = Scheme::setup(8, &ctx, &params, &mut rng);
+        let pp = <Scheme as PolynomialCommitmentScheme>::trim(&pp, 6);
+        let encoder = pp.encoder();
+        let polies = random_polies(3, 6, &encoder);
+        let point = (0..6).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let evals = vec![polies
+            .iter()
+            .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &encoder))
+            .collect::<Vec<_>>()];
+
+        let (commitment, data) = <Scheme as PolynomialCommitmentScheme>::commit(&pp, &polies);
+        let proof = <Scheme as PolynomialCommitmentScheme>::open(
+            &pp,
+            &[&data],
+            &point,
+            &mut Transcript::new(),
+        );
+        assert!(proof.size() <= Scheme::proof_size(&pp, 6, 3));
+        let mut buffer = vec![0u8; proof.size()];
+        proof.serialize_into(&mut buffer);
+        let mut stream = util::fiat_shamir::Proof::default();
+        stream.append_u8_slice(&buffer, buffer.len());
+        let proof = OpeningProof::deserialize_from(&mut stream).unwrap();
+
+        let verify = |evals: &[Vec<F>], proof: &OpeningProof| {
+            <Scheme as PolynomialCommitmentScheme>::verify(
+                &pp,
+                &[&commitment],
+                &point,
+                evals,
+                proof,
+                &mut Transcript::new(),
+            )
+        };
+        assert_eq!(verify(&evals, &proof), Ok(()));
+
+        // a streamed commitment is the same root and opens through the scheme as well
+        let (streamed, data) =
+            Scheme::commit_streaming(&pp, polies.clone(), &std::env::temp_dir());
+        assert_eq!(streamed, commitment);
+        let streamed_proof = <Scheme as PolynomialCommitmentScheme>::open(
+            &pp,
+            &[&data],
+            &point,
+            &mut Transcript::new(),
+        );
+        assert_eq!(verify(&evals, &streamed_proof), Ok(()));
+
+        let mut wrong_evals = evals.clone();
+        wrong_evals[0][1] = F::add(&wrong_evals[0][1], &F::from_int(1, &encoder), &encoder);
+        assert!(verify(&wrong_evals, &proof).is_err());
+
+        let truncated = OpeningProof {
+            bytes: proof.bytes[..proof.bytes.len() - 1].to_vec(),
+        };
+        assert_eq!(verify(&evals, &truncated), Err(PcsError::MalformedProof));
+        // the last bytes are the values opened in the last round, a changed value no longer
+        // hashes to the root committed for it
+        let mut tampered = proof.clone();
+        let len = tampered.bytes.len();
+        tampered.bytes[len - 1] ^= 1;
+        assert_eq!(
+            verify(&evals, &tampered),
+            Err(PcsError::MerklePath {
+                round: pp.round_num() - 1
+            })
+        );
+    }
+
+    #[test]
+    fn accumulate() {
+        let (params, ctx) = gen_params_n_ctx();
+        let nv = 6;
+        let pp = DeepFoldParam::new(config(nv), &ctx, &params).0;
+        let encoder = pp.encoder();
+        let points = (0..3)
+            .map(|_| (0..nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        // a commitment shared by three proofs, and one commitment of each proof
+        let shared = random_polies(2, nv, &encoder);
+        let own = (0..3)
+            .map(|_| random_polies(1, nv, &encoder))
+            .collect::<Vec<_>>();
+        let eval =
+            |poly: &Vec<F>, k: usize| MultiLinearPoly::eval_multilinear(poly, &points[k], &encoder);
+
+        let (shared_root, shared_prover) = DeepFoldProver::commit(&pp, &shared);
+        let (own_roots, own_provers): (Vec<_>, Vec<_>) =
+            own.iter().map(|x| DeepFoldProver::commit(&pp, x)).unzip();
+        let mut transcript = Transcript::new();
+        let mut acc = DeepFoldProverAccumulator::new();
+        let mut proofs = vec![];
+        for k in 0..3 {
+            let mut next = DeepFoldProverAccumulator::new();
+            for j in 0..2 {
+                let value = eval(&shared[j], k);
+                next.defer(&shared_prover, j, points[k].clone(), value, &mut transcript, &encoder);
+            }
+            let value = eval(&own[k][0], k);
+            next.defer(&own_provers[k], 0, points[k].clone(), value, &mut transcript, &encoder);
+            proofs.push(acc.merge(next, &mut transcript, &encoder));
+        }
+        assert_eq!(acc.claims().len(), 5);
+        proofs.push(acc.discharge(&pp, &mut transcript));
+
+        let verify = |offset: u64, proofs: &[OpeningProof]| {
+            let mut transcript = Transcript::new();
+            let mut acc = DeepFoldAccumulator::new();
+            for k in 0..3 {
+                let mut next = DeepFoldAccumulator::new();
+                for j in 0..2 {
+                    let value = eval(&shared[j], k);
+                    next.defer(
+                        &shared_root,
+                        2,
+                        j,
+                        points[k].clone(),
+                        value,
+                        &mut transcript,
+                        &encoder,
+                    )?;
+                }
+                let value = F::add(
+                    &eval(&own[k][0], k),
+                    &F::from_int(offset, &encoder),
+                    &encoder,
+                );
+                next.defer(
+                    &own_roots[k],
+                    1,
+                    0,
+                    points[k].clone(),
+                    value,
+                    &mut transcript,
+                    &encoder,
+                )?;
+                acc.merge(next, &mut transcript, &proofs[k], &encoder)?;
+            }
+            assert_eq!(acc.commit_num(), 4);
+            acc.discharge(&pp, &mut transcript, &proofs[3])
+        };
+        assert_eq!(verify(0, &proofs), Ok(()));
+        assert!(verify(1, &proofs).is_err());
+        // the claims are bound before eta is drawn, so the reduction of one set of claims
+        // does not carry over to another
+        let mut swapped = proofs.clone();
+        swapped.swap(1, 2);
+        assert!(verify(0, &swapped).is_err());
+
+        // a claim on a polynomial the commitment does not hold, and a root seen before with
+        // a different number of polynomials
+        let mut acc = DeepFoldAccumulator::new();
+        let mut transcript = Transcript::new();
+        let value = eval(&shared[0], 0);
+        assert_eq!(
+            acc.defer(
+                &shared_root,
+                2,
+                2,
+                points[0].clone(),
+                value.clone(),
+                &mut transcript,
+                &encoder
+            ),
+            Err(PcsError::Claim)
+        );
+        acc.defer(&shared_root, 2, 0, points[0].clone(), value.clone(), &mut transcript, &encoder)
+            .unwrap();
+        assert_eq!(
+            acc.defer(&shared_root, 3, 0, points[0].clone(), value, &mut transcript, &encoder),
+            Err(PcsError::Claim)
+        );
     }
 }
@@ -0,0 +1,543 @@
+use std::marker::PhantomData;
+
+use arithmetic::{
+    field::plaintext_size,
+    mul_group::{mul_mod, pow_mod},
+    poly::MultiLinearPoly,
+};
+use rand::{Rng, RngCore};
+use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
+use util::fiat_shamir::{Proof, Transcript};
+
+use crate::{
+    ensure_remaining, CommitmentSerde, HomomorphicCommitment, OpeningProof, PcsError,
+    PolynomialCommitmentScheme, ProofSerde,
+};
+
+type F = Plaintext;
+
+const MILLER_RABIN_BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+// deterministic for every u64
+fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for p in MILLER_RABIN_BASES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    MILLER_RABIN_BASES.iter().all(|&a| {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+// the low half of the variables index the columns of the evaluation matrix
+fn col_var_num(nv: usize) -> usize {
+    nv.div_ceil(2)
+}
+
+// eq(point, i) for every i, the first coordinate on the lowest bit
+fn eq_evals(point: &[F], encoder: &BFVEncoder) -> Vec<F> {
+    let one = F::from_int(1, encoder);
+    let mut res = vec![one.clone()];
+    for p in point {
+        let np = F::sub(&one, p, encoder);
+        let len = res.len();
+        for j in 0..len {
+            res.push(F::mult(&res[j], p, encoder));
+            res[j] = F::mult(&res[j], &np, encoder);
+        }
+    }
+    res
+}
+
+// pedersen commitments to the rows of the evaluation matrix, slot by slot: the commitment to
+// a row v is prod_j g_j^v_j[s] for every slot s, in the subgroup of order t (the plain
+// modulus) of Z_P^* with P = k * t + 1 prime. exponents are taken modulo t, so the
+// commitment is linear in the committed polynomials over the plaintext slots.
+// an opening sends the eq-weighted combination of the rows, checked against the same
+// combination of the row commitments, so proofs hold 2^(nv / 2) plaintexts and the scheme
+// is binding but not hiding.
+// INSECURE: discrete logs in a group of order t fall to pollard rho in about sqrt(t) steps,
+// which for the plain moduli used here is a few million, so nothing binds the prover. the
+// module only builds for tests, as the homomorphic backend to test that path against
+#[derive(Debug, Clone)]
+pub struct HyraxParam<'a> {
+    pub modulus: u64,
+    pub generators: Vec<u64>,
+    pub ctx: &'a Context,
+    pub params: &'a EncryptionParameters,
+}
+
+impl<'a> HyraxParam<'a> {
+    pub fn new(
+        max_nv: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+        rng: &mut impl RngCore,
+    ) -> Self {
+        let t = params.get_plain_modulus().value();
+        let k = (1..)
+            .map(|i| i * 2)
+            .find(|&k| is_prime(k * t + 1))
+            .unwrap();
+        let modulus = k * t + 1;
+        let generators = (0..1 << col_var_num(max_nv))
+            .map(|_| loop {
+                let g = pow_mod(rng.gen_range(2..modulus), k, modulus);
+                if g != 1 {
+                    break g;
+                }
+            })
+            .collect();
+        HyraxParam {
+            modulus,
+            generators,
+            ctx,
+            params,
+        }
+    }
+
+    pub fn encoder(&self) -> BFVEncoder<'a> {
+        BFVEncoder::new(self.ctx, self.params).unwrap()
+    }
+
+    fn commit_row(&self, row: &[F], encoder: &BFVEncoder) -> Vec<u64> {
+        let mut res = vec![1; encoder.get_slot_count()];
+        for (g, x) in self.generators.iter().zip(row.iter()) {
+            for (r, v) in res.iter_mut().zip(x.get_value(encoder)) {
+                *r = mul_mod(*r, pow_mod(*g, v, self.modulus), self.modulus);
+            }
+        }
+        res
+    }
+
+    // prod_k commits[k]^exps[k] slot by slot
+    fn multi_exp(&self, commits: &[&Vec<u64>], exps: &[F], encoder: &BFVEncoder) -> Vec<u64> {
+        let mut res = vec![1; encoder.get_slot_count()];
+        for (c, e) in commits.iter().zip(exps.iter()) {
+            for ((r, c), e) in res.iter_mut().zip(c.iter()).zip(e.get_value(encoder)) {
+                *r = mul_mod(*r, pow_mod(*c, e, self.modulus), self.modulus);
+            }
+        }
+        res
+    }
+}
+
+// one commitment per row, the rows of every polynomial one after another
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HyraxCommitment(pub Vec<Vec<u64>>);
+
+impl CommitmentSerde for HyraxCommitment {
+    fn size(nv: usize, np: usize, encoder: &BFVEncoder) -> usize {
+        np * (1 << (nv - col_var_num(nv))) * encoder.get_slot_count() * 8
+    }
+
+    fn serialize_into(&self, buffer: &mut [u8], _encoder: &BFVEncoder) {
+        for (chunk, x) in buffer.chunks_mut(8).zip(self.0.iter().flatten()) {
+            chunk.copy_from_slice(&x.to_be_bytes());
+        }
+    }
+
+    fn deserialize_from(
+        proof: &mut Proof,
+        nv: usize,
+        np: usize,
+        encoder: &BFVEncoder,
+    ) -> Self {
+        let slot_count = encoder.get_slot_count();
+        Self(
+            (0..np << (nv - col_var_num(nv)))
+                .map(|_| {
+                    proof
+                        .get_next_slice(slot_count * 8)
+                        .chunks(8)
+                        .map(|x| u64::from_be_bytes(x.try_into().unwrap()))
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HyraxProver<'a> {
+    poly: Vec<Vec<F>>,
+    commitment: HyraxCommitment,
+    _data: PhantomData<&'a Context>,
+}
+
+impl<'a> HyraxProver<'a> {
+    pub fn new(pp: &HyraxParam<'a>, poly: &[Vec<F>]) -> Self {
+        let encoder = pp.encoder();
+        let cols = 1 << col_var_num(poly[0].len().ilog2() as usize);
+        assert!(cols <= pp.generators.len());
+        let commitment = HyraxCommitment(
+            poly.iter()
+                .flat_map(|x| x.chunks(cols))
+                .map(|row| pp.commit_row(row, &encoder))
+                .collect(),
+        );
+        HyraxProver {
+            poly: poly.to_vec(),
+            commitment,
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<'a> PolynomialCommitmentScheme<'a> for HyraxProver<'a> {
+    type Param = HyraxParam<'a>;
+    type Commitment = HyraxCommitment;
+    type ProverData = Self;
+    type Proof = OpeningProof;
+
+    fn setup(
+        max_nv: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+        rng: &mut impl RngCore,
+    ) -> Self::Param {
+        HyraxParam::new(max_nv, ctx, params, rng)
+    }
+
+    // the generators of the longest rows serve the shorter ones
+    fn trim(pp: &Self::Param, _nv: usize) -> Self::Param {
+        pp.clone()
+    }
+
+    fn commit(pp: &Self::Param, polies: &[Vec<F>]) -> (Self::Commitment, Self) {
+        let prover = Self::new(pp, polies);
+        (prover.commitment.clone(), prover)
+    }
+
+    fn open(
+        pp: &Self::Param,
+        provers: &[&Self],
+        point: &[F],
+        transcript: &mut Transcript,
+    ) -> Self::Proof {
+        let start = transcript.proof.bytes.len();
+        let encoder = pp.encoder();
+        let r = transcript.challenge_f(&encoder);
+        let mut matrix = provers[0].poly[0].clone();
+        for poly in provers.iter().flat_map(|x| x.poly.iter()).skip(1) {
+            for (m, x) in matrix.iter_mut().zip(poly.iter()) {
+                *m = F::add(&F::mult(m, &r, &encoder), x, &encoder);
+            }
+        }
+        let cols = 1 << col_var_num(point.len());
+        let weights = eq_evals(&point[col_var_num(point.len())..], &encoder);
+        let mut message = vec![F::from_int(0, &encoder); cols];
+        for (row, w) in matrix.chunks(cols).zip(weights.iter()) {
+            for (m, x) in message.iter_mut().zip(row.iter()) {
+                *m = F::add(m, &F::mult(x, w, &encoder), &encoder);
+            }
+        }
+        for x in message.iter() {
+            transcript.append_f(x, &encoder);
+        }
+        OpeningProof {
+            bytes: transcript.proof.bytes[start..].to_vec(),
+        }
+    }
+
+    fn verify(
+        pp: &Self::Param,
+        commits: &[&Self::Commitment],
+        point: &[F],
+        evals: &[Vec<F>],
+        proof: &Self::Proof,
+        transcript: &mut Transcript,
+    ) -> Result<(), PcsError> {
+        if commits.len() != evals.len() {
+            return Err(PcsError::Claim);
+        }
+        let verifiers = commits
+            .iter()
+            .zip(evals.iter())
+            .map(|(commit, evals)| HyraxVerifier::new((*commit).clone(), evals.len()))
+            .collect::<Vec<_>>();
+        let mut reader = proof.reader();
+        HyraxVerifier::check(
+            pp,
+            &verifiers.iter().collect::<Vec<_>>(),
+            point.to_vec(),
+            evals.to_vec(),
+            transcript,
+            &mut reader,
+        )?;
+        if reader.remaining() > 0 {
+            return Err(PcsError::MalformedProof);
+        }
+        Ok(())
+    }
+
+    // the eq-weighted combination of the rows
+    fn proof_size(pp: &Self::Param, nv: usize, _poly_num: usize) -> usize {
+        OpeningProof::default().size() + (1 << col_var_num(nv)) * plaintext_size(&pp.encoder())
+    }
+}
+
+impl<'a> HomomorphicCommitment<'a> for HyraxProver<'a> {
+    fn combine(
+        pp: &Self::Param,
+        commits: &[HyraxCommitment],
+        coeffs: &[F],
+        encoder: &BFVEncoder,
+    ) -> HyraxCommitment {
+        HyraxCommitment(
+            (0..commits[0].0.len())
+                .map(|i| {
+                    let row = commits.iter().map(|x| &x.0[i]).collect::<Vec<_>>();
+                    pp.multi_exp(&row, coeffs, encoder)
+                })
+                .collect(),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HyraxVerifier<'a> {
+    commit: HyraxCommitment,
+    poly_num: usize,
+    _data: PhantomData<&'a Context>,
+}
+
+impl<'a> HyraxVerifier<'a> {
+    pub fn new(commit: HyraxCommitment, poly_num: usize) -> Self {
+        HyraxVerifier {
+            commit,
+            poly_num,
+            _data: PhantomData,
+        }
+    }
+
+    // verify reporting why a proof is rejected; a short proof is rejected, not a panic
+    pub fn check(
+        pp: &HyraxParam<'a>,
+        verifiers: &[&Self],
+        point: Vec<F>,
+        evals: Vec<Vec<F>>,
+        transcript: &mut Transcript,
+        proof: &mut Proof,
+    ) -> Result<(), PcsError> {
+        let encoder = pp.encoder();
+        let r = transcript.challenge_f(&encoder);
+        let mut eval = F::from_int(0, &encoder);
+        for i in evals {
+            for j in i {
+                eval = F::add(&F::mult(&eval, &r, &encoder), &j, &encoder);
+            }
+        }
+        let col_num = col_var_num(point.len());
+        let cols = 1 << col_num;
+        ensure_remaining(proof, cols * plaintext_size(&encoder))?;
+        let message = (0..cols)
+            .map(|_| proof.get_next_and_step(&encoder))
+            .collect::<Vec<_>>();
+        for x in message.iter() {
+            transcript.append_f(x, &encoder);
+        }
+        if MultiLinearPoly::eval_multilinear(&message, &point[..col_num], &encoder) != eval {
+            return Err(PcsError::Evaluation);
+        }
+
+        // batch the row commitments with r as the prover batched the rows
+        let rows = 1 << (point.len() - col_num);
+        let commits = verifiers
+            .iter()
+            .flat_map(|x| x.commit.0.chunks(rows))
+            .collect::<Vec<_>>();
+        if commits.len() != verifiers.iter().map(|x| x.poly_num).sum::<usize>()
+            || commits.iter().any(|x| x.len() != rows)
+        {
+            return Err(PcsError::MalformedProof);
+        }
+        let mut powers = vec![F::from_int(1, &encoder)];
+        for _ in 1..commits.len() {
+            powers.push(F::mult(powers.last().unwrap(), &r, &encoder));
+        }
+        powers.reverse();
+        let batched = (0..rows)
+            .map(|i| {
+                let row = commits.iter().map(|x| &x[i]).collect::<Vec<_>>();
+                pp.multi_exp(&row, &powers, &encoder)
+            })
+            .collect::<Vec<_>>();
+        let weights = eq_evals(&point[col_num..], &encoder);
+        let expected = pp.multi_exp(&batched.iter().collect::<Vec<_>>(), &weights, &encoder);
+        if pp.commit_row(&message, &encoder) != expected {
+            return Err(PcsError::Evaluation);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arithmetic::{poly::MultiLinearPoly, test_utils::gen_params_n_ctx};
+    use seal_fhe::Plaintext;
+    use util::fiat_shamir::Transcript;
+
+    use crate::{
+        CommitmentSerde, HomomorphicCommitment, OpeningProof, PcsError,
+        PolynomialCommitmentScheme,
+    };
+
+    use super::{is_prime, HyraxCommitment, HyraxParam, HyraxProver};
+
+    type F = Plaintext;
+
+    #[test]
+    fn group() {
+        let (params, ctx) = gen_params_n_ctx();
+        let pp = HyraxParam::new(6, &ctx, &params, &mut rand::thread_rng());
+        let t = params.get_plain_modulus().value();
+        assert!(is_prime(pp.modulus));
+        assert_eq!((pp.modulus - 1) % t, 0);
+        assert!(pp
+            .generators
+            .iter()
+            .all(|&g| g != 1 && arithmetic::mul_group::pow_mod(g, t, pp.modulus) == 1));
+    }
+
+    #[test]
+    fn commit_open_verify() {
+        let (params, ctx) = gen_params_n_ctx();
+        let nv = 7;
+        let poly_num = 2;
+        let pp = HyraxProver::setup(nv, &ctx, &params, &mut rand::thread_rng());
+        let encoder = pp.encoder();
+        let polies = (0..poly_num)
+            .map(|_| {
+                (0..(1 << nv))
+                    .map(|_| F::random_pt(&encoder))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let point = (0..nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let evals = vec![polies
+            .iter()
+            .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &encoder))
+            .collect::<Vec<_>>()];
+
+        let (commitment, prover) = HyraxProver::commit(&pp, &polies);
+        let size = HyraxCommitment::size(nv, poly_num, &encoder);
+        let mut buffer = vec![0u8; size];
+        commitment.serialize_into(&mut buffer, &encoder);
+        let mut reader = OpeningProof {
+            bytes: buffer.clone(),
+        }
+        .reader();
+        assert_eq!(
+            HyraxCommitment::deserialize_from(&mut reader, nv, poly_num, &encoder),
+            commitment
+        );
+        let transcript = || {
+            let mut transcript = Transcript::new();
+            transcript.append_u8_slice(&buffer, size);
+            for i in &evals[0] {
+                transcript.append_f(i, &encoder);
+            }
+            transcript
+        };
+        let proof = HyraxProver::open(&pp, &[&prover], &point, &mut transcript());
+        let verify = |evals: &[Vec<F>], proof: &OpeningProof| {
+            HyraxProver::verify(&pp, &[&commitment], &point, evals, proof, &mut transcript())
+        };
+        assert_eq!(verify(&evals, &proof), Ok(()));
+
+        let mut tampered = evals.clone();
+        tampered[0][1] = F::add(&tampered[0][1], &F::from_int(1, &encoder), &encoder);
+        assert_eq!(verify(&tampered, &proof), Err(PcsError::Evaluation));
+
+        // a proof cut short is rejected rather than read past its end
+        let truncated = OpeningProof {
+            bytes: proof.bytes[..proof.bytes.len() - 1].to_vec(),
+        };
+        assert_eq!(verify(&evals, &truncated), Err(PcsError::MalformedProof));
+    }
+
+    #[test]
+    fn combine() {
+        let (params, ctx) = gen_params_n_ctx();
+        let nv = 6;
+        let poly_num = 2;
+        let pp = HyraxProver::setup(nv, &ctx, &params, &mut rand::thread_rng());
+        let encoder = pp.encoder();
+        let batches = (0..3)
+            .map(|_| {
+                (0..poly_num)
+                    .map(|_| {
+                        (0..(1 << nv))
+                            .map(|_| F::random_pt(&encoder))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let coeffs = (0..3).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let commits = batches
+            .iter()
+            .map(|x| HyraxProver::commit(&pp, x).0)
+            .collect::<Vec<_>>();
+
+        // the combination of the commitments commits to the combination of the polynomials
+        let combined = (0..poly_num)
+            .map(|j| {
+                (0..(1 << nv))
+                    .map(|i| {
+                        batches.iter().zip(coeffs.iter()).fold(
+                            F::from_int(0, &encoder),
+                            |acc, (b, c)| F::add(&acc, &F::mult(&b[j][i], c, &encoder), &encoder),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let (commitment, prover) = HyraxProver::commit(&pp, &combined);
+        assert_eq!(
+            HyraxProver::combine(&pp, &commits, &coeffs, &encoder),
+            commitment
+        );
+
+        let point = (0..nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let evals = batches
+            .iter()
+            .map(|b| {
+                b.iter()
+                    .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &encoder))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let proof = HyraxProver::open(&pp, &[&prover], &point, &mut Transcript::new());
+        assert_eq!(
+            HyraxProver::verify_combined(
+                &pp,
+                &commits,
+                &coeffs,
+                &point,
+                &evals,
+                &encoder,
+                &proof,
+                &mut Transcript::new()
+            ),
+            Ok(())
+        );
+    }
+}
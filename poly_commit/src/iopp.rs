@@ -0,0 +1,384 @@
+use std::{collections::HashMap, mem::size_of};
+
+use arithmetic::{
+    field::plaintext_size,
+    mul_group::{mul_mod, pow_mod, Radix2Group},
+};
+use seal_fhe::{BFVEncoder, Plaintext};
+use util::{
+    fiat_shamir::{Proof, Transcript},
+    merkle_tree::{MerkleTreeVerifier, HASH_SIZE},
+};
+
+use crate::{
+    deepfold::{DeepFoldParam, InterpolateValue, QueryResult},
+    ensure_remaining, PcsError,
+};
+
+type F = Plaintext;
+
+pub(crate) fn eval_univariate(coeffs: &[F], x: &F, encoder: &BFVEncoder) -> F {
+    coeffs.iter().rev().fold(F::from_int(0, encoder), |acc, c| {
+        F::add(&F::mult(&acc, x, encoder), c, encoder)
+    })
+}
+
+// one folding step: the codeword on `subgroup` to the codeword on its square, the even and
+// odd parts combined with `challenge`
+pub(crate) fn fold_codeword(
+    codeword: &[F],
+    subgroup: &Radix2Group,
+    challenge: &F,
+    encoder: &BFVEncoder,
+) -> Vec<F> {
+    let len = subgroup.size();
+    let inv_2 = F::inverse_2(encoder);
+    (0..len / 2)
+        .map(|i| {
+            let x = &codeword[i];
+            let nx = &codeword[i + len / 2];
+            let sum = F::add(x, nx, encoder);
+            let w_inv = F::from_int(subgroup.element_inv_at(i), encoder);
+            let diff = F::mult(&F::sub(x, nx, encoder), &w_inv, encoder);
+            let new_v = F::add(
+                &sum,
+                &F::mult(challenge, &F::sub(&diff, &sum, encoder), encoder),
+                encoder,
+            );
+            F::mult(&new_v, &inv_2, encoder)
+        })
+        .collect()
+}
+
+// folds the coset {w^(index + m * len)} of a codeword on subgroups[0] down to one value,
+// one challenge per variable
+pub(crate) fn fold_fiber(
+    mut values: Vec<F>,
+    index: usize,
+    subgroups: &[Radix2Group],
+    challenges: &[F],
+    encoder: &BFVEncoder,
+) -> F {
+    let len = subgroups[0].size() >> challenges.len();
+    let inv_2 = F::inverse_2(encoder);
+    for (s, challenge) in challenges.iter().enumerate() {
+        let half = values.len() / 2;
+        for m in 0..half {
+            let x = &values[m];
+            let nx = &values[m + half];
+            let sum = F::add(x, nx, encoder);
+            let w_inv = F::from_int(subgroups[s].element_inv_at(index + m * len), encoder);
+            let diff = F::mult(&F::sub(x, nx, encoder), &w_inv, encoder);
+            let new_v = F::add(
+                &sum,
+                &F::mult(challenge, &F::sub(&diff, &sum, encoder), encoder),
+                encoder,
+            );
+            values[m] = F::mult(&new_v, &inv_2, encoder);
+        }
+        values.truncate(half);
+    }
+    values.swap_remove(0)
+}
+
+// the query indices reduced to the leaves of the tree of `round`
+pub(crate) fn round_indices(pp: &DeepFoldParam, indices: &[usize], round: usize) -> Vec<usize> {
+    let leaf_num =
+        pp.mult_subgroups[round * pp.folding_factor].size() >> pp.round_folding(round);
+    let mut res = indices.iter().map(|v| v % leaf_num).collect::<Vec<_>>();
+    res.sort();
+    res.dedup();
+    res
+}
+
+// the consistency checks of the query phase: every queried fiber of round i folds to the
+// value opened at its index in folded[i], and the fibers of the last round to the final
+// polynomial
+pub(crate) fn check_folding(
+    pp: &DeepFoldParam,
+    indices: &[usize],
+    challenges: &[F],
+    fibers: &[HashMap<usize, F>],
+    folded: &[HashMap<usize, F>],
+    final_poly: &[F],
+    encoder: &BFVEncoder,
+) -> Result<(), PcsError> {
+    let modulus = pp.mult_subgroups[0].modulus();
+    for i in 0..pp.round_num() {
+        let start = i * pp.folding_factor;
+        let folding = pp.round_folding(i);
+        let leaf_num = pp.mult_subgroups[start].size() >> folding;
+        for j in round_indices(pp, indices, i) {
+            let values = (0..(1 << folding))
+                .map(|m| fibers[i][&(j + m * leaf_num)].clone())
+                .collect();
+            let new_v = fold_fiber(
+                values,
+                j,
+                &pp.mult_subgroups[start..],
+                &challenges[start..start + folding],
+                encoder,
+            );
+            let expected = if i < pp.round_num() - 1 {
+                folded[i][&j].clone()
+            } else {
+                let x = pow_mod(
+                    pp.mult_subgroups[start].element_at(j),
+                    1 << folding,
+                    modulus,
+                );
+                eval_univariate(final_poly, &F::from_int(x, encoder), encoder)
+            };
+            if new_v != expected {
+                return Err(PcsError::Folding { round: i });
+            }
+        }
+    }
+    Ok(())
+}
+
+// proximity test for a committed codeword: the codeword on pp.mult_subgroups[0] is close to
+// the evaluations of a polynomial of degree below 2^pp.variable_num. it folds like deepfold,
+// one challenge per variable and one merkle tree every pp.folding_factor variables, minus the
+// evaluation claims, and ends with the 2^pp.final_poly_log_size coefficients in the clear.
+// the caller puts the root of the codeword into the transcript
+pub struct Iopp;
+
+impl Iopp {
+    pub fn commit(pp: &DeepFoldParam, codeword: Vec<F>) -> InterpolateValue {
+        assert_eq!(codeword.len(), pp.mult_subgroups[0].size());
+        InterpolateValue::new(codeword, 1 << pp.round_folding(0), false, &pp.encoder())
+    }
+
+    // coefficients of the polynomial whose evaluations on the squares of `subgroup` are `values`
+    fn interpolate(
+        values: &[F],
+        subgroup: &Radix2Group,
+        coeff_num: usize,
+        encoder: &BFVEncoder,
+    ) -> Vec<F> {
+        let modulus = subgroup.modulus();
+        let mut coeffs = vec![F::from_int(0, encoder); coeff_num];
+        for (j, v) in values.iter().enumerate() {
+            let w_inv = subgroup.element_inv_at(j);
+            let x_inv = F::from_int(mul_mod(w_inv, w_inv, modulus), encoder);
+            let mut term = v.clone();
+            for c in coeffs.iter_mut() {
+                *c = F::add(c, &term, encoder);
+                term = F::mult(&term, &x_inv, encoder);
+            }
+        }
+        let inv_2 = F::inverse_2(encoder);
+        for _ in 0..values.len().ilog2() {
+            for c in coeffs.iter_mut() {
+                *c = F::mult(c, &inv_2, encoder);
+            }
+        }
+        coeffs
+    }
+
+    pub fn prove(pp: &DeepFoldParam, codeword: &InterpolateValue, transcript: &mut Transcript) {
+        assert!(!pp.hiding);
+        let encoder = pp.encoder();
+        let folded_var_num = pp.folded_var_num();
+        let mut interpolations = vec![];
+        let mut values = codeword.value.clone();
+        for i in 0..folded_var_num {
+            let challenge = transcript.challenge_f(&encoder);
+            values = fold_codeword(&values, &pp.mult_subgroups[i], &challenge, &encoder);
+            if i == folded_var_num - 1 {
+                let final_poly = Self::interpolate(
+                    &values,
+                    &pp.mult_subgroups[i],
+                    1 << pp.final_poly_log_size,
+                    &encoder,
+                );
+                for j in final_poly.iter() {
+                    transcript.append_f(j, &encoder);
+                }
+            } else if (i + 1) % pp.folding_factor == 0 {
+                let new_interpolation = InterpolateValue::new(
+                    values.clone(),
+                    1 << pp.round_folding((i + 1) / pp.folding_factor),
+                    false,
+                    &encoder,
+                );
+                transcript.append_u8_slice(&new_interpolation.commit(), HASH_SIZE);
+                interpolations.push(new_interpolation);
+            }
+        }
+        transcript.grind(pp.pow_bits);
+        let indices = transcript.challenge_usizes(pp.query_num);
+        for i in 0..pp.round_num() {
+            let leaf_indices = round_indices(pp, &indices, i);
+            let query = if i == 0 {
+                codeword.query(&leaf_indices)
+            } else {
+                interpolations[i - 1].query(&leaf_indices)
+            };
+            transcript.append_u8_slice(&query.0, query.0.len());
+            for j in query.1.iter() {
+                transcript.append_f(j, &encoder);
+            }
+        }
+    }
+
+    pub fn verify(
+        pp: &DeepFoldParam,
+        root: [u8; HASH_SIZE],
+        transcript: &mut Transcript,
+        proof: &mut Proof,
+    ) -> Result<(), PcsError> {
+        assert!(!pp.hiding);
+        let encoder = pp.encoder();
+        let f_size = plaintext_size(&encoder);
+        let folded_var_num = pp.folded_var_num();
+        let mut challenges = vec![];
+        let mut commits = vec![MerkleTreeVerifier::new(
+            pp.mult_subgroups[0].size() >> pp.round_folding(0),
+            root,
+        )];
+        let mut final_poly = vec![];
+        for i in 0..folded_var_num {
+            challenges.push(transcript.challenge_f(&encoder));
+            if i == folded_var_num - 1 {
+                ensure_remaining(proof, f_size << pp.final_poly_log_size)?;
+                final_poly = (0..(1 << pp.final_poly_log_size))
+                    .map(|_| proof.get_next_and_step(&encoder))
+                    .collect::<Vec<_>>();
+                for j in final_poly.iter() {
+                    transcript.append_f(j, &encoder);
+                }
+            } else if (i + 1) % pp.folding_factor == 0 {
+                ensure_remaining(proof, HASH_SIZE)?;
+                let merkle_root = proof.get_next_hash();
+                transcript.append_u8_slice(&merkle_root, HASH_SIZE);
+                commits.push(MerkleTreeVerifier::new(
+                    pp.mult_subgroups[i + 1].size()
+                        >> pp.round_folding((i + 1) / pp.folding_factor),
+                    merkle_root,
+                ));
+            }
+        }
+
+        if pp.pow_bits > 0 {
+            ensure_remaining(proof, size_of::<u64>())?;
+        }
+        if !transcript.verify_grind(proof, pp.pow_bits) {
+            return Err(PcsError::ProofOfWork);
+        }
+        let indices = transcript.challenge_usizes(pp.query_num);
+        let mut query_results: Vec<HashMap<usize, F>> = vec![];
+        for i in 0..pp.round_num() {
+            let arity = 1 << pp.round_folding(i);
+            let leaf_num = pp.mult_subgroups[i * pp.folding_factor].size() / arity;
+            let leaf_indices = round_indices(pp, &indices, i);
+            let query_num = leaf_indices.len();
+
+            let proof_length = commits[i].proof_length(&leaf_indices);
+            ensure_remaining(proof, proof_length + f_size * query_num * arity)?;
+            let proof_bytes = proof.get_next_slice(proof_length);
+            let proof_values = (0..query_num * arity)
+                .map(|_| proof.get_next_and_step(&encoder))
+                .collect::<Vec<_>>();
+            transcript.append_u8_slice(&proof_bytes, proof_bytes.len());
+            for j in &proof_values {
+                transcript.append_f(j, &encoder);
+            }
+            let query = QueryResult {
+                proof_bytes,
+                proof_values: (0..arity)
+                    .flat_map(|m| leaf_indices.iter().map(move |&x| x + m * leaf_num))
+                    .zip(proof_values)
+                    .collect(),
+                salts: vec![],
+            };
+            query.verify_merkle_tree(i, &leaf_indices, arity, &commits[i], &encoder)?;
+            query_results.push(query.proof_values);
+        }
+        check_folding(
+            pp,
+            &indices,
+            &challenges,
+            &query_results,
+            &query_results[1..],
+            &final_poly,
+            &encoder,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arithmetic::test_utils::gen_params_n_ctx;
+    use seal_fhe::Plaintext;
+    use util::{
+        fiat_shamir::{Proof, Transcript},
+        merkle_tree::HASH_SIZE,
+    };
+
+    use crate::{
+        deepfold::{DeepFoldConfig, DeepFoldParam},
+        PcsError,
+    };
+
+    use super::Iopp;
+
+    fn config(final_poly_log_size: usize, folding_factor: usize) -> DeepFoldConfig {
+        DeepFoldConfig {
+            log_blowup: 2,
+            security_bits: 32,
+            final_poly_log_size,
+            folding_factor,
+            ..DeepFoldConfig::new(8)
+        }
+    }
+
+    // a codeword of a random polynomial with `coeff_num` coefficients
+    fn prove(pp: &DeepFoldParam, coeff_num: usize) -> ([u8; HASH_SIZE], Proof) {
+        let encoder = pp.encoder();
+        let coeffs = (0..coeff_num)
+            .map(|_| Plaintext::random_pt(&encoder))
+            .collect::<Vec<_>>();
+        let codeword = Iopp::commit(pp, pp.mult_subgroups[0].fft(coeffs, &encoder));
+        let mut transcript = Transcript::new();
+        transcript.append_u8_slice(&codeword.commit(), HASH_SIZE);
+        Iopp::prove(pp, &codeword, &mut transcript);
+        (codeword.commit(), transcript.proof)
+    }
+
+    fn verify(pp: &DeepFoldParam, root: [u8; HASH_SIZE], mut proof: Proof) -> Result<(), PcsError> {
+        let mut transcript = Transcript::new();
+        transcript.append_u8_slice(&proof.get_next_hash(), HASH_SIZE);
+        Iopp::verify(pp, root, &mut transcript, &mut proof)
+    }
+
+    #[test]
+    fn low_degree() {
+        let (params, ctx) = gen_params_n_ctx();
+        for folding_factor in 1..=3 {
+            let pp = DeepFoldParam::new(config(2, folding_factor), &ctx, &params).0;
+            let (root, proof) = prove(&pp, 1 << 8);
+            assert_eq!(verify(&pp, root, proof.clone()), Ok(()));
+
+            let mut truncated = proof;
+            truncated.bytes.pop();
+            assert_eq!(verify(&pp, root, truncated), Err(PcsError::MalformedProof));
+        }
+        // a lower degree passes too
+        let pp = DeepFoldParam::new(config(0, 2), &ctx, &params).0;
+        let (root, proof) = prove(&pp, 1 << 5);
+        assert_eq!(verify(&pp, root, proof), Ok(()));
+    }
+
+    #[test]
+    fn high_degree() {
+        let (params, ctx) = gen_params_n_ctx();
+        let pp = DeepFoldParam::new(config(2, 2), &ctx, &params).0;
+        let (root, proof) = prove(&pp, 1 << 9);
+        assert!(matches!(
+            verify(&pp, root, proof),
+            Err(PcsError::Folding { .. })
+        ));
+    }
+}
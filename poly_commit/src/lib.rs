@@ -1,104 +1,288 @@
-use std::fmt::Debug;
+use std::{
+    fmt::{Debug, Display},
+    mem::size_of,
+};
 
 use arithmetic::field::Field;
-use seal_fhe::Plaintext;
-use util::fiat_shamir::{Proof, Transcript};
+use rand::RngCore;
+use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
+use util::{
+    fiat_shamir::{Proof, Transcript},
+    merkle_tree::HASH_SIZE,
+};
 
-// pub mod basefold;
-// pub mod deepfold;
+// no pairing-based backend (KZG, Zeromorph): the committed values are plaintext slots modulo
+// the plain modulus, and no pairing-friendly curve has a group of that order
+pub mod basefold;
+pub mod deepfold;
+// insecure, only to test the homomorphic commitment path against
+#[cfg(test)]
+mod hyrax;
+pub mod iopp;
+pub mod ligero;
+pub mod multi_point;
 pub mod nil;
-// pub mod shuffle;
+pub mod shuffle;
+pub mod soundness;
+pub mod whir;
 
 type F = Plaintext;
+// polynomial `poly_index` of commitment `commit_index`, opened at the last `var_num`
+// coordinates of the shared point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpeningClaim {
+    pub commit_index: usize,
+    pub poly_index: usize,
+    pub var_num: usize,
+}
+
 pub trait CommitmentSerde {
-    fn size(nv: usize, np: usize) -> usize;
+    fn size(nv: usize, np: usize, encoder: &BFVEncoder) -> usize;
+    fn serialize_into(&self, buffer: &mut [u8], encoder: &BFVEncoder);
+    fn deserialize_from(
+        proof: &mut Proof,
+        var_num: usize,
+        poly_num: usize,
+        encoder: &BFVEncoder,
+    ) -> Self;
+}
+
+// root of the merkle tree over the codewords, the commitment of the hash-based schemes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MerkleRoot(pub [u8; HASH_SIZE]);
+
+impl CommitmentSerde for MerkleRoot {
+    fn size(_nv: usize, _np: usize, _encoder: &BFVEncoder) -> usize {
+        HASH_SIZE
+    }
+
+    fn serialize_into(&self, buffer: &mut [u8], _encoder: &BFVEncoder) {
+        buffer.copy_from_slice(&self.0);
+    }
+
+    fn deserialize_from(
+        proof: &mut Proof,
+        _nv: usize,
+        _np: usize,
+        _encoder: &BFVEncoder,
+    ) -> Self {
+        Self(proof.get_next_hash())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcsError {
+    // the proof ends before the verifier has read every message
+    MalformedProof,
+    // an opened leaf does not hash to the root committed in this round
+    MerklePath { round: usize },
+    // folding the queried values disagrees with the values committed in the next round
+    Folding { round: usize },
+    // the opened polynomial does not evaluate to the claimed values
+    Evaluation,
+    // the nonce sent before the queries does not meet the grinding target
+    ProofOfWork,
+    // a sumcheck message does not match the running claim of this round
+    Sumcheck { round: usize },
+    // the claims do not fit the commitments they refer to
+    Claim,
+}
+
+impl Display for PcsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PcsError::MalformedProof => write!(f, "proof too short"),
+            PcsError::MerklePath { round } => write!(f, "merkle path rejected in round {}", round),
+            PcsError::Folding { round } => write!(f, "inconsistent folding in round {}", round),
+            PcsError::Evaluation => write!(f, "evaluation mismatch"),
+            PcsError::ProofOfWork => write!(f, "invalid proof of work"),
+            PcsError::Sumcheck { round } => write!(f, "sumcheck rejected in round {}", round),
+            PcsError::Claim => write!(f, "claim out of range"),
+        }
+    }
+}
+
+impl std::error::Error for PcsError {}
+
+pub(crate) fn ensure_remaining(proof: &Proof, len: usize) -> Result<(), PcsError> {
+    if proof.remaining() < len {
+        return Err(PcsError::MalformedProof);
+    }
+    Ok(())
+}
+
+pub trait ProofSerde: Sized {
+    fn size(&self) -> usize;
     fn serialize_into(&self, buffer: &mut [u8]);
-    fn deserialize_from(proof: &mut Proof, var_num: usize, poly_num: usize) -> Self;
+    fn deserialize_from(proof: &mut Proof) -> Option<Self>;
 }
 
-pub trait PolyCommitProver: Clone {
-    type Param: Clone;
-    type Commitment: Clone + Debug + Default + CommitmentSerde;
+// the messages of one opening, length-prefixed when serialized. they are still absorbed by
+// the transcript for fiat-shamir, but can be stored and sent apart from the protocol around it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpeningProof {
+    pub bytes: Vec<u8>,
+}
+
+impl OpeningProof {
+    // the messages as a stream for the verifier to read
+    pub fn reader(&self) -> Proof {
+        let mut proof = Proof::default();
+        proof.append_u8_slice(&self.bytes, self.bytes.len());
+        proof
+    }
+}
+
+impl ProofSerde for OpeningProof {
+    fn size(&self) -> usize {
+        size_of::<u64>() + self.bytes.len()
+    }
+
+    fn serialize_into(&self, buffer: &mut [u8]) {
+        buffer[..size_of::<u64>()].copy_from_slice(&(self.bytes.len() as u64).to_be_bytes());
+        buffer[size_of::<u64>()..self.size()].copy_from_slice(&self.bytes);
+    }
 
-    fn new(pp: &Self::Param, poly: &[Vec<F>]) -> Self;
-    fn commit(&self) -> Self::Commitment;
-    fn open(pp: &Self::Param, provers: Vec<&Self>, point: Vec<F>, transcript: &mut Transcript);
+    fn deserialize_from(proof: &mut Proof) -> Option<Self> {
+        if proof.remaining() < size_of::<u64>() {
+            return None;
+        }
+        let len = u64::from_be_bytes(proof.get_next_slice(size_of::<u64>()).try_into().unwrap());
+        if (proof.remaining() as u64) < len {
+            return None;
+        }
+        Some(OpeningProof {
+            bytes: proof.get_next_slice(len as usize),
+        })
+    }
 }
 
-pub trait PolyCommitVerifier: Clone {
+// parameters come from a setup, committing keeps what the prover needs to open later, and
+// an opening comes back as a proof. evals[i] holds the evaluations of every polynomial under
+// commits[i]
+pub trait PolynomialCommitmentScheme<'a> {
+    // commitments and openings reveal nothing about the polynomials beyond the claimed
+    // evaluations, which zero-knowledge provers rely on
+    const HIDING: bool = false;
+
     type Param: Clone;
     type Commitment: Clone + Debug + Default + CommitmentSerde;
+    type ProverData: Clone;
+    type Proof: Clone + Debug + ProofSerde;
 
-    fn new(pp: &Self::Param, commit: Self::Commitment, poly_num: usize) -> Self;
+    // the parameters keep the context to encode and decode plaintexts with
+    fn setup(
+        max_nv: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+        rng: &mut impl RngCore,
+    ) -> Self::Param;
+    // parameters for polynomials with fewer variables
+    fn trim(pp: &Self::Param, nv: usize) -> Self::Param;
+    fn commit(pp: &Self::Param, polies: &[Vec<F>]) -> (Self::Commitment, Self::ProverData);
+    fn open(
+        pp: &Self::Param,
+        data: &[&Self::ProverData],
+        point: &[F],
+        transcript: &mut Transcript,
+    ) -> Self::Proof;
     fn verify(
         pp: &Self::Param,
-        commits: Vec<&Self>,
-        point: Vec<F>,
-        evals: Vec<Vec<F>>,
+        commits: &[&Self::Commitment],
+        point: &[F],
+        evals: &[Vec<F>],
+        proof: &Self::Proof,
         transcript: &mut Transcript,
-        proof: &mut Proof,
-    ) -> bool;
+    ) -> Result<(), PcsError>;
+    // upper bound on the serialized proof size in bytes, for planning
+    fn proof_size(pp: &Self::Param, nv: usize, poly_num: usize) -> usize;
+
+    // one proof for claims over different numbers of variables, schemes without native support
+    // only take full-size claims
+    fn batch_open(
+        pp: &Self::Param,
+        data: &[&Self::ProverData],
+        claims: &[OpeningClaim],
+        point: &[F],
+        transcript: &mut Transcript,
+    ) -> Self::Proof {
+        assert!(claims.iter().all(|x| x.var_num == point.len()));
+        Self::open(pp, data, point, transcript)
+    }
+
+    // poly_nums[i] is the number of polynomials under commits[i], the claims cover each of
+    // them in order
+    fn batch_verify(
+        pp: &Self::Param,
+        commits: &[&Self::Commitment],
+        poly_nums: &[usize],
+        claims: &[OpeningClaim],
+        point: &[F],
+        evals: &[F],
+        proof: &Self::Proof,
+        transcript: &mut Transcript,
+    ) -> Result<(), PcsError> {
+        if commits.len() != poly_nums.len() || claims.len() != evals.len() {
+            return Err(PcsError::Claim);
+        }
+        let mut grouped_evals = vec![vec![]; commits.len()];
+        for (claim, eval) in claims.iter().zip(evals) {
+            if claim.var_num != point.len()
+                || claim.commit_index >= commits.len()
+                || claim.poly_index != grouped_evals[claim.commit_index].len()
+            {
+                return Err(PcsError::Claim);
+            }
+            grouped_evals[claim.commit_index].push(eval.clone());
+        }
+        if grouped_evals.iter().zip(poly_nums).any(|(x, &n)| x.len() != n) {
+            return Err(PcsError::Claim);
+        }
+        Self::verify(pp, commits, point, &grouped_evals, proof, transcript)
+    }
 }
 
-// mod tests {
-//     use arithmetic::{
-//         field::{
-//             goldilocks64::{Goldilocks64, Goldilocks64Ext},
-//             Field,
-//         },
-//         mul_group::Radix2Group,
-//         poly::MultiLinearPoly,
-//     };
-//     use util::fiat_shamir::Transcript;
-
-//     use crate::{
-//         deepfold::{DeepFoldParam, DeepFoldProver, DeepFoldVerifier, MerkleRoot},
-//         CommitmentSerde, PolyCommitProver, PolyCommitVerifier,
-//     };
-
-//     #[test]
-//     fn pc_commit_prove_verify() {
-//         let mut rng = rand::thread_rng();
-//         let mut transcript = Transcript::new();
-//         let poly_evals = (0..4096).map(|_| Goldilocks64::random(&mut rng)).collect();
-//         let point = (0..12)
-//             .map(|_| Goldilocks64Ext::random(&mut rng))
-//             .collect::<Vec<_>>();
-//         let eval = MultiLinearPoly::eval_multilinear(&poly_evals, &point);
-//         let mut mult_subgroups = vec![Radix2Group::<Goldilocks64>::new(15)];
-//         for i in 1..12 {
-//             mult_subgroups.push(mult_subgroups[i - 1].exp(2));
-//         }
-
-//         let pp = DeepFoldParam::<Goldilocks64Ext> {
-//             mult_subgroups,
-//             variable_num: 12,
-//             query_num: 30,
-//         };
-//         let prover = DeepFoldProver::new(&pp, &[poly_evals]);
-//         let commitment = prover.commit();
-//         let mut buffer = vec![0u8; MerkleRoot::size(12, 1)];
-//         commitment.serialize_into(&mut buffer);
-//         transcript.append_u8_slice(&buffer, MerkleRoot::size(12, 1));
-//         transcript.append_f(eval);
-//         DeepFoldProver::open(&pp, vec![&prover], point.clone(), &mut transcript);
-//         let mut proof = transcript.proof;
-
-//         let commitment = MerkleRoot::deserialize_from(&mut proof, 12, 1);
-//         let mut transcript = Transcript::new();
-//         let mut buffer = vec![0u8; MerkleRoot::size(12, 1)];
-//         commitment.serialize_into(&mut buffer);
-//         transcript.append_u8_slice(&buffer, MerkleRoot::size(12, 1));
-//         let verifier = DeepFoldVerifier::new(&pp, commitment, 1);
-//         let eval = vec![vec![proof.get_next_and_step()]];
-//         transcript.append_f(eval[0][0]);
-//         assert!(DeepFoldVerifier::verify(
-//             &pp,
-//             vec![&verifier],
-//             point,
-//             eval,
-//             &mut transcript,
-//             &mut proof
-//         ));
-//     }
-// }
+// commitments that are linear in the committed polynomials, so the commitment to
+// sum_k coeffs[k] * f_k follows from the commitments to the f_k without recommitting.
+// every commitment holds the same number of polynomials, combined index by index
+pub trait HomomorphicCommitment<'a>: PolynomialCommitmentScheme<'a> {
+    fn combine(
+        pp: &Self::Param,
+        commits: &[Self::Commitment],
+        coeffs: &[F],
+        encoder: &BFVEncoder,
+    ) -> Self::Commitment;
+
+    // one opening check for the combination, evals[k] being the evaluations under commits[k]
+    fn verify_combined(
+        pp: &Self::Param,
+        commits: &[Self::Commitment],
+        coeffs: &[F],
+        point: &[F],
+        evals: &[Vec<F>],
+        encoder: &BFVEncoder,
+        proof: &Self::Proof,
+        transcript: &mut Transcript,
+    ) -> Result<(), PcsError> {
+        if commits.is_empty()
+            || commits.len() != coeffs.len()
+            || commits.len() != evals.len()
+            || evals.iter().any(|x| x.len() != evals[0].len())
+        {
+            return Err(PcsError::Claim);
+        }
+        let poly_num = evals[0].len();
+        let evals = (0..poly_num)
+            .map(|j| {
+                coeffs
+                    .iter()
+                    .zip(evals.iter())
+                    .fold(F::from_int(0, encoder), |acc, (c, e)| {
+                        F::add(&acc, &F::mult(c, &e[j], encoder), encoder)
+                    })
+            })
+            .collect();
+        let commit = Self::combine(pp, commits, coeffs, encoder);
+        Self::verify(pp, &[&commit], point, &[evals], proof, transcript)
+    }
+}
@@ -0,0 +1,633 @@
+use std::marker::PhantomData;
+
+use arithmetic::{
+    field::{as_bytes_vec, plaintext_size},
+    mul_group::Radix2Group,
+    poly::MultiLinearPoly,
+};
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
+use util::{
+    fiat_shamir::Proof,
+    merkle_tree::{MerkleTreeProver, MerkleTreeVerifier, HASH_SIZE},
+};
+
+use crate::{
+    ensure_remaining, MerkleRoot, OpeningProof, PcsError, PolynomialCommitmentScheme,
+    ProofSerde, Transcript,
+};
+
+type F = Plaintext;
+
+// a code linear over the integers modulo the plain modulus, applied slot-wise to plaintexts
+pub trait LinearCode: Clone {
+    // the code the scheme's setup uses for messages of 2^log_message_len plaintexts
+    fn setup(log_message_len: usize, modulus: u64, rng: &mut impl RngCore) -> Self;
+    fn message_len(&self) -> usize;
+    fn codeword_len(&self) -> usize;
+    fn encode(&self, message: &[F], encoder: &BFVEncoder) -> Vec<F>;
+    // minimum distance over the codeword length, the query count follows from it
+    fn relative_distance(&self) -> f64;
+}
+
+#[derive(Debug, Clone)]
+pub struct ReedSolomonCode {
+    message_len: usize,
+    group: Radix2Group,
+}
+
+impl ReedSolomonCode {
+    pub fn new(log_message_len: usize, log_blowup: usize, modulus: u64) -> Self {
+        ReedSolomonCode {
+            message_len: 1 << log_message_len,
+            group: Radix2Group::new((log_message_len + log_blowup) as u32, modulus),
+        }
+    }
+}
+
+impl LinearCode for ReedSolomonCode {
+    // rate 1/4
+    fn setup(log_message_len: usize, modulus: u64, _rng: &mut impl RngCore) -> Self {
+        Self::new(log_message_len, 2, modulus)
+    }
+
+    fn message_len(&self) -> usize {
+        self.message_len
+    }
+
+    fn codeword_len(&self) -> usize {
+        self.group.size()
+    }
+
+    fn encode(&self, message: &[F], encoder: &BFVEncoder) -> Vec<F> {
+        assert_eq!(message.len(), self.message_len);
+        self.group.fft(message.to_vec(), encoder)
+    }
+
+    // maximum distance separable
+    fn relative_distance(&self) -> f64 {
+        1.0 - self.message_len as f64 / self.codeword_len() as f64
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SparseMatrix {
+    cols: usize,
+    rows: Vec<Vec<(usize, u64)>>,
+}
+
+impl SparseMatrix {
+    fn random(rows: usize, cols: usize, degree: usize, modulus: u64, rng: &mut StdRng) -> Self {
+        SparseMatrix {
+            cols,
+            rows: (0..rows)
+                .map(|_| {
+                    (0..degree)
+                        .map(|_| (rng.gen_range(0..cols), rng.gen_range(0..modulus)))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    fn dense(rows: usize, cols: usize, modulus: u64, rng: &mut StdRng) -> Self {
+        SparseMatrix {
+            cols,
+            rows: (0..rows)
+                .map(|_| (0..cols).map(|j| (j, rng.gen_range(0..modulus))).collect())
+                .collect(),
+        }
+    }
+
+    fn left_mul(&self, v: &[F], encoder: &BFVEncoder) -> Vec<F> {
+        let mut res = vec![F::from_int(0, encoder); self.cols];
+        for (x, row) in v.iter().zip(self.rows.iter()) {
+            for &(j, c) in row {
+                let term = F::mult(x, &F::from_int(c, encoder), encoder);
+                res[j] = F::add(&res[j], &term, encoder);
+            }
+        }
+        res
+    }
+}
+
+const EXPANDER_BASE_LEN: usize = 32;
+const EXPANDER_DEGREE: usize = 10;
+// heuristic: the distance of these random sparse matrices is not proven, this is about what
+// brakedown's analysis gives for its rate 1/2 parameters
+const EXPANDER_DISTANCE: f64 = 0.04;
+
+// rate 1/2 recursive expander code in the style of Brakedown, needs no smooth subgroup:
+// enc(x) = (x, z, zB) with z = enc(xA), A: n * n/4 and B: n/2 * n/2 sparse.
+// its relative distance is far below that of Reed-Solomon, so it needs more queries.
+#[derive(Debug, Clone)]
+pub struct ExpanderCode {
+    message_len: usize,
+    a: Vec<SparseMatrix>,
+    b: Vec<SparseMatrix>,
+    base: SparseMatrix,
+}
+
+impl ExpanderCode {
+    pub fn new(log_message_len: usize, seed: u64, modulus: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut a = vec![];
+        let mut b = vec![];
+        let mut len = 1 << log_message_len;
+        while len > EXPANDER_BASE_LEN {
+            a.push(SparseMatrix::random(len, len / 4, EXPANDER_DEGREE, modulus, &mut rng));
+            b.push(SparseMatrix::random(len / 2, len / 2, EXPANDER_DEGREE, modulus, &mut rng));
+            len /= 4;
+        }
+        ExpanderCode {
+            message_len: 1 << log_message_len,
+            a,
+            b,
+            base: SparseMatrix::dense(len, len, modulus, &mut rng),
+        }
+    }
+
+    fn encode_level(&self, level: usize, message: &[F], encoder: &BFVEncoder) -> Vec<F> {
+        let mut res = message.to_vec();
+        if level == self.a.len() {
+            res.append(&mut self.base.left_mul(message, encoder));
+        } else {
+            let mut z =
+                self.encode_level(level + 1, &self.a[level].left_mul(message, encoder), encoder);
+            let mut v = self.b[level].left_mul(&z, encoder);
+            res.append(&mut z);
+            res.append(&mut v);
+        }
+        res
+    }
+}
+
+impl LinearCode for ExpanderCode {
+    // the matrices are sampled from a seed drawn from `rng`
+    fn setup(log_message_len: usize, modulus: u64, rng: &mut impl RngCore) -> Self {
+        Self::new(log_message_len, rng.gen(), modulus)
+    }
+
+    fn message_len(&self) -> usize {
+        self.message_len
+    }
+
+    fn codeword_len(&self) -> usize {
+        self.message_len * 2
+    }
+
+    fn encode(&self, message: &[F], encoder: &BFVEncoder) -> Vec<F> {
+        assert_eq!(message.len(), self.message_len);
+        self.encode_level(0, message, encoder)
+    }
+
+    fn relative_distance(&self) -> f64 {
+        EXPANDER_DISTANCE
+    }
+}
+
+// evaluations are laid out as a (2^nv / cols) * cols matrix, the low variables index columns
+#[derive(Debug, Clone)]
+pub struct LigeroParam<'a, C> {
+    pub code: C,
+    pub variable_num: usize,
+    pub query_num: usize,
+    pub ctx: &'a Context,
+    pub params: &'a EncryptionParameters,
+}
+
+impl<'a, C: LinearCode> LigeroParam<'a, C> {
+    // a query catches a word a third of the distance away from the code with probability at
+    // least a third of the relative distance
+    pub fn new(
+        code: C,
+        variable_num: usize,
+        security_bits: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+    ) -> Self {
+        let query_bits = -(1.0 - code.relative_distance() / 3.0).log2();
+        LigeroParam {
+            code,
+            variable_num,
+            query_num: (security_bits as f64 / query_bits).ceil() as usize,
+            ctx,
+            params,
+        }
+    }
+}
+
+impl<'a, C> LigeroParam<'a, C> {
+    pub fn encoder(&self) -> BFVEncoder<'a> {
+        BFVEncoder::new(self.ctx, self.params).unwrap()
+    }
+}
+
+#[derive(Clone)]
+pub struct LigeroProver<'a, C> {
+    poly: Vec<Vec<F>>,
+    codewords: Vec<Vec<F>>,
+    merkle_tree: MerkleTreeProver,
+    _data: PhantomData<(&'a Context, C)>,
+}
+
+impl<'a, C: LinearCode> LigeroProver<'a, C> {
+    fn combine_rows(matrix: &[F], cols: usize, point: &[F], encoder: &BFVEncoder) -> Vec<F> {
+        let mut rows = matrix.chunks(cols).map(|x| x.to_vec()).collect::<Vec<_>>();
+        for p in point {
+            let new_len = rows.len() / 2;
+            for i in 0..new_len {
+                rows[i] = rows[i * 2]
+                    .iter()
+                    .zip(rows[i * 2 + 1].iter())
+                    .map(|(l, h)| {
+                        let diff = F::sub(h, l, encoder);
+                        F::add(l, &F::mult(&diff, p, encoder), encoder)
+                    })
+                    .collect();
+            }
+            rows.truncate(new_len);
+        }
+        rows.pop().unwrap()
+    }
+
+    fn query(&self, leaf_indices: &Vec<usize>) -> (Vec<u8>, Vec<F>) {
+        let len = self.merkle_tree.leave_num();
+        let rows = self.codewords[0].len() / len;
+        let proof_values = leaf_indices
+            .iter()
+            .flat_map(|&j| {
+                self.codewords
+                    .iter()
+                    .flat_map(move |c| (0..rows).map(move |i| c[i * len + j].clone()))
+            })
+            .collect();
+        let proof_bytes = self.merkle_tree.open(leaf_indices);
+        (proof_bytes, proof_values)
+    }
+}
+
+impl<'a, C: LinearCode> LigeroProver<'a, C> {
+    pub fn new(pp: &LigeroParam<'a, C>, poly: &[Vec<F>]) -> Self {
+        let encoder = pp.encoder();
+        let cols = pp.code.message_len();
+        let len = pp.code.codeword_len();
+        let rows = poly[0].len() / cols;
+        let codewords = poly
+            .iter()
+            .map(|x| {
+                x.chunks(cols)
+                    .flat_map(|row| pp.code.encode(row, &encoder))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let merkle_tree = MerkleTreeProver::new(
+            (0..len)
+                .map(|j| {
+                    as_bytes_vec(
+                        &codewords
+                            .iter()
+                            .flat_map(|c| (0..rows).map(move |i| c[i * len + j].clone()))
+                            .collect::<Vec<_>>(),
+                        &encoder,
+                    )
+                })
+                .collect(),
+        );
+        LigeroProver {
+            poly: poly.to_vec(),
+            codewords,
+            merkle_tree,
+            _data: PhantomData,
+        }
+    }
+
+    pub fn root(&self) -> MerkleRoot {
+        MerkleRoot(self.merkle_tree.commit())
+    }
+}
+
+#[derive(Clone)]
+pub struct LigeroVerifier<'a, C> {
+    commit: MerkleTreeVerifier,
+    poly_num: usize,
+    _data: PhantomData<(&'a Context, C)>,
+}
+
+impl<'a, C: LinearCode> LigeroVerifier<'a, C> {
+    pub fn new(pp: &LigeroParam<'a, C>, commit: MerkleRoot, poly_num: usize) -> Self {
+        LigeroVerifier {
+            commit: MerkleTreeVerifier::new(pp.code.codeword_len(), commit.0),
+            poly_num,
+            _data: PhantomData,
+        }
+    }
+
+    // verify reporting why a proof is rejected; a short proof is rejected, not a panic
+    pub fn check(
+        pp: &LigeroParam<'a, C>,
+        verifiers: &[&Self],
+        point: Vec<F>,
+        evals: Vec<Vec<F>>,
+        transcript: &mut Transcript,
+        proof: &mut Proof,
+    ) -> Result<(), PcsError> {
+        let encoder = pp.encoder();
+        let f_size = plaintext_size(&encoder);
+        let r = transcript.challenge_f(&encoder);
+        let mut eval = F::from_int(0, &encoder);
+        for i in evals {
+            for j in i {
+                eval = F::add(&F::mult(&eval, &r, &encoder), &j, &encoder);
+            }
+        }
+        let cols = pp.code.message_len();
+        let col_var_num = cols.ilog2() as usize;
+        let rows = 1 << (pp.variable_num - col_var_num);
+        let proximity_point = (col_var_num..pp.variable_num)
+            .map(|_| transcript.challenge_f(&encoder))
+            .collect::<Vec<F>>();
+        let row_points = [proximity_point, point[col_var_num..].to_vec()];
+        let mut messages = vec![];
+        for _ in 0..row_points.len() {
+            ensure_remaining(proof, f_size * cols)?;
+            let message = (0..cols)
+                .map(|_| proof.get_next_and_step(&encoder))
+                .collect::<Vec<_>>();
+            for x in &message {
+                transcript.append_f(x, &encoder);
+            }
+            messages.push(message);
+        }
+        if MultiLinearPoly::eval_multilinear_ext(&messages[1], &point[..col_var_num], &encoder)
+            != eval
+        {
+            return Err(PcsError::Evaluation);
+        }
+        let codewords = messages
+            .iter()
+            .map(|x| pp.code.encode(x, &encoder))
+            .collect::<Vec<_>>();
+
+        let len = pp.code.codeword_len();
+        let mut leaf_indices = transcript
+            .challenge_usizes(pp.query_num)
+            .into_iter()
+            .map(|x| x % len)
+            .collect::<Vec<_>>();
+        leaf_indices.sort();
+        leaf_indices.dedup();
+        let mut columns = vec![vec![F::from_int(0, &encoder); rows]; leaf_indices.len()];
+        for verifier in verifiers {
+            let leaf_size = verifier.poly_num * rows;
+            let proof_length = verifier.commit.proof_length(&leaf_indices);
+            ensure_remaining(proof, proof_length + f_size * leaf_indices.len() * leaf_size)?;
+            let proof_bytes = proof.get_next_slice(proof_length);
+            let proof_values = (0..leaf_indices.len() * leaf_size)
+                .map(|_| proof.get_next_and_step(&encoder))
+                .collect::<Vec<_>>();
+            transcript.append_u8_slice(&proof_bytes, proof_bytes.len());
+            for k in &proof_values {
+                transcript.append_f(k, &encoder);
+            }
+            let leaves = proof_values
+                .chunks(leaf_size)
+                .map(|x| as_bytes_vec(x, &encoder))
+                .collect();
+            if !verifier.commit.verify(proof_bytes, &leaf_indices, &leaves) {
+                return Err(PcsError::MerklePath { round: 0 });
+            }
+            for (column, leaf) in columns.iter_mut().zip(proof_values.chunks(leaf_size)) {
+                for poly in leaf.chunks(rows) {
+                    for (c, x) in column.iter_mut().zip(poly.iter()) {
+                        *c = F::add(&F::mult(c, &r, &encoder), x, &encoder);
+                    }
+                }
+            }
+        }
+        for (j, column) in leaf_indices.iter().zip(columns.iter()) {
+            for (row_point, codeword) in row_points.iter().zip(codewords.iter()) {
+                if MultiLinearPoly::eval_multilinear_ext(column, row_point, &encoder)
+                    != codeword[*j]
+                {
+                    return Err(PcsError::Folding { round: 0 });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, C: LinearCode> PolynomialCommitmentScheme<'a> for LigeroProver<'a, C> {
+    type Param = LigeroParam<'a, C>;
+    type Commitment = MerkleRoot;
+    type ProverData = Self;
+    type Proof = OpeningProof;
+
+    // a square matrix for `max_nv` variables and 100 bits
+    fn setup(
+        max_nv: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+        rng: &mut impl RngCore,
+    ) -> Self::Param {
+        let modulus = params.get_plain_modulus().value();
+        let code = C::setup(max_nv.div_ceil(2), modulus, rng);
+        LigeroParam::new(code, max_nv, 100, ctx, params)
+    }
+
+    // the code fixes the row length, so fewer variables means fewer rows
+    fn trim(pp: &Self::Param, nv: usize) -> Self::Param {
+        assert!(1 << nv >= pp.code.message_len());
+        LigeroParam {
+            variable_num: nv,
+            ..pp.clone()
+        }
+    }
+
+    fn commit(pp: &Self::Param, polies: &[Vec<F>]) -> (Self::Commitment, Self) {
+        let prover = Self::new(pp, polies);
+        (prover.root(), prover)
+    }
+
+    fn open(
+        pp: &Self::Param,
+        provers: &[&Self],
+        point: &[F],
+        transcript: &mut Transcript,
+    ) -> Self::Proof {
+        let start = transcript.proof.bytes.len();
+        let encoder = pp.encoder();
+        let r = transcript.challenge_f(&encoder);
+        let mut matrix = provers[0].poly[0].clone();
+        for poly in provers.iter().flat_map(|x| x.poly.iter()).skip(1) {
+            for (m, x) in matrix.iter_mut().zip(poly.iter()) {
+                *m = F::add(&F::mult(m, &r, &encoder), x, &encoder);
+            }
+        }
+        let cols = pp.code.message_len();
+        let col_var_num = cols.ilog2() as usize;
+        let proximity_point = (col_var_num..pp.variable_num)
+            .map(|_| transcript.challenge_f(&encoder))
+            .collect::<Vec<F>>();
+        for row_point in [proximity_point, point[col_var_num..].to_vec()] {
+            for x in Self::combine_rows(&matrix, cols, &row_point, &encoder).iter() {
+                transcript.append_f(x, &encoder);
+            }
+        }
+
+        let len = pp.code.codeword_len();
+        let mut leaf_indices = transcript
+            .challenge_usizes(pp.query_num)
+            .into_iter()
+            .map(|x| x % len)
+            .collect::<Vec<_>>();
+        leaf_indices.sort();
+        leaf_indices.dedup();
+        for prover in provers.iter() {
+            let query = prover.query(&leaf_indices);
+            transcript.append_u8_slice(&query.0, query.0.len());
+            for j in query.1.iter() {
+                transcript.append_f(j, &encoder);
+            }
+        }
+        OpeningProof {
+            bytes: transcript.proof.bytes[start..].to_vec(),
+        }
+    }
+
+    fn verify(
+        pp: &Self::Param,
+        commits: &[&Self::Commitment],
+        point: &[F],
+        evals: &[Vec<F>],
+        proof: &Self::Proof,
+        transcript: &mut Transcript,
+    ) -> Result<(), PcsError> {
+        if commits.len() != evals.len() || point.len() != pp.variable_num {
+            return Err(PcsError::Claim);
+        }
+        let verifiers = commits
+            .iter()
+            .zip(evals.iter())
+            .map(|(commit, evals)| LigeroVerifier::new(pp, (*commit).clone(), evals.len()))
+            .collect::<Vec<_>>();
+        let mut reader = proof.reader();
+        LigeroVerifier::check(
+            pp,
+            &verifiers.iter().collect::<Vec<_>>(),
+            point.to_vec(),
+            evals.to_vec(),
+            transcript,
+            &mut reader,
+        )?;
+        if reader.remaining() > 0 {
+            return Err(PcsError::MalformedProof);
+        }
+        Ok(())
+    }
+
+    // the two combined rows, then a column of every polynomial and a merkle path per query
+    fn proof_size(pp: &Self::Param, nv: usize, poly_num: usize) -> usize {
+        let pp = Self::trim(pp, nv);
+        let f_size = plaintext_size(&pp.encoder());
+        let cols = pp.code.message_len();
+        let len = pp.code.codeword_len();
+        let rows = (1 << nv) / cols;
+        let path_size = HASH_SIZE * len.next_power_of_two().ilog2() as usize;
+        let query_num = pp.query_num.min(len);
+        OpeningProof::default().size()
+            + 2 * cols * f_size
+            + query_num * (f_size * rows * poly_num + path_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arithmetic::{poly::MultiLinearPoly, test_utils::gen_params_n_ctx};
+    use seal_fhe::Plaintext;
+    use util::fiat_shamir::Transcript;
+
+    use crate::{
+        CommitmentSerde, MerkleRoot, OpeningProof, PcsError, PolynomialCommitmentScheme,
+        ProofSerde,
+    };
+
+    use super::{ExpanderCode, LigeroParam, LigeroProver, LinearCode, ReedSolomonCode};
+
+    type F = Plaintext;
+
+    fn commit_open_verify<C: LinearCode>(pp: LigeroParam<C>, poly_num: usize) {
+        let encoder = pp.encoder();
+        let nv = pp.variable_num;
+        let polies = (0..poly_num)
+            .map(|_| {
+                (0..(1 << nv))
+                    .map(|_| F::random_pt(&encoder))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let point = (0..nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let evals = vec![polies
+            .iter()
+            .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &encoder))
+            .collect::<Vec<_>>()];
+
+        let (commitment, prover) = LigeroProver::commit(&pp, &polies);
+        let size = MerkleRoot::size(nv, poly_num, &encoder);
+        let mut buffer = vec![0u8; size];
+        commitment.serialize_into(&mut buffer, &encoder);
+        let transcript = || {
+            let mut transcript = Transcript::new();
+            transcript.append_u8_slice(&buffer, size);
+            for i in &evals[0] {
+                transcript.append_f(i, &encoder);
+            }
+            transcript
+        };
+        let proof = LigeroProver::open(&pp, &[&prover], &point, &mut transcript());
+        assert!(proof.size() <= LigeroProver::proof_size(&pp, nv, poly_num));
+        let verify = |proof: &OpeningProof| {
+            LigeroProver::verify(
+                &pp,
+                &[&commitment],
+                &point,
+                &evals,
+                proof,
+                &mut transcript(),
+            )
+        };
+        assert_eq!(verify(&proof), Ok(()));
+
+        // a proof cut short is rejected rather than read past its end
+        let truncated = OpeningProof {
+            bytes: proof.bytes[..proof.bytes.len() - 1].to_vec(),
+        };
+        assert_eq!(verify(&truncated), Err(PcsError::MalformedProof));
+    }
+
+    #[test]
+    fn reed_solomon() {
+        let (params, ctx) = gen_params_n_ctx();
+        let modulus = params.get_plain_modulus().value();
+        let pp = LigeroParam::new(ReedSolomonCode::new(4, 2, modulus), 8, 32, &ctx, &params);
+        commit_open_verify(pp, 3);
+    }
+
+    #[test]
+    fn expander() {
+        let (params, ctx) = gen_params_n_ctx();
+        let modulus = params.get_plain_modulus().value();
+        let pp = LigeroParam::new(ExpanderCode::new(7, 0, modulus), 9, 4, &ctx, &params);
+        commit_open_verify(pp, 2);
+    }
+
+    #[test]
+    fn scheme() {
+        type Scheme<'a> = LigeroProver<'a, ReedSolomonCode>;
+        let (params, ctx) = gen_params_n_ctx();
+        let pp = Scheme::setup(9, &ctx, &params, &mut rand::thread_rng());
+        assert_eq!(pp.code.message_len(), 1 << 5);
+        commit_open_verify(Scheme::trim(&pp, 7), 2);
+    }
+}
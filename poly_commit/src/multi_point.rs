@@ -0,0 +1,329 @@
+use arithmetic::{field::plaintext_size, poly::MultiLinearPoly};
+use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
+use util::fiat_shamir::{Proof, Transcript};
+
+use crate::{ensure_remaining, PcsError, PolynomialCommitmentScheme};
+
+type F = Plaintext;
+
+// polynomial `poly_index` of commitment `commit_index` evaluated at `points[point_index]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointClaim {
+    pub commit_index: usize,
+    pub poly_index: usize,
+    pub point_index: usize,
+}
+
+// messages of the reduction sumcheck: p(0), p(1), p(2) per round, then every committed
+// polynomial at the reduced point. they go through the transcript ahead of the opening
+#[derive(Debug, Clone)]
+pub struct ReducedOpening {
+    pub total_sums: Vec<[F; 3]>,
+    pub evals: Vec<Vec<F>>,
+}
+
+// sum_i eta^i * eq(z_i, X) * f_i(X) over the hypercube turns claims f_i(z_i) = y_i at
+// distinct points into claims at the single sumcheck point, which one PC::open covers.
+// eta and the sumcheck challenges come from the transcript, which should have absorbed the
+// claimed evaluations already
+pub struct MultiPointOpening;
+
+impl MultiPointOpening {
+    fn batching_powers(eta: &F, n: usize, encoder: &BFVEncoder) -> Vec<F> {
+        let mut powers = vec![F::from_int(1, encoder)];
+        for i in 1..n {
+            powers.push(F::mult(&powers[i - 1], eta, encoder));
+        }
+        powers
+    }
+
+    fn fold_next_domain(poly_evals: &mut Vec<F>, m: usize, challenge: &F, encoder: &BFVEncoder) {
+        for j in 0..m {
+            let diff = F::sub(&poly_evals[j * 2 + 1], &poly_evals[j * 2], encoder);
+            poly_evals[j] = F::add(
+                &poly_evals[j * 2],
+                &F::mult(&diff, challenge, encoder),
+                encoder,
+            );
+        }
+        poly_evals.truncate(m)
+    }
+
+    // quadratic through (0, v[0]), (1, v[1]), (2, v[2])
+    fn extrapolate(v: &[F; 3], x: &F, encoder: &BFVEncoder) -> F {
+        let d1 = F::sub(&v[1], &v[0], encoder);
+        let d2 = F::sub(&F::sub(&v[2], &v[1], encoder), &d1, encoder);
+        let x_choose_2 = F::mult(
+            &F::mult(x, &F::sub(x, &F::from_int(1, encoder), encoder), encoder),
+            &F::inverse_2(encoder),
+            encoder,
+        );
+        F::add(
+            &F::add(&v[0], &F::mult(x, &d1, encoder), encoder),
+            &F::mult(&x_choose_2, &d2, encoder),
+            encoder,
+        )
+    }
+
+    pub fn open<'a, PC: PolynomialCommitmentScheme<'a>>(
+        pp: &PC::Param,
+        data: &[&PC::ProverData],
+        polies: &[Vec<Vec<F>>],
+        claims: &[PointClaim],
+        points: &[Vec<F>],
+        params: &'a EncryptionParameters,
+        ctx: &'a Context,
+        encoder: &BFVEncoder,
+        transcript: &mut Transcript,
+    ) -> (Vec<F>, ReducedOpening, PC::Proof) {
+        assert_eq!(data.len(), polies.len());
+        let var_num = points[0].len();
+        let eta = transcript.challenge_f(encoder);
+        let weights = Self::batching_powers(&eta, claims.len(), encoder);
+
+        // claims sharing a point share one eq table: g_z = sum_{i: z_i = z} eta^i * f_i
+        let mut eqs = vec![];
+        let mut combined = vec![];
+        for (point_index, point) in points.iter().enumerate() {
+            assert_eq!(point.len(), var_num);
+            let mut g = vec![F::from_int(0, encoder); 1 << var_num];
+            let mut used = false;
+            for (claim, weight) in claims.iter().zip(weights.iter()) {
+                if claim.point_index != point_index {
+                    continue;
+                }
+                used = true;
+                let f = &polies[claim.commit_index][claim.poly_index];
+                for (g, f) in g.iter_mut().zip(f.iter()) {
+                    *g = F::add(g, &F::mult(weight, f, encoder), encoder);
+                }
+            }
+            if used {
+                eqs.push(MultiLinearPoly::new_eq(point, params, ctx).evals);
+                combined.push(g);
+            }
+        }
+
+        let mut new_point = vec![];
+        let mut total_sums = vec![];
+        for i in 0..var_num {
+            let m = 1usize << (var_num - i);
+            let mut sums = [0; 3].map(|_| F::from_int(0, encoder));
+            for (eq, g) in eqs.iter().zip(combined.iter()) {
+                for x in (0..m).step_by(2) {
+                    let e2 = F::sub(&F::add(&eq[x + 1], &eq[x + 1], encoder), &eq[x], encoder);
+                    let g2 = F::sub(&F::add(&g[x + 1], &g[x + 1], encoder), &g[x], encoder);
+                    sums[0] = F::add(&sums[0], &F::mult(&eq[x], &g[x], encoder), encoder);
+                    sums[1] = F::add(&sums[1], &F::mult(&eq[x + 1], &g[x + 1], encoder), encoder);
+                    sums[2] = F::add(&sums[2], &F::mult(&e2, &g2, encoder), encoder);
+                }
+            }
+            for x in sums.iter() {
+                transcript.append_f(x, encoder);
+            }
+            total_sums.push(sums);
+            let challenge = transcript.challenge_f(encoder);
+            for j in eqs.iter_mut().chain(combined.iter_mut()) {
+                Self::fold_next_domain(j, m / 2, &challenge, encoder);
+            }
+            new_point.push(challenge);
+        }
+
+        let evals = polies
+            .iter()
+            .map(|x| {
+                x.iter()
+                    .map(|f| MultiLinearPoly::eval_multilinear(f, &new_point, encoder))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for x in evals.iter().flatten() {
+            transcript.append_f(x, encoder);
+        }
+        let proof = PC::open(pp, data, &new_point, transcript);
+        (new_point, ReducedOpening { total_sums, evals }, proof)
+    }
+
+    // `poly_nums[k]` is the number of polynomials under `commits[k]`. the reduction messages
+    // are read from `proof` and the opening at the reduced point is `opening`
+    pub fn verify<'a, PC: PolynomialCommitmentScheme<'a>>(
+        pp: &PC::Param,
+        commits: &[&PC::Commitment],
+        poly_nums: &[usize],
+        claims: &[PointClaim],
+        points: &[Vec<F>],
+        evals: &[F],
+        encoder: &BFVEncoder,
+        transcript: &mut Transcript,
+        proof: &mut Proof,
+        opening: &PC::Proof,
+    ) -> Result<(), PcsError> {
+        let var_num = points[0].len();
+        if claims.len() != evals.len()
+            || poly_nums.len() != commits.len()
+            || points.iter().any(|x| x.len() != var_num)
+            || claims.iter().any(|x| {
+                x.commit_index >= commits.len()
+                    || x.poly_index >= poly_nums[x.commit_index]
+                    || x.point_index >= points.len()
+            })
+        {
+            return Err(PcsError::Claim);
+        }
+        let f_size = plaintext_size(encoder);
+        ensure_remaining(proof, (3 * var_num + poly_nums.iter().sum::<usize>()) * f_size)?;
+        let eta = transcript.challenge_f(encoder);
+        let weights = Self::batching_powers(&eta, claims.len(), encoder);
+
+        let mut y = F::from_int(0, encoder);
+        for (weight, eval) in weights.iter().zip(evals.iter()) {
+            y = F::add(&y, &F::mult(weight, eval, encoder), encoder);
+        }
+        let mut new_point = vec![];
+        for round in 0..var_num {
+            let sums = [0; 3].map(|_| proof.get_next_and_step(encoder));
+            for x in sums.iter() {
+                transcript.append_f(x, encoder);
+            }
+            if F::add(&sums[0], &sums[1], encoder) != y {
+                return Err(PcsError::Sumcheck { round });
+            }
+            let challenge = transcript.challenge_f(encoder);
+            y = Self::extrapolate(&sums, &challenge, encoder);
+            new_point.push(challenge);
+        }
+        let reduced_evals = poly_nums
+            .iter()
+            .map(|&n| {
+                (0..n)
+                    .map(|_| proof.get_next_and_step(encoder))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for x in reduced_evals.iter().flatten() {
+            transcript.append_f(x, encoder);
+        }
+
+        let mut expected = F::from_int(0, encoder);
+        for (claim, weight) in claims.iter().zip(weights.iter()) {
+            let eq = MultiLinearPoly::eval_eq(&points[claim.point_index], &new_point, encoder);
+            let f = &reduced_evals[claim.commit_index][claim.poly_index];
+            expected = F::add(
+                &expected,
+                &F::mult(weight, &F::mult(&eq, f, encoder), encoder),
+                encoder,
+            );
+        }
+        if expected != y {
+            return Err(PcsError::Evaluation);
+        }
+        PC::verify(pp, commits, &new_point, &reduced_evals, opening, transcript)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arithmetic::{poly::MultiLinearPoly, test_utils::gen_params_n_ctx};
+    use seal_fhe::{BFVEncoder, Plaintext};
+    use util::fiat_shamir::Transcript;
+
+    use crate::{nil::NilPcProver, PcsError, PolynomialCommitmentScheme};
+
+    use super::{MultiPointOpening, PointClaim};
+
+    type F = Plaintext;
+
+    const VN: usize = 5;
+
+    #[test]
+    fn multi_point() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        // two commitments of 2 and 1 polynomials, opened at three points
+        let polies = [2, 1].map(|n| {
+            (0..n)
+                .map(|_| (0..1 << VN).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        });
+        let points = (0..3)
+            .map(|_| (0..VN).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let claims = [(0, 0, 0), (0, 1, 0), (1, 0, 0), (0, 0, 1), (1, 0, 1), (0, 1, 2)].map(
+            |(commit_index, poly_index, point_index)| PointClaim {
+                commit_index,
+                poly_index,
+                point_index,
+            },
+        );
+        let evals = claims
+            .iter()
+            .map(|x| {
+                MultiLinearPoly::eval_multilinear(
+                    &polies[x.commit_index][x.poly_index],
+                    &points[x.point_index],
+                    &encoder,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let (commits, provers): (Vec<_>, Vec<_>) =
+            polies.iter().map(|x| NilPcProver::commit(&(), x)).unzip();
+        let mut transcript = Transcript::new();
+        for x in evals.iter() {
+            transcript.append_f(x, &encoder);
+        }
+        let (point, opening, pcs_proof) = MultiPointOpening::open::<NilPcProver>(
+            &(),
+            &provers.iter().collect::<Vec<_>>(),
+            &polies,
+            &claims,
+            &points,
+            &params,
+            &ctx,
+            &encoder,
+            &mut transcript,
+        );
+        assert_eq!(
+            opening.evals[1][0],
+            MultiLinearPoly::eval_multilinear(&polies[1][0], &point, &encoder)
+        );
+        let proof = transcript.proof;
+
+        let verify = |claims: &[PointClaim], evals: &[F]| {
+            let mut proof = proof.clone();
+            let mut transcript = Transcript::new();
+            for _ in 0..claims.len() {
+                transcript.append_f(&proof.get_next_and_step(&encoder), &encoder);
+            }
+            MultiPointOpening::verify::<NilPcProver>(
+                &(),
+                &commits.iter().collect::<Vec<_>>(),
+                &[2, 1],
+                claims,
+                &points,
+                evals,
+                &encoder,
+                &mut transcript,
+                &mut proof,
+                &pcs_proof,
+            )
+        };
+        assert_eq!(verify(&claims, &evals), Ok(()));
+
+        let mut wrong_evals = evals.clone();
+        wrong_evals[4] = F::add(&wrong_evals[4], &F::from_int(1, &encoder), &encoder);
+        assert_eq!(verify(&claims, &wrong_evals), Err(PcsError::Sumcheck { round: 0 }));
+
+        // claims naming a polynomial or point that does not exist are rejected, not a panic
+        for (commit_index, poly_index, point_index) in [(2, 0, 0), (1, 1, 0), (0, 0, 3)] {
+            let mut wrong_claims = claims;
+            wrong_claims[5] = PointClaim {
+                commit_index,
+                poly_index,
+                point_index,
+            };
+            assert_eq!(verify(&wrong_claims, &evals), Err(PcsError::Claim));
+        }
+    }
+}
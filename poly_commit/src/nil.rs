@@ -1,10 +1,13 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, mem::size_of};
 
 use arithmetic::{field::Field, poly::MultiLinearPoly};
-use seal_fhe::Plaintext;
+use rand::RngCore;
+use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
 use util::fiat_shamir::{Proof, Transcript};
 
-use crate::{CommitmentSerde, PolyCommitProver, PolyCommitVerifier};
+use crate::{
+    CommitmentSerde, HomomorphicCommitment, OpeningProof, PcsError, PolynomialCommitmentScheme,
+};
 
 #[derive(Debug, Clone, Default)]
 pub struct NilCommitment(PhantomData<F>);
@@ -12,66 +15,78 @@ pub struct NilCommitment(PhantomData<F>);
 type F = Plaintext;
 
 impl CommitmentSerde for NilCommitment {
-    fn size(nv: usize, np: usize) -> usize {
+    fn size(nv: usize, np: usize, encoder: &BFVEncoder) -> usize {
         0
     }
 
-    fn serialize_into(&self, buffer: &mut [u8]) {}
+    fn serialize_into(&self, buffer: &mut [u8], encoder: &BFVEncoder) {}
 
-    fn deserialize_from(proof: &mut Proof, var_num: usize, poly_num: usize) -> Self {
+    fn deserialize_from(
+        proof: &mut Proof,
+        var_num: usize,
+        poly_num: usize,
+        encoder: &BFVEncoder,
+    ) -> Self {
         NilCommitment::default()
     }
 }
 
+// commits to nothing and opens to whatever is claimed, to run the rest of a protocol alone
 #[derive(Debug, Clone)]
-pub struct NilPcProver {
-    evals: Vec<Vec<F>>,
+pub struct NilPcProver;
+
+impl<'a> HomomorphicCommitment<'a> for NilPcProver {
+    fn combine(
+        _pp: &(),
+        _commits: &[NilCommitment],
+        _coeffs: &[F],
+        _encoder: &BFVEncoder,
+    ) -> NilCommitment {
+        NilCommitment::default()
+    }
 }
 
-impl PolyCommitProver for NilPcProver {
+impl<'a> PolynomialCommitmentScheme<'a> for NilPcProver {
     type Param = ();
     type Commitment = NilCommitment;
-
-    fn new(_pp: &(), evals: &[Vec<F>]) -> Self {
-        NilPcProver {
-            evals: evals.iter().map(|x| x.clone()).collect(),
-        }
+    type ProverData = Self;
+    type Proof = OpeningProof;
+
+    fn setup(
+        _max_nv: usize,
+        _ctx: &'a Context,
+        _params: &'a EncryptionParameters,
+        _rng: &mut impl RngCore,
+    ) -> Self::Param {
     }
 
-    fn commit(&self) -> Self::Commitment {
-        NilCommitment::default()
+    fn trim(_pp: &Self::Param, _nv: usize) -> Self::Param {}
+
+    fn commit(_pp: &Self::Param, _polies: &[Vec<F>]) -> (Self::Commitment, Self::ProverData) {
+        (NilCommitment::default(), NilPcProver)
     }
 
     fn open(
         _pp: &Self::Param,
-        provers: Vec<&Self>,
-        mut point: Vec<F>,
-        transcript: &mut Transcript,
-    ) {
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct NilPcVerifier {
-    commit: NilCommitment,
-}
-
-impl PolyCommitVerifier for NilPcVerifier {
-    type Param = ();
-    type Commitment = NilCommitment;
-
-    fn new(_pp: &Self::Param, commit: Self::Commitment, poly_num: usize) -> Self {
-        NilPcVerifier { commit }
+        _data: &[&Self::ProverData],
+        _point: &[F],
+        _transcript: &mut Transcript,
+    ) -> Self::Proof {
+        OpeningProof::default()
     }
 
     fn verify(
         _pp: &Self::Param,
-        verifiers: Vec<&Self>,
-        point: Vec<F>,
-        mut evals: Vec<Vec<F>>,
-        transcript: &mut Transcript,
-        proof: &mut Proof,
-    ) -> bool {
-        true
+        _commits: &[&Self::Commitment],
+        _point: &[F],
+        _evals: &[Vec<F>],
+        _proof: &Self::Proof,
+        _transcript: &mut Transcript,
+    ) -> Result<(), PcsError> {
+        Ok(())
+    }
+
+    fn proof_size(_pp: &Self::Param, _nv: usize, _poly_num: usize) -> usize {
+        size_of::<u64>()
     }
 }
@@ -1,36 +1,45 @@
-use arithmetic::{field::Field, poly::MultiLinearPoly};
+use std::marker::PhantomData;
+
+use arithmetic::{
+    field::{as_bytes_vec, plaintext_size},
+    poly::MultiLinearPoly,
+};
+use rand::RngCore;
+use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
 use util::fiat_shamir::{Proof, Transcript};
 
-use crate::{CommitmentSerde, PolyCommitProver, PolyCommitVerifier};
+use crate::{
+    ensure_remaining, CommitmentSerde, OpeningProof, PcsError, PolynomialCommitmentScheme,
+    ProofSerde,
+};
+
+type F = Plaintext;
 
 #[derive(Debug, Clone, Default)]
-pub struct RawCommitment<F: Field> {
-    pub poly_evals: Vec<Vec<F::BaseField>>,
+pub struct RawCommitment {
+    pub poly_evals: Vec<Vec<F>>,
 }
 
-impl<F: Field> CommitmentSerde for RawCommitment<F> {
-    fn size(nv: usize, np: usize) -> usize {
-        (1 << nv) * np * F::BaseField::SIZE
+impl CommitmentSerde for RawCommitment {
+    fn size(nv: usize, np: usize, encoder: &BFVEncoder) -> usize {
+        (1 << nv) * np * plaintext_size(encoder)
     }
 
-    fn serialize_into(&self, buffer: &mut [u8]) {
-        let poly_len = self.poly_evals[0].len();
-        self.poly_evals.iter().enumerate().for_each(|(i, poly)| {
-            poly.iter().enumerate().for_each(|(j, v)| {
-                v.serialize_into(
-                    &mut buffer[(i * poly_len + j) * F::BaseField::SIZE
-                        ..(i * poly_len + j + 1) * F::BaseField::SIZE],
-                )
-            })
-        });
+    fn serialize_into(&self, buffer: &mut [u8], encoder: &BFVEncoder) {
+        buffer.copy_from_slice(&as_bytes_vec(&self.poly_evals.concat(), encoder));
     }
 
-    fn deserialize_from(proof: &mut Proof, var_num: usize, poly_num: usize) -> Self {
+    fn deserialize_from(
+        proof: &mut Proof,
+        var_num: usize,
+        poly_num: usize,
+        encoder: &BFVEncoder,
+    ) -> Self {
         let mut poly_evals = Vec::new();
         for _ in 0..poly_num {
             let mut poly = vec![];
             for _ in 0..(1 << var_num) {
-                poly.push(proof.get_next_and_step());
+                poly.push(proof.get_next_and_step(encoder));
             }
             poly_evals.push(poly);
         }
@@ -38,58 +47,155 @@ impl<F: Field> CommitmentSerde for RawCommitment<F> {
     }
 }
 
+// the context the plaintexts are encoded under
+#[derive(Debug, Clone, Copy)]
+pub struct ShuffleParam<'a> {
+    pub ctx: &'a Context,
+    pub params: &'a EncryptionParameters,
+}
+
+impl<'a> ShuffleParam<'a> {
+    pub fn new(ctx: &'a Context, params: &'a EncryptionParameters) -> Self {
+        ShuffleParam { ctx, params }
+    }
+
+    pub fn encoder(&self) -> BFVEncoder<'a> {
+        BFVEncoder::new(self.ctx, self.params).unwrap()
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct ShufflePcProver<F: Field> {
-    evals: Vec<Vec<F::BaseField>>,
+pub struct ShufflePcProver<'a> {
+    evals: Vec<Vec<F>>,
+    _data: PhantomData<&'a Context>,
 }
 
-impl<F: Field> PolyCommitProver<F> for ShufflePcProver<F> {
-    type Param = ();
-    type Commitment = RawCommitment<F>;
+impl<'a> ShufflePcProver<'a> {
+    // verify reporting why a proof is rejected; a short proof is rejected, not a panic
+    pub fn check(
+        pp: &ShuffleParam<'a>,
+        commits: &[&RawCommitment],
+        point: Vec<F>,
+        mut evals: Vec<Vec<F>>,
+        transcript: &mut Transcript,
+        proof: &mut Proof,
+    ) -> Result<(), PcsError> {
+        if commits.len() != evals.len()
+            || commits
+                .iter()
+                .zip(evals.iter())
+                .any(|(c, e)| c.poly_evals.len() != e.len())
+        {
+            return Err(PcsError::Claim);
+        }
+        let encoder = pp.encoder();
+        let mut new_point = vec![];
+        let nv = point.len();
+        for s in 0..nv {
+            ensure_remaining(
+                proof,
+                plaintext_size(&encoder) * evals.iter().map(|x| x.len()).sum::<usize>(),
+            )?;
+            let mut next_evals = vec![];
+            for i in 0..evals.len() {
+                let mut poly = vec![];
+                for _ in 0..evals[i].len() {
+                    let e = proof.get_next_and_step(&encoder);
+                    transcript.append_f(&e, &encoder);
+                    poly.push(e);
+                }
+                next_evals.push(poly);
+            }
+            let r = transcript.challenge_f(&encoder);
+            let step = F::sub(&r, &point[s], &encoder);
+            for i in 0..evals.len() {
+                for j in 0..evals[i].len() {
+                    let diff = F::sub(&next_evals[i][j], &evals[i][j], &encoder);
+                    evals[i][j] = F::add(&evals[i][j], &F::mult(&step, &diff, &encoder), &encoder);
+                }
+            }
+            new_point.push(r);
+        }
 
-    fn new(_pp: &(), evals: &[Vec<F::BaseField>]) -> Self {
-        ShufflePcProver {
-            evals: evals.iter().map(|x| x.clone()).collect(),
+        for i in 0..evals.len() {
+            for j in 0..evals[i].len() {
+                if evals[i][j]
+                    != MultiLinearPoly::eval_multilinear(
+                        &commits[i].poly_evals[j],
+                        &new_point,
+                        &encoder,
+                    )
+                {
+                    return Err(PcsError::Evaluation);
+                }
+            }
         }
+        Ok(())
     }
+}
 
-    fn commit(&self) -> Self::Commitment {
-        RawCommitment {
-            poly_evals: self.evals.clone(),
-        }
+impl<'a> PolynomialCommitmentScheme<'a> for ShufflePcProver<'a> {
+    type Param = ShuffleParam<'a>;
+    type Commitment = RawCommitment;
+    type ProverData = Self;
+    type Proof = OpeningProof;
+
+    fn setup(
+        _max_nv: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+        _rng: &mut impl RngCore,
+    ) -> Self::Param {
+        ShuffleParam::new(ctx, params)
+    }
+
+    fn trim(pp: &Self::Param, _nv: usize) -> Self::Param {
+        *pp
+    }
+
+    fn commit(_pp: &Self::Param, polies: &[Vec<F>]) -> (Self::Commitment, Self) {
+        let prover = ShufflePcProver {
+            evals: polies.to_vec(),
+            _data: PhantomData,
+        };
+        let commit = RawCommitment {
+            poly_evals: prover.evals.clone(),
+        };
+        (commit, prover)
     }
 
     fn open(
-        _pp: &Self::Param,
-        provers: Vec<&Self>,
-        mut point: Vec<F>,
+        pp: &Self::Param,
+        provers: &[&Self],
+        point: &[F],
         transcript: &mut Transcript,
-    ) {
+    ) -> Self::Proof {
+        let start = transcript.proof.bytes.len();
+        let mut point = point.to_vec();
+        let encoder = pp.encoder();
+        let one = F::from_int(1, &encoder);
         let commit_num = provers.len();
         let nv = point.len();
-        point[0].add_assign_base_elem(F::BaseField::one());
+        point[0] = F::add(&point[0], &one, &encoder);
         for i in 0..commit_num {
             for j in 0..provers[i].evals.len() {
-                transcript.append_f(MultiLinearPoly::eval_multilinear(
-                    &provers[i].evals[j],
-                    &point,
-                ));
+                transcript.append_f(
+                    &MultiLinearPoly::eval_multilinear(&provers[i].evals[j], &point, &encoder),
+                    &encoder,
+                );
             }
         }
-        let r = transcript.challenge_f::<F>();
+        let r = transcript.challenge_f(&encoder);
         let mut new_len = 1 << (nv - 1);
         let mut poly_evals = vec![];
         for i in 0..commit_num {
             let mut commit = vec![];
             for j in 0..provers[i].evals.len() {
+                let evals = &provers[i].evals[j];
                 let mut poly = vec![];
                 for k in 0..new_len {
-                    poly.push(
-                        r.mul_base_elem(
-                            provers[i].evals[j][k * 2 + 1] - provers[i].evals[j][k * 2],
-                        )
-                        .add_base_elem(provers[i].evals[j][k * 2]),
-                    );
+                    let diff = F::sub(&evals[k * 2 + 1], &evals[k * 2], &encoder);
+                    poly.push(F::add(&F::mult(&r, &diff, &encoder), &evals[k * 2], &encoder));
                 }
                 commit.push(poly);
             }
@@ -97,86 +203,62 @@ impl<F: Field> PolyCommitProver<F> for ShufflePcProver<F> {
         }
 
         for s in 1..nv {
-            point[s].add_assign_base_elem(F::BaseField::one());
+            point[s] = F::add(&point[s], &one, &encoder);
             for i in 0..commit_num {
                 for j in 0..provers[i].evals.len() {
-                    transcript.append_f(MultiLinearPoly::eval_multilinear_ext(
-                        &poly_evals[i][j],
-                        &point[s..].to_vec(),
-                    ));
+                    transcript.append_f(
+                        &MultiLinearPoly::eval_multilinear_ext(
+                            &poly_evals[i][j],
+                            &point[s..],
+                            &encoder,
+                        ),
+                        &encoder,
+                    );
                 }
             }
-            let r = transcript.challenge_f();
+            let r = transcript.challenge_f(&encoder);
             new_len /= 2;
             for i in 0..commit_num {
                 for j in 0..provers[i].evals.len() {
+                    let poly = &mut poly_evals[i][j];
                     for k in 0..new_len {
-                        poly_evals[i][j][k] = poly_evals[i][j][k * 2]
-                            + (poly_evals[i][j][k * 2 + 1] - poly_evals[i][j][k * 2]) * r;
+                        let diff = F::sub(&poly[k * 2 + 1], &poly[k * 2], &encoder);
+                        poly[k] = F::add(&poly[k * 2], &F::mult(&diff, &r, &encoder), &encoder);
                     }
-                    poly_evals[i][j].truncate(new_len);
+                    poly.truncate(new_len);
                 }
             }
         }
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct ShufflePcVerifier<F: Field> {
-    commit: RawCommitment<F>,
-}
-
-impl<F: Field> PolyCommitVerifier<F> for ShufflePcVerifier<F> {
-    type Param = ();
-    type Commitment = RawCommitment<F>;
-
-    fn new(_pp: &Self::Param, commit: Self::Commitment, poly_num: usize) -> Self {
-        ShufflePcVerifier { commit }
+        OpeningProof {
+            bytes: transcript.proof.bytes[start..].to_vec(),
+        }
     }
 
     fn verify(
-        _pp: &Self::Param,
-        verifiers: Vec<&Self>,
-        point: Vec<F>,
-        mut evals: Vec<Vec<F>>,
+        pp: &Self::Param,
+        commits: &[&Self::Commitment],
+        point: &[F],
+        evals: &[Vec<F>],
+        proof: &Self::Proof,
         transcript: &mut Transcript,
-        proof: &mut Proof,
-    ) -> bool {
-        let mut new_point = vec![];
-        let nv = point.len();
-        for s in 0..nv {
-            let mut next_evals = vec![];
-            for i in 0..evals.len() {
-                let mut poly = vec![];
-                for _ in 0..evals[i].len() {
-                    let e = proof.get_next_and_step::<F>();
-                    transcript.append_f(e);
-                    poly.push(e);
-                }
-                next_evals.push(poly);
-            }
-            let r: F = transcript.challenge_f();
-            new_point.push(r);
-            for i in 0..evals.len() {
-                for j in 0..evals[i].len() {
-                    let e = evals[i][j];
-                    evals[i][j] += (r - point[s]) * (next_evals[i][j] - e);
-                }
-            }
-        }
-
-        for i in 0..evals.len() {
-            for j in 0..evals[i].len() {
-                assert_eq!(
-                    evals[i][j],
-                    MultiLinearPoly::eval_multilinear(
-                        &verifiers[i].commit.poly_evals[j],
-                        &new_point
-                    )
-                );
-            }
+    ) -> Result<(), PcsError> {
+        let mut reader = proof.reader();
+        Self::check(
+            pp,
+            commits,
+            point.to_vec(),
+            evals.to_vec(),
+            transcript,
+            &mut reader,
+        )?;
+        if reader.remaining() > 0 {
+            return Err(PcsError::MalformedProof);
         }
+        Ok(())
+    }
 
-        true
+    // one evaluation per polynomial and variable
+    fn proof_size(pp: &Self::Param, nv: usize, poly_num: usize) -> usize {
+        OpeningProof::default().size() + nv * poly_num * plaintext_size(&pp.encoder())
     }
 }
@@ -0,0 +1,149 @@
+use std::fmt::Display;
+
+// eta in the johnson radius 1 - sqrt(rho) - eta, taken relative to sqrt(rho)
+const JOHNSON_SLACK: f64 = 32.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodingRegime {
+    // proven proximity gaps up to (1 - rho) / 2, as in the basefold analysis
+    Unique,
+    // up to the johnson bound, needs the out-of-domain samples of deepfold/whir
+    Johnson,
+}
+
+impl DecodingRegime {
+    // bits of security a single query buys at code rate 2^-log_blowup
+    pub fn query_bits(&self, log_blowup: usize) -> f64 {
+        let rate = 0.5f64.powi(log_blowup as i32);
+        match self {
+            DecodingRegime::Unique => -((1.0 + rate) / 2.0).log2(),
+            DecodingRegime::Johnson => -(rate.sqrt() * (1.0 + 1.0 / JOHNSON_SLACK)).log2(),
+        }
+    }
+
+    // log2 of the number of codewords a folded word can be close to
+    pub fn log_list_size(&self, log_blowup: usize) -> f64 {
+        match self {
+            DecodingRegime::Unique => 0.0,
+            // 1 / (2 * eta * sqrt(rho))
+            DecodingRegime::Johnson => (JOHNSON_SLACK / 2.0).log2() + log_blowup as f64,
+        }
+    }
+
+    // grinding `pow_bits` before the queries covers part of the target
+    pub fn query_num(&self, security_bits: usize, log_blowup: usize, pow_bits: usize) -> usize {
+        (security_bits.saturating_sub(pow_bits) as f64 / self.query_bits(log_blowup)).ceil()
+            as usize
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SoundnessReport {
+    pub regime: DecodingRegime,
+    pub variable_num: usize,
+    pub log_blowup: usize,
+    pub field_bits: usize,
+    pub security_bits: usize,
+    pub query_num: usize,
+    pub pow_bits: usize,
+    // batching and every folding round each lose list_size * |D| / |F|
+    pub algebraic_bits: f64,
+    pub query_bits: f64,
+}
+
+impl SoundnessReport {
+    pub fn new(
+        regime: DecodingRegime,
+        variable_num: usize,
+        log_blowup: usize,
+        field_bits: usize,
+        security_bits: usize,
+        query_num: usize,
+        pow_bits: usize,
+    ) -> Self {
+        let log_domain = (variable_num + log_blowup) as f64;
+        let algebraic_bits = field_bits as f64
+            - regime.log_list_size(log_blowup)
+            - log_domain
+            - ((variable_num + 1) as f64).log2();
+        SoundnessReport {
+            regime,
+            variable_num,
+            log_blowup,
+            field_bits,
+            security_bits,
+            query_num,
+            pow_bits,
+            algebraic_bits,
+            query_bits: regime.query_bits(log_blowup) * query_num as f64 + pow_bits as f64,
+        }
+    }
+
+    pub fn achieved_bits(&self) -> f64 {
+        self.algebraic_bits.min(self.query_bits)
+    }
+}
+
+impl Display for SoundnessReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "soundness ({:?} decoding): nv = {}, rate = 2^-{}, |F| = 2^{}",
+            self.regime, self.variable_num, self.log_blowup, self.field_bits
+        )?;
+        writeln!(f, "  algebraic: {:.1} bits", self.algebraic_bits)?;
+        writeln!(
+            f,
+            "  queries: {} x {:.3} + {} bits of work = {:.1} bits",
+            self.query_num,
+            self.regime.query_bits(self.log_blowup),
+            self.pow_bits,
+            self.query_bits
+        )?;
+        write!(
+            f,
+            "  total: {:.1} bits (target {})",
+            self.achieved_bits(),
+            self.security_bits
+        )?;
+        if self.achieved_bits() < self.security_bits as f64 {
+            write!(f, ", field too small for the target")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodingRegime, SoundnessReport};
+
+    #[test]
+    fn query_num() {
+        for log_blowup in 1..4 {
+            for regime in [DecodingRegime::Unique, DecodingRegime::Johnson] {
+                for pow_bits in [0, 20] {
+                    let query_num = regime.query_num(100, log_blowup, pow_bits);
+                    let report =
+                        SoundnessReport::new(regime, 20, log_blowup, 256, 100, query_num, pow_bits);
+                    assert!(report.query_bits >= 100.0);
+                    assert!(report.query_bits - regime.query_bits(log_blowup) < 100.0);
+                    assert!(report.achieved_bits() >= 100.0);
+                }
+                assert!(
+                    regime.query_num(100, log_blowup, 20) < regime.query_num(100, log_blowup, 0)
+                );
+            }
+            assert!(
+                DecodingRegime::Johnson.query_num(100, log_blowup, 0)
+                    < DecodingRegime::Unique.query_num(100, log_blowup, 0)
+            );
+        }
+    }
+
+    #[test]
+    fn small_field() {
+        let report = SoundnessReport::new(DecodingRegime::Johnson, 20, 2, 64, 100, 200, 0);
+        assert!(report.achieved_bits() < 100.0);
+        assert!(report.to_string().contains("field too small"));
+    }
+}
@@ -0,0 +1,721 @@
+use std::marker::PhantomData;
+
+use arithmetic::{
+    field::{as_bytes_vec, plaintext_size},
+    mul_group::Radix2Group,
+};
+use rand::RngCore;
+use seal_fhe::{BFVEncoder, Context, EncryptionParameters, Plaintext};
+use util::{
+    fiat_shamir::Proof,
+    merkle_tree::{MerkleTreeVerifier, HASH_SIZE},
+};
+
+use crate::{
+    deepfold::InterpolateValue,
+    ensure_remaining,
+    iopp::{eval_univariate, fold_fiber},
+    soundness::DecodingRegime,
+    MerkleRoot, OpeningProof, PcsError, PolynomialCommitmentScheme, ProofSerde, Transcript,
+};
+
+type F = Plaintext;
+
+const WHIR_MIN_FINAL_VAR_NUM: usize = 2;
+const WHIR_MAX_FOLDING_FACTOR: usize = 4;
+
+// every round folds `folding_factor` variables but only halves the domain, so the rate improves
+// by 2^(folding_factor - 1) per round and the later rounds need fewer queries
+#[derive(Debug, Clone)]
+pub struct WhirParam<'a> {
+    pub mult_subgroups: Vec<Radix2Group>,
+    pub variable_num: usize,
+    pub folding_factor: usize,
+    pub final_var_num: usize,
+    pub query_nums: Vec<usize>,
+    pub log_blowup: usize,
+    pub security_bits: usize,
+    pub ctx: &'a Context,
+    pub params: &'a EncryptionParameters,
+}
+
+impl<'a> WhirParam<'a> {
+    pub fn new(
+        variable_num: usize,
+        log_blowup: usize,
+        folding_factor: usize,
+        security_bits: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+    ) -> Self {
+        let min_final = WHIR_MIN_FINAL_VAR_NUM.min(variable_num);
+        let round_num = ((variable_num - min_final) / folding_factor).max(1);
+        assert!(round_num * folding_factor <= variable_num);
+        // unique decoding is not needed thanks to the out-of-domain samples
+        let query_nums = (0..round_num)
+            .map(|i| {
+                DecodingRegime::Johnson.query_num(
+                    security_bits,
+                    log_blowup + i * (folding_factor - 1),
+                    0,
+                )
+            })
+            .collect();
+        let mut mult_subgroups = vec![Radix2Group::new(
+            (variable_num + log_blowup) as u32,
+            params.get_plain_modulus().value(),
+        )];
+        for i in 1..round_num + folding_factor {
+            mult_subgroups.push(mult_subgroups[i - 1].exp(2));
+        }
+        WhirParam {
+            mult_subgroups,
+            variable_num,
+            folding_factor,
+            final_var_num: variable_num - round_num * folding_factor,
+            query_nums,
+            log_blowup,
+            security_bits,
+            ctx,
+            params,
+        }
+    }
+
+    // picks the folding factor with the smallest estimated proof for a batch of `poly_num`
+    pub fn search(
+        variable_num: usize,
+        log_blowup: usize,
+        security_bits: usize,
+        poly_num: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+    ) -> Self {
+        (1..=WHIR_MAX_FOLDING_FACTOR.min(variable_num))
+            .map(|k| Self::new(variable_num, log_blowup, k, security_bits, ctx, params))
+            .min_by_key(|pp| pp.estimated_proof_size(poly_num))
+            .unwrap()
+    }
+
+    pub fn encoder(&self) -> BFVEncoder<'a> {
+        BFVEncoder::new(self.ctx, self.params).unwrap()
+    }
+
+    pub fn round_num(&self) -> usize {
+        self.query_nums.len()
+    }
+
+    pub fn estimated_proof_size(&self, poly_num: usize) -> usize {
+        let f_size = plaintext_size(&self.encoder());
+        let k = self.folding_factor;
+        let mut size = f_size;
+        for i in 0..self.round_num() {
+            size += 3 * k * f_size;
+            if i < self.round_num() - 1 {
+                size += HASH_SIZE + f_size;
+            } else {
+                size += (1 << self.final_var_num) * f_size;
+            }
+            let elem_size = if i == 0 { f_size * poly_num } else { f_size };
+            let depth = self.mult_subgroups[i].size().ilog2() as usize - k;
+            size += self.query_nums[i] * ((1 << k) * elem_size + depth * HASH_SIZE);
+        }
+        size
+    }
+}
+
+// weight(b) = coeff * prod_k (a_k + b_k * d_k), covers both eq(z, b) and z^b
+#[derive(Debug, Clone)]
+struct Constraint {
+    coeff: F,
+    factors: Vec<(F, F)>,
+}
+
+impl Constraint {
+    fn eq(coeff: F, point: &[F], encoder: &BFVEncoder) -> Self {
+        let one = F::from_int(1, encoder);
+        Constraint {
+            coeff,
+            factors: point
+                .iter()
+                .map(|p| {
+                    (
+                        F::sub(&one, p, encoder),
+                        F::sub(&F::add(p, p, encoder), &one, encoder),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn pow(coeff: F, mut x: F, var_num: usize, encoder: &BFVEncoder) -> Self {
+        let one = F::from_int(1, encoder);
+        let mut factors = vec![];
+        for _ in 0..var_num {
+            factors.push((one.clone(), F::sub(&x, &one, encoder)));
+            x = F::mult(&x, &x, encoder);
+        }
+        Constraint { coeff, factors }
+    }
+
+    fn bind(&mut self, challenge: &F, encoder: &BFVEncoder) {
+        let (a, d) = self.factors.remove(0);
+        let factor = F::add(&a, &F::mult(challenge, &d, encoder), encoder);
+        self.coeff = F::mult(&self.coeff, &factor, encoder);
+    }
+
+    fn evals(&self, encoder: &BFVEncoder) -> Vec<F> {
+        let mut res = vec![self.coeff.clone()];
+        for (a, d) in self.factors.iter() {
+            let ad = F::add(a, d, encoder);
+            let len = res.len();
+            for j in 0..len {
+                res.push(F::mult(&res[j], &ad, encoder));
+                res[j] = F::mult(&res[j], a, encoder);
+            }
+        }
+        res
+    }
+}
+
+fn interpolate_quadratic(h: &[F; 3], x: &F, encoder: &BFVEncoder) -> F {
+    let x_1 = F::sub(x, &F::from_int(1, encoder), encoder);
+    let x_2 = F::sub(x, &F::from_int(2, encoder), encoder);
+    let t0 = F::mult(&F::mult(&h[0], &x_1, encoder), &x_2, encoder);
+    let t1 = F::mult(&F::mult(&h[1], x, encoder), &x_2, encoder);
+    let t2 = F::mult(&F::mult(&h[2], x, encoder), &x_1, encoder);
+    let even = F::mult(&F::add(&t0, &t2, encoder), &F::inverse_2(encoder), encoder);
+    F::sub(&even, &t1, encoder)
+}
+
+fn add_constraint(weights: &mut [F], constraint: &Constraint, encoder: &BFVEncoder) {
+    for (w, c) in weights.iter_mut().zip(constraint.evals(encoder)) {
+        *w = F::add(w, &c, encoder);
+    }
+}
+
+fn fold(values: &mut Vec<F>, challenge: &F, encoder: &BFVEncoder) {
+    let new_len = values.len() / 2;
+    for j in 0..new_len {
+        let diff = F::sub(&values[j * 2 + 1], &values[j * 2], encoder);
+        values[j] = F::add(&values[j * 2], &F::mult(&diff, challenge, encoder), encoder);
+    }
+    values.truncate(new_len);
+}
+
+#[derive(Clone)]
+pub struct WhirProver<'a> {
+    interpolation: InterpolateValue,
+    poly: Vec<Vec<F>>,
+    _data: PhantomData<&'a Context>,
+}
+
+impl<'a> WhirProver<'a> {
+    // k rounds of sumcheck on sum_b weights(b) * poly(b), folding both as we go
+    fn sumcheck(
+        poly: &mut Vec<F>,
+        weights: &mut Vec<F>,
+        round_num: usize,
+        transcript: &mut Transcript,
+        encoder: &BFVEncoder,
+    ) {
+        for _ in 0..round_num {
+            let mut h = [(); 3].map(|_| F::from_int(0, encoder));
+            for j in 0..poly.len() / 2 {
+                let (e0, e1) = (&poly[j * 2], &poly[j * 2 + 1]);
+                let (w0, w1) = (&weights[j * 2], &weights[j * 2 + 1]);
+                let e2 = F::sub(&F::add(e1, e1, encoder), e0, encoder);
+                let w2 = F::sub(&F::add(w1, w1, encoder), w0, encoder);
+                h[0] = F::add(&h[0], &F::mult(w0, e0, encoder), encoder);
+                h[1] = F::add(&h[1], &F::mult(w1, e1, encoder), encoder);
+                h[2] = F::add(&h[2], &F::mult(&w2, &e2, encoder), encoder);
+            }
+            for i in h.iter() {
+                transcript.append_f(i, encoder);
+            }
+            let challenge = transcript.challenge_f(encoder);
+            fold(poly, &challenge, encoder);
+            fold(weights, &challenge, encoder);
+        }
+    }
+
+    pub fn new(pp: &WhirParam<'a>, poly: &[Vec<F>]) -> Self {
+        let encoder = pp.encoder();
+        let values = poly
+            .iter()
+            .flat_map(|x| pp.mult_subgroups[0].fft(x.clone(), &encoder))
+            .collect::<Vec<_>>();
+        WhirProver {
+            interpolation: InterpolateValue::new(
+                values,
+                (1 << pp.folding_factor) * poly.len(),
+                false,
+                &encoder,
+            ),
+            poly: poly.to_vec(),
+            _data: PhantomData,
+        }
+    }
+
+    pub fn root(&self) -> MerkleRoot {
+        MerkleRoot(self.interpolation.commit())
+    }
+}
+
+impl<'a> PolynomialCommitmentScheme<'a> for WhirProver<'a> {
+    type Param = WhirParam<'a>;
+    type Commitment = MerkleRoot;
+    type ProverData = Self;
+    type Proof = OpeningProof;
+
+    // the folding factor with the smallest single-polynomial proof at 100 bits
+    fn setup(
+        max_nv: usize,
+        ctx: &'a Context,
+        params: &'a EncryptionParameters,
+        _rng: &mut impl RngCore,
+    ) -> Self::Param {
+        WhirParam::search(max_nv, 1, 100, 1, ctx, params)
+    }
+
+    fn trim(pp: &Self::Param, nv: usize) -> Self::Param {
+        WhirParam::new(
+            nv,
+            pp.log_blowup,
+            pp.folding_factor.min(nv),
+            pp.security_bits,
+            pp.ctx,
+            pp.params,
+        )
+    }
+
+    fn commit(pp: &Self::Param, polies: &[Vec<F>]) -> (Self::Commitment, Self) {
+        let prover = Self::new(pp, polies);
+        (prover.root(), prover)
+    }
+
+    fn open(
+        pp: &Self::Param,
+        provers: &[&Self],
+        point: &[F],
+        transcript: &mut Transcript,
+    ) -> Self::Proof {
+        let start = transcript.proof.bytes.len();
+        let encoder = pp.encoder();
+        let r = transcript.challenge_f(&encoder);
+        let mut poly_evals = provers[0].poly[0].clone();
+        for poly in provers.iter().flat_map(|x| x.poly.iter()).skip(1) {
+            for (e, x) in poly_evals.iter_mut().zip(poly.iter()) {
+                *e = F::add(&F::mult(e, &r, &encoder), x, &encoder);
+            }
+        }
+        let k = pp.folding_factor;
+        let one = F::from_int(1, &encoder);
+        let mut weights = Constraint::eq(one.clone(), point, &encoder).evals(&encoder);
+        let z = transcript.challenge_f(&encoder);
+        transcript.append_f(&eval_univariate(&poly_evals, &z, &encoder), &encoder);
+        let gamma = transcript.challenge_f(&encoder);
+        add_constraint(
+            &mut weights,
+            &Constraint::pow(gamma, z, pp.variable_num, &encoder),
+            &encoder,
+        );
+
+        let mut interpolations: Vec<InterpolateValue> = vec![];
+        for i in 0..pp.round_num() {
+            Self::sumcheck(&mut poly_evals, &mut weights, k, transcript, &encoder);
+            let var_num = pp.variable_num - (i + 1) * k;
+            let last_round = i == pp.round_num() - 1;
+            let mut points = vec![];
+            if !last_round {
+                let new_interpolation = InterpolateValue::new(
+                    pp.mult_subgroups[i + 1].fft(poly_evals.clone(), &encoder),
+                    1 << k,
+                    false,
+                    &encoder,
+                );
+                transcript.append_u8_slice(&new_interpolation.commit(), HASH_SIZE);
+                interpolations.push(new_interpolation);
+                let z = transcript.challenge_f(&encoder);
+                transcript.append_f(&eval_univariate(&poly_evals, &z, &encoder), &encoder);
+                points.push(z);
+            } else {
+                for j in poly_evals.iter() {
+                    transcript.append_f(j, &encoder);
+                }
+            }
+
+            let len = pp.mult_subgroups[i].size() >> k;
+            let mut leaf_indices = transcript
+                .challenge_usizes(pp.query_nums[i])
+                .into_iter()
+                .map(|x| x % len)
+                .collect::<Vec<_>>();
+            leaf_indices.sort();
+            leaf_indices.dedup();
+            let queried = if i == 0 {
+                provers.iter().map(|x| &x.interpolation).collect::<Vec<_>>()
+            } else {
+                vec![&interpolations[i - 1]]
+            };
+            for interpolation in queried {
+                let query = interpolation.query(&leaf_indices);
+                transcript.append_u8_slice(&query.0, query.0.len());
+                for j in query.1.iter() {
+                    transcript.append_f(j, &encoder);
+                }
+            }
+
+            if !last_round {
+                points.extend(
+                    leaf_indices
+                        .iter()
+                        .map(|&j| F::from_int(pp.mult_subgroups[i + k].element_at(j), &encoder)),
+                );
+                let gamma = transcript.challenge_f(&encoder);
+                let mut coeff = one.clone();
+                for x in points {
+                    add_constraint(
+                        &mut weights,
+                        &Constraint::pow(coeff.clone(), x, var_num, &encoder),
+                        &encoder,
+                    );
+                    coeff = F::mult(&coeff, &gamma, &encoder);
+                }
+            }
+        }
+        OpeningProof {
+            bytes: transcript.proof.bytes[start..].to_vec(),
+        }
+    }
+
+    fn verify(
+        pp: &Self::Param,
+        commits: &[&Self::Commitment],
+        point: &[F],
+        evals: &[Vec<F>],
+        proof: &Self::Proof,
+        transcript: &mut Transcript,
+    ) -> Result<(), PcsError> {
+        if commits.len() != evals.len() || point.len() != pp.variable_num {
+            return Err(PcsError::Claim);
+        }
+        let verifiers = commits
+            .iter()
+            .zip(evals.iter())
+            .map(|(commit, evals)| WhirVerifier::new(pp, (*commit).clone(), evals.len()))
+            .collect::<Vec<_>>();
+        let mut reader = proof.reader();
+        WhirVerifier::check(
+            pp,
+            &verifiers.iter().collect::<Vec<_>>(),
+            point.to_vec(),
+            evals.to_vec(),
+            transcript,
+            &mut reader,
+        )?;
+        if reader.remaining() > 0 {
+            return Err(PcsError::MalformedProof);
+        }
+        Ok(())
+    }
+
+    fn proof_size(pp: &Self::Param, nv: usize, poly_num: usize) -> usize {
+        OpeningProof::default().size() + Self::trim(pp, nv).estimated_proof_size(poly_num)
+    }
+}
+
+#[derive(Clone)]
+pub struct WhirVerifier<'a> {
+    commit: MerkleTreeVerifier,
+    poly_num: usize,
+    _data: PhantomData<&'a Context>,
+}
+
+impl<'a> WhirVerifier<'a> {
+    pub fn new(pp: &WhirParam<'a>, commit: MerkleRoot, poly_num: usize) -> Self {
+        WhirVerifier {
+            commit: MerkleTreeVerifier::new(
+                pp.mult_subgroups[0].size() >> pp.folding_factor,
+                commit.0,
+            ),
+            poly_num,
+            _data: PhantomData,
+        }
+    }
+
+    fn sumcheck(
+        claim: &mut F,
+        constraints: &mut [Constraint],
+        round: usize,
+        round_num: usize,
+        transcript: &mut Transcript,
+        proof: &mut Proof,
+        encoder: &BFVEncoder,
+    ) -> Result<Vec<F>, PcsError> {
+        let mut challenges = vec![];
+        for _ in 0..round_num {
+            ensure_remaining(proof, 3 * plaintext_size(encoder))?;
+            let h = [(); 3].map(|_| proof.get_next_and_step(encoder));
+            for i in h.iter() {
+                transcript.append_f(i, encoder);
+            }
+            if F::add(&h[0], &h[1], encoder) != *claim {
+                return Err(PcsError::Sumcheck { round });
+            }
+            let challenge = transcript.challenge_f(encoder);
+            *claim = interpolate_quadratic(&h, &challenge, encoder);
+            constraints
+                .iter_mut()
+                .for_each(|c| c.bind(&challenge, encoder));
+            challenges.push(challenge);
+        }
+        Ok(challenges)
+    }
+
+    // verify reporting why a proof is rejected; a short proof is rejected, not a panic
+    pub fn check(
+        pp: &WhirParam<'a>,
+        verifiers: &[&Self],
+        point: Vec<F>,
+        evals: Vec<Vec<F>>,
+        transcript: &mut Transcript,
+        proof: &mut Proof,
+    ) -> Result<(), PcsError> {
+        let encoder = pp.encoder();
+        let f_size = plaintext_size(&encoder);
+        let r = transcript.challenge_f(&encoder);
+        let mut claim = F::from_int(0, &encoder);
+        for i in evals {
+            for j in i {
+                claim = F::add(&F::mult(&claim, &r, &encoder), &j, &encoder);
+            }
+        }
+        let k = pp.folding_factor;
+        let one = F::from_int(1, &encoder);
+        let mut constraints = vec![Constraint::eq(one.clone(), &point, &encoder)];
+        let z = transcript.challenge_f(&encoder);
+        ensure_remaining(proof, f_size)?;
+        let y = proof.get_next_and_step(&encoder);
+        transcript.append_f(&y, &encoder);
+        let gamma = transcript.challenge_f(&encoder);
+        claim = F::add(&claim, &F::mult(&gamma, &y, &encoder), &encoder);
+        constraints.push(Constraint::pow(gamma, z, pp.variable_num, &encoder));
+
+        let mut commits = vec![];
+        let mut final_poly = vec![];
+        for i in 0..pp.round_num() {
+            let challenges = Self::sumcheck(
+                &mut claim,
+                &mut constraints,
+                i,
+                k,
+                transcript,
+                proof,
+                &encoder,
+            )?;
+            let var_num = pp.variable_num - (i + 1) * k;
+            let last_round = i == pp.round_num() - 1;
+            let mut points = vec![];
+            let mut values = vec![];
+            if !last_round {
+                ensure_remaining(proof, HASH_SIZE + f_size)?;
+                let merkle_root = proof.get_next_hash();
+                transcript.append_u8_slice(&merkle_root, HASH_SIZE);
+                commits.push(MerkleTreeVerifier::new(
+                    pp.mult_subgroups[i + 1].size() >> k,
+                    merkle_root,
+                ));
+                let z = transcript.challenge_f(&encoder);
+                let y = proof.get_next_and_step(&encoder);
+                transcript.append_f(&y, &encoder);
+                points.push(z);
+                values.push(y);
+            } else {
+                ensure_remaining(proof, (1 << var_num) * f_size)?;
+                final_poly = (0..(1 << var_num))
+                    .map(|_| proof.get_next_and_step(&encoder))
+                    .collect::<Vec<_>>();
+                for j in final_poly.iter() {
+                    transcript.append_f(j, &encoder);
+                }
+            }
+
+            let len = pp.mult_subgroups[i].size() >> k;
+            let mut leaf_indices = transcript
+                .challenge_usizes(pp.query_nums[i])
+                .into_iter()
+                .map(|x| x % len)
+                .collect::<Vec<_>>();
+            leaf_indices.sort();
+            leaf_indices.dedup();
+            let query_num = leaf_indices.len();
+            let mut fibers = vec![vec![F::from_int(0, &encoder); 1 << k]; query_num];
+            let queried = if i == 0 {
+                verifiers
+                    .iter()
+                    .map(|x| (&x.commit, x.poly_num))
+                    .collect::<Vec<_>>()
+            } else {
+                vec![(&commits[i - 1], 1)]
+            };
+            for (commit, poly_num) in queried {
+                let leaf_size = (1 << k) * poly_num;
+                let proof_length = commit.proof_length(&leaf_indices);
+                ensure_remaining(proof, proof_length + query_num * leaf_size * f_size)?;
+                let proof_bytes = proof.get_next_slice(proof_length);
+                let proof_values = (0..query_num * leaf_size)
+                    .map(|_| proof.get_next_and_step(&encoder))
+                    .collect::<Vec<_>>();
+                transcript.append_u8_slice(&proof_bytes, proof_bytes.len());
+                for j in proof_values.iter() {
+                    transcript.append_f(j, &encoder);
+                }
+                let leaves = (0..query_num)
+                    .map(|q| {
+                        as_bytes_vec(
+                            &(0..leaf_size)
+                                .map(|j| proof_values[j * query_num + q].clone())
+                                .collect::<Vec<_>>(),
+                            &encoder,
+                        )
+                    })
+                    .collect();
+                if !commit.verify(proof_bytes, &leaf_indices, &leaves) {
+                    return Err(PcsError::MerklePath { round: i });
+                }
+                // batches the polynomials of the first round with r, the fibers start at zero
+                // so the single folded polynomial of a later round is taken as is
+                for (q, fiber) in fibers.iter_mut().enumerate() {
+                    for p in 0..poly_num {
+                        for (m, f) in fiber.iter_mut().enumerate() {
+                            let x = &proof_values[((p << k) + m) * query_num + q];
+                            *f = F::add(&F::mult(f, &r, &encoder), x, &encoder);
+                        }
+                    }
+                }
+            }
+            for (&j, fiber) in leaf_indices.iter().zip(fibers) {
+                let x = F::from_int(pp.mult_subgroups[i + k].element_at(j), &encoder);
+                let folded = fold_fiber(fiber, j, &pp.mult_subgroups[i..], &challenges, &encoder);
+                if last_round {
+                    if folded != eval_univariate(&final_poly, &x, &encoder) {
+                        return Err(PcsError::Folding { round: i });
+                    }
+                } else {
+                    points.push(x);
+                    values.push(folded);
+                }
+            }
+
+            if !last_round {
+                let gamma = transcript.challenge_f(&encoder);
+                let mut coeff = one.clone();
+                for (x, y) in points.into_iter().zip(values) {
+                    claim = F::add(&claim, &F::mult(&coeff, &y, &encoder), &encoder);
+                    constraints.push(Constraint::pow(coeff.clone(), x, var_num, &encoder));
+                    coeff = F::mult(&coeff, &gamma, &encoder);
+                }
+            }
+        }
+
+        let mut expected = F::from_int(0, &encoder);
+        for c in constraints.iter() {
+            for (w, f) in c.evals(&encoder).iter().zip(final_poly.iter()) {
+                expected = F::add(&expected, &F::mult(w, f, &encoder), &encoder);
+            }
+        }
+        if expected != claim {
+            return Err(PcsError::Evaluation);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arithmetic::{poly::MultiLinearPoly, test_utils::gen_params_n_ctx};
+    use seal_fhe::Plaintext;
+    use util::fiat_shamir::Transcript;
+
+    use crate::{
+        CommitmentSerde, MerkleRoot, OpeningProof, PcsError, PolynomialCommitmentScheme,
+        ProofSerde,
+    };
+
+    use super::{WhirParam, WhirProver};
+
+    type F = Plaintext;
+
+    fn commit_open_verify(pp: WhirParam, poly_num: usize) {
+        let encoder = pp.encoder();
+        let nv = pp.variable_num;
+        let polies = (0..poly_num)
+            .map(|_| {
+                (0..(1 << nv))
+                    .map(|_| F::random_pt(&encoder))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let point = (0..nv).map(|_| F::random_pt(&encoder)).collect::<Vec<_>>();
+        let evals = vec![polies
+            .iter()
+            .map(|x| MultiLinearPoly::eval_multilinear(x, &point, &encoder))
+            .collect::<Vec<_>>()];
+
+        let (commitment, prover) = WhirProver::commit(&pp, &polies);
+        let size = MerkleRoot::size(nv, poly_num, &encoder);
+        let mut buffer = vec![0u8; size];
+        commitment.serialize_into(&mut buffer, &encoder);
+        let transcript = || {
+            let mut transcript = Transcript::new();
+            transcript.append_u8_slice(&buffer, size);
+            for i in &evals[0] {
+                transcript.append_f(i, &encoder);
+            }
+            transcript
+        };
+        let proof = WhirProver::open(&pp, &[&prover], &point, &mut transcript());
+        assert!(proof.size() <= WhirProver::proof_size(&pp, nv, poly_num));
+        let verify = |proof: &OpeningProof| {
+            WhirProver::verify(
+                &pp,
+                &[&commitment],
+                &point,
+                &evals,
+                proof,
+                &mut transcript(),
+            )
+        };
+        assert_eq!(verify(&proof), Ok(()));
+
+        // a proof cut short is rejected rather than read past its end
+        let truncated = OpeningProof {
+            bytes: proof.bytes[..proof.bytes.len() - 1].to_vec(),
+        };
+        assert_eq!(verify(&truncated), Err(PcsError::MalformedProof));
+    }
+
+    #[test]
+    fn folding_factors() {
+        let (params, ctx) = gen_params_n_ctx();
+        for k in 1..=4 {
+            commit_open_verify(WhirParam::new(8, 2, k, 32, &ctx, &params), 2);
+        }
+    }
+
+    #[test]
+    fn search() {
+        let (params, ctx) = gen_params_n_ctx();
+        let pp = WhirParam::search(10, 2, 32, 1, &ctx, &params);
+        let binary = WhirParam::new(10, 2, 1, 32, &ctx, &params);
+        assert!(pp.estimated_proof_size(1) <= binary.estimated_proof_size(1));
+        commit_open_verify(pp, 1);
+    }
+
+    #[test]
+    fn scheme() {
+        let (params, ctx) = gen_params_n_ctx();
+        let pp = WhirProver::setup(9, &ctx, &params, &mut rand::thread_rng());
+        commit_open_verify(WhirProver::trim(&pp, 7), 2);
+    }
+}
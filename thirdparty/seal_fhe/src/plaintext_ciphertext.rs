@@ -167,8 +167,29 @@ impl Plaintext {
      */
     pub const SIZE: usize = 1; // Todo: size
 
+    /**
+     * Slot-wise inverse modulo the (prime) plain modulus, zero slots staying zero
+     */
     pub fn inv(&self, encoder: &BFVEncoder) -> Self {
-        encoder.encode_unsigned(&[0]).unwrap()
+        let modulus = encoder.get_params().get_plain_modulus().value() as u128;
+        let res = encoder
+            .decode_unsigned(&self)
+            .unwrap()
+            .into_iter()
+            .map(|x| {
+                // x^(modulus - 2)
+                let (mut base, mut exp, mut acc) = (x as u128, modulus - 2, 1u128);
+                while exp > 0 {
+                    if exp & 1 == 1 {
+                        acc = acc * base % modulus;
+                    }
+                    base = base * base % modulus;
+                    exp >>= 1;
+                }
+                if x == 0 { 0 } else { acc as u64 }
+            })
+            .collect::<Vec<u64>>();
+        encoder.encode_unsigned(&res).unwrap()
     }
 
     pub fn get_value(&self, encoder: &BFVEncoder) -> Vec<u64> {
@@ -751,6 +772,26 @@ mod tests {
         assert_eq!(encoder.decode_unsigned(&m).unwrap()[0], 1);
     }
 
+    #[test]
+    fn test_inv() {
+        let params = BfvEncryptionParametersBuilder::new()
+            .set_poly_modulus_degree(8192)
+            .set_coefficient_modulus(
+                CoefficientModulus::create(8192, &[50, 30, 30, 50, 50]).unwrap(),
+            )
+            .set_plain_modulus(PlainModulus::batching(8192, 20).unwrap())
+            .build()
+            .unwrap();
+
+        let ctx = Context::new(&params, false, crate::SecurityLevel::TC128).unwrap();
+
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        let p = encoder.encode_unsigned(&[1234, 0, 1]).unwrap();
+        let m = Plaintext::mult(&p, &p.inv(&encoder), &encoder);
+        assert_eq!(encoder.decode_unsigned(&m).unwrap()[..3], [1, 0, 1]);
+    }
+
     #[test]
     fn can_sub_ppp() {
         let modulus = PlainModulus::batching(8192, 20).unwrap();
@@ -1,7 +1,7 @@
 use std::mem::size_of;
 
-// use arithmetic::field::Field;
-use seal_fhe::{Context, FromBytes, Plaintext, ToBytes};
+use arithmetic::field::{as_bytes_vec, from_bytes, plaintext_size};
+use seal_fhe::{BFVEncoder, Plaintext};
 use sha2::{Digest, Sha256};
 
 const HASH_SIZE: usize = 32;
@@ -25,14 +25,14 @@ impl Proof {
     }
 
     #[inline(always)]
-    pub fn get_next_and_step(&mut self, ctx: &Context) -> F {
-        let ret = F::from_bytes(ctx, &self.bytes[self.idx..(self.idx + F::SIZE)]).unwrap();
-        self.step(F::SIZE);
+    pub fn get_next_and_step(&mut self, encoder: &BFVEncoder) -> F {
+        let size = plaintext_size(encoder);
+        let ret = from_bytes(&self.bytes[self.idx..(self.idx + size)], encoder);
+        self.step(size);
         ret
     }
 
     pub fn get_next_hash(&mut self) -> [u8; HASH_SIZE] {
-        
         let ret = self.bytes[self.idx..(self.idx + HASH_SIZE)]
             .try_into()
             .unwrap();
@@ -45,6 +45,11 @@ impl Proof {
         self.step(len);
         ret
     }
+
+    // bytes not read yet, so verifiers can reject short proofs instead of panicking
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.idx
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -102,21 +107,35 @@ impl Transcript {
         }
     }
 
-    pub fn append_f(&mut self, f: F) {
-        let cur_size = self.proof.bytes.len();
-        self.proof.bytes.resize(cur_size + F::SIZE, 0);
-        let _ = &self.proof.bytes[cur_size..].copy_from_slice(&f.as_bytes().unwrap());
-        // f.serialize_into(&mut self.proof.bytes[cur_size..]);
+    pub fn append_f(&mut self, f: &F, encoder: &BFVEncoder) {
+        self.proof
+            .bytes
+            .extend_from_slice(&as_bytes_vec(std::slice::from_ref(f), encoder));
     }
 
     pub fn append_u8_slice(&mut self, buffer: &[u8], size: usize) {
         self.proof.append_u8_slice(buffer, size);
     }
 
-    pub fn challenge_f(&mut self, ctx: &Context) -> F {
+    // every slot gets its own challenge, drawn from H(state || counter) 16 bytes at a time
+    // so the reduction modulo the plain modulus is close to uniform
+    pub fn challenge_f(&mut self, encoder: &BFVEncoder) -> F {
         self.hash_to_digest();
-        assert!(F::SIZE <= Self::DIGEST_SIZE);
-        F::from_bytes(ctx, &self.digest).unwrap()
+        let modulus = encoder.get_params().get_plain_modulus().value() as u128;
+        let mut buffer = [0u8; Self::DIGEST_SIZE + size_of::<u64>()];
+        buffer[..Self::DIGEST_SIZE].copy_from_slice(&self.digest);
+        let mut output = [0u8; HASH_SIZE];
+        let values = (0..encoder.get_slot_count())
+            .map(|i| {
+                if i % 2 == 0 {
+                    buffer[Self::DIGEST_SIZE..].copy_from_slice(&(i as u64 / 2).to_be_bytes());
+                    self.hasher.hash(&mut output, &buffer, buffer.len());
+                }
+                let bytes = &output[(i % 2) * 16..(i % 2 + 1) * 16];
+                (u128::from_be_bytes(bytes.try_into().unwrap()) % modulus) as u64
+            })
+            .collect::<Vec<_>>();
+        encoder.encode_unsigned(&values).unwrap()
     }
 
     pub fn challenge_usizes(&mut self, num: usize) -> Vec<usize> {
@@ -127,4 +146,93 @@ impl Transcript {
             })
             .collect()
     }
+
+    // proof of work: a nonce such that H(state || nonce) starts with `pow_bits` zero bits
+    pub fn grind(&mut self, pow_bits: usize) {
+        if pow_bits == 0 {
+            return;
+        }
+        self.hash_to_digest();
+        let nonce = (0u64..)
+            .find(|&nonce| self.pow_zeros(nonce) >= pow_bits)
+            .unwrap();
+        self.append_u8_slice(&nonce.to_be_bytes(), size_of::<u64>());
+    }
+
+    pub fn verify_grind(&mut self, proof: &mut Proof, pow_bits: usize) -> bool {
+        if pow_bits == 0 {
+            return true;
+        }
+        if proof.remaining() < size_of::<u64>() {
+            return false;
+        }
+        self.hash_to_digest();
+        let nonce = proof.get_next_slice(size_of::<u64>());
+        self.append_u8_slice(&nonce, size_of::<u64>());
+        self.pow_zeros(u64::from_be_bytes(nonce.try_into().unwrap())) >= pow_bits
+    }
+
+    fn pow_zeros(&self, nonce: u64) -> usize {
+        let mut buffer = [0u8; Self::DIGEST_SIZE + size_of::<u64>()];
+        buffer[..Self::DIGEST_SIZE].copy_from_slice(&self.digest);
+        buffer[Self::DIGEST_SIZE..].copy_from_slice(&nonce.to_be_bytes());
+        let mut output = [0u8; HASH_SIZE];
+        self.hasher.hash(&mut output, &buffer, buffer.len());
+        u128::from_be_bytes(output[..16].try_into().unwrap()).leading_zeros() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arithmetic::test_utils::gen_params_n_ctx;
+    use seal_fhe::{BFVEncoder, Plaintext};
+
+    use super::Transcript;
+
+    #[test]
+    fn plaintexts() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+
+        let mut transcript = Transcript::new();
+        let f = Plaintext::random_pt(&encoder);
+        transcript.append_f(&f, &encoder);
+        let challenge = transcript.challenge_f(&encoder);
+        let values = challenge.get_value(&encoder);
+        assert!(values.iter().all(|&x| x < params.get_plain_modulus().value()));
+        assert_ne!(values[0], values[1]);
+        let mut proof = transcript.proof;
+
+        let mut transcript = Transcript::new();
+        let g = proof.get_next_and_step(&encoder);
+        assert_eq!(proof.remaining(), 0);
+        assert_eq!(f, g);
+        transcript.append_f(&g, &encoder);
+        assert_eq!(transcript.challenge_f(&encoder), challenge);
+    }
+
+    #[test]
+    fn grind() {
+        let mut transcript = Transcript::new();
+        transcript.append_u8_slice(&[1, 2, 3], 3);
+        transcript.grind(12);
+        let prover_query = transcript.challenge_usizes(4);
+        let mut proof = transcript.proof;
+
+        let mut transcript = Transcript::new();
+        transcript.append_u8_slice(&proof.get_next_slice(3), 3);
+        assert!(transcript.verify_grind(&mut proof, 12));
+        assert_eq!(transcript.challenge_usizes(4), prover_query);
+
+        let mut transcript = Transcript::new();
+        transcript.append_u8_slice(&[1, 2, 4], 3);
+        proof.idx = 3;
+        assert!(!transcript.verify_grind(&mut proof, 12));
+
+        // a proof cut before the nonce is rejected, not read past its end
+        let mut transcript = Transcript::new();
+        proof.idx = 3;
+        proof.bytes.truncate(3 + 7);
+        assert!(!transcript.verify_grind(&mut proof, 12));
+    }
 }
@@ -1,3 +1,9 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
 use rs_merkle::{Hasher, MerkleProof, MerkleTree};
 
 #[derive(Debug, Clone)]
@@ -51,6 +57,121 @@ impl MerkleTreeProver {
     }
 }
 
+// same tree with every layer kept in a file, for commitments too large for memory.
+// openings match MerkleTreeProver byte for byte; the file is removed on drop
+pub struct DiskMerkleTree {
+    file: File,
+    path: PathBuf,
+    leave_num: usize,
+    root: [u8; HASH_SIZE],
+}
+
+impl DiskMerkleTree {
+    const CHUNK: usize = 1 << 16;
+
+    // leaf number should be 2^n
+    pub fn new(path: PathBuf, leaf_values: impl Iterator<Item = Vec<u8>>) -> Self {
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        let mut writer = BufWriter::new(&file);
+        let mut leave_num = 0usize;
+        for leaf in leaf_values {
+            writer.write_all(&Blake3Algorithm::hash(&leaf)).unwrap();
+            leave_num += 1;
+        }
+        writer.flush().unwrap();
+        drop(writer);
+        assert!(leave_num.is_power_of_two());
+
+        let mut offset = 0;
+        let mut layer_len = leave_num;
+        let mut root = [0u8; HASH_SIZE];
+        let mut buffer = vec![0u8; Self::CHUNK.min(leave_num) * HASH_SIZE];
+        while layer_len > 1 {
+            let next_offset = offset + layer_len;
+            for start in (0..layer_len).step_by(Self::CHUNK) {
+                let len = Self::CHUNK.min(layer_len - start);
+                file.seek(SeekFrom::Start(((offset + start) * HASH_SIZE) as u64))
+                    .unwrap();
+                file.read_exact(&mut buffer[..len * HASH_SIZE]).unwrap();
+                let parents = buffer[..len * HASH_SIZE]
+                    .chunks(HASH_SIZE * 2)
+                    .flat_map(|x| {
+                        let left: [u8; HASH_SIZE] = x[..HASH_SIZE].try_into().unwrap();
+                        let right: [u8; HASH_SIZE] = x[HASH_SIZE..].try_into().unwrap();
+                        Blake3Algorithm::concat_and_hash(&left, Some(&right))
+                    })
+                    .collect::<Vec<_>>();
+                file.seek(SeekFrom::Start(
+                    ((next_offset + start / 2) * HASH_SIZE) as u64,
+                ))
+                .unwrap();
+                file.write_all(&parents).unwrap();
+                if layer_len == 2 {
+                    root.copy_from_slice(&parents);
+                }
+            }
+            offset = next_offset;
+            layer_len /= 2;
+        }
+        if leave_num == 1 {
+            file.seek(SeekFrom::Start(0)).unwrap();
+            file.read_exact(&mut root).unwrap();
+        }
+        Self {
+            file,
+            path,
+            leave_num,
+            root,
+        }
+    }
+
+    pub fn leave_num(&self) -> usize {
+        self.leave_num
+    }
+
+    pub fn commit(&self) -> [u8; HASH_SIZE] {
+        self.root
+    }
+
+    pub fn open(&self, leaf_indices: &Vec<usize>) -> Vec<u8> {
+        let mut res = vec![];
+        let mut current_layer_indices = leaf_indices.to_vec();
+        let mut offset = 0;
+        let mut layer_len = self.leave_num;
+        let mut file = &self.file;
+        while layer_len > 1 {
+            let siblings = current_layer_indices
+                .iter()
+                .map(|index| index ^ 1)
+                .collect::<Vec<_>>();
+            for i in MerkleTreeVerifier::difference(&siblings, &current_layer_indices) {
+                let mut hash = [0u8; HASH_SIZE];
+                file.seek(SeekFrom::Start(((offset + i) * HASH_SIZE) as u64))
+                    .unwrap();
+                file.read_exact(&mut hash).unwrap();
+                res.extend_from_slice(&hash);
+            }
+            current_layer_indices.iter_mut().for_each(|x| *x >>= 1);
+            current_layer_indices.dedup();
+            offset += layer_len;
+            layer_len /= 2;
+        }
+        res
+    }
+}
+
+impl Drop for DiskMerkleTree {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
 impl MerkleTreeVerifier {
     pub fn new(leave_number: usize, merkle_root: [u8; HASH_SIZE]) -> Self {
         Self {
@@ -86,7 +207,10 @@ impl MerkleTreeVerifier {
         indices: &Vec<usize>,
         leaves: &Vec<Vec<u8>>,
     ) -> bool {
-        let proof = MerkleProof::<Blake3Algorithm>::try_from(proof_bytes).unwrap();
+        // malformed proof bytes are rejected, not a panic
+        let Ok(proof) = MerkleProof::<Blake3Algorithm>::try_from(proof_bytes) else {
+            return false;
+        };
         let leaves_to_prove: Vec<[u8; HASH_SIZE]> =
             leaves.iter().map(|x| Blake3Algorithm::hash(x)).collect();
         proof.verify(
@@ -116,32 +240,22 @@ impl MerkleRoot {
 
 #[cfg(test)]
 mod tests {
-    use arithmetic::field::{goldilocks64::Goldilocks64, Field};
+    use arithmetic::{field::as_bytes_vec, test_utils::gen_params_n_ctx};
+    use seal_fhe::{BFVEncoder, Plaintext};
 
     use super::*;
 
-    fn as_bytes_vec<F: Field>(v: &[F]) -> Vec<u8> {
-        let mut res = vec![0u8; F::SIZE * v.len()];
-        let mut cnt = 0;
-        for i in v {
-            i.serialize_into(&mut res[cnt..]);
-            cnt += F::SIZE;
-        }
-        res
-    }
-
     #[test]
     fn commit_and_open() {
-        let leaf_values = vec![
-            as_bytes_vec(&[Goldilocks64::from(1u32), Goldilocks64::from(2u32)]),
-            as_bytes_vec(&[Goldilocks64::from(3u32), Goldilocks64::from(4u32)]),
-            as_bytes_vec(&[Goldilocks64::from(5u32), Goldilocks64::from(6u32)]),
-            as_bytes_vec(&[Goldilocks64::from(7u32), Goldilocks64::from(8u32)]),
-            as_bytes_vec(&[Goldilocks64::from(9u32), Goldilocks64::from(10u32)]),
-            as_bytes_vec(&[Goldilocks64::from(11u32), Goldilocks64::from(12u32)]),
-            as_bytes_vec(&[Goldilocks64::from(13u32), Goldilocks64::from(14u32)]),
-            as_bytes_vec(&[Goldilocks64::from(15u32), Goldilocks64::from(16u32)]),
-        ];
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+        let pair = |i: u64| {
+            as_bytes_vec(
+                &[Plaintext::from_int(i, &encoder), Plaintext::from_int(i + 1, &encoder)],
+                &encoder,
+            )
+        };
+        let leaf_values = (0..8).map(|i| pair(2 * i + 1)).collect::<Vec<_>>();
         let leave_number = leaf_values.len();
         let prover = MerkleTreeProver::new(leaf_values);
         let root = prover.commit();
@@ -149,11 +263,32 @@ mod tests {
         let leaf_indices = vec![2, 3, 4];
         let proof_bytes = prover.open(&leaf_indices);
         assert_eq!(proof_bytes.len(), verifier.proof_length(&leaf_indices));
-        let open_values = vec![
-            as_bytes_vec(&[Goldilocks64::from(5u32), Goldilocks64::from(6u32)]),
-            as_bytes_vec(&[Goldilocks64::from(7u32), Goldilocks64::from(8u32)]),
-            as_bytes_vec(&[Goldilocks64::from(9u32), Goldilocks64::from(10u32)]),
-        ];
-        assert!(verifier.verify(proof_bytes, &leaf_indices, &open_values));
+        let open_values = vec![pair(5), pair(7), pair(9)];
+        assert!(verifier.verify(proof_bytes.clone(), &leaf_indices, &open_values));
+        let open_values = vec![pair(5), pair(7), pair(10)];
+        assert!(!verifier.verify(proof_bytes, &leaf_indices, &open_values));
+    }
+
+    #[test]
+    fn disk_tree() {
+        let (params, ctx) = gen_params_n_ctx();
+        let encoder = BFVEncoder::new(&ctx, &params).unwrap();
+        let leaf_values = (0..64u64)
+            .map(|i| {
+                as_bytes_vec(
+                    &[Plaintext::from_int(i, &encoder), Plaintext::from_int(i * i, &encoder)],
+                    &encoder,
+                )
+            })
+            .collect::<Vec<_>>();
+        let prover = MerkleTreeProver::new(leaf_values.clone());
+        let path = std::env::temp_dir().join(format!("disk_tree-{}", std::process::id()));
+        let disk_prover = DiskMerkleTree::new(path.clone(), leaf_values.into_iter());
+        assert_eq!(disk_prover.commit(), prover.commit());
+        for leaf_indices in [vec![0], vec![5, 6, 7, 40], vec![63]] {
+            assert_eq!(disk_prover.open(&leaf_indices), prover.open(&leaf_indices));
+        }
+        drop(disk_prover);
+        assert!(!path.exists());
     }
 }